@@ -0,0 +1,64 @@
+/* Prints the damaged files of an install directory and what it needs to download:
+   example INDEX_FILE INSTALL_DIR */
+#include <inttypes.h>
+#include <stdio.h>
+
+#include "ryzom_patch_info.h"
+
+static bool progress(void *user_data, size_t done, size_t total, const char *name)
+{
+    (void)user_data;
+    fprintf(stderr, "\r%zu/%zu %s", done, total, name);
+    return true;
+}
+
+int main(int argc, char **argv)
+{
+    RpiIndex *index = NULL;
+    RpiPlan *plan = NULL;
+    RpiVerification *verification = NULL;
+
+    if (argc != 3) {
+        fprintf(stderr, "usage: %s INDEX_FILE INSTALL_DIR\n", argv[0]);
+        return 2;
+    }
+    if (rpi_index_open(argv[1], false, RPI_ENCODING_UTF8, &index) != RPI_STATUS_OK) {
+        fprintf(stderr, "%s\n", rpi_last_error());
+        return 1;
+    }
+    for (size_t i = 0; i < rpi_index_issue_count(index); i++) {
+        fprintf(stderr, "warning: %s\n", rpi_index_issue(index, i));
+    }
+    if (rpi_verify(index, argv[2], false, progress, NULL, &verification) != RPI_STATUS_OK) {
+        fprintf(stderr, "\n%s\n", rpi_last_error());
+        rpi_index_free(index);
+        return 1;
+    }
+    fprintf(stderr, "\n");
+    for (size_t i = 0; i < rpi_verification_count(verification); i++) {
+        RpiMismatch mismatch;
+        rpi_verification_mismatch(verification, i, &mismatch);
+        if (!mismatch.outdated) {
+            printf("%s is damaged\n", mismatch.path);
+        }
+    }
+    rpi_verification_free(verification);
+
+    if (rpi_plan_create(index, argv[2], false, progress, NULL, &plan) != RPI_STATUS_OK) {
+        fprintf(stderr, "\n%s\n", rpi_last_error());
+        rpi_index_free(index);
+        return 1;
+    }
+    fprintf(stderr, "\n");
+
+    for (size_t i = 0; i < rpi_plan_count(plan); i++) {
+        RpiUpdate update;
+        rpi_plan_update(plan, i, &update);
+        printf("%s %" PRIu32 " bytes\n", update.path, update.download);
+    }
+    printf("%" PRIu64 " bytes in total\n", rpi_plan_download(plan));
+
+    rpi_plan_free(plan);
+    rpi_index_free(index);
+    return 0;
+}
@@ -0,0 +1,283 @@
+/* Generated from capi/src/lib.rs by cbindgen when the crate is built, do not edit. */
+
+#ifndef RYZOM_PATCH_INFO_H
+#define RYZOM_PATCH_INFO_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum RpiStatus {
+  RPI_STATUS_OK = 0,
+  /**
+   * A null pointer or a string that is not UTF-8
+   */
+  RPI_STATUS_INVALID_ARGUMENT,
+  /**
+   * An index, file or category past the end
+   */
+  RPI_STATUS_OUT_OF_RANGE,
+  /**
+   * The index file can not be read or decoded
+   */
+  RPI_STATUS_INVALID_INDEX,
+  /**
+   * A file of the install can not be read
+   */
+  RPI_STATUS_IO,
+  /**
+   * The progress callback returned false
+   */
+  RPI_STATUS_CANCELLED,
+  /**
+   * A bug of the library, the call had no effect
+   */
+  RPI_STATUS_PANIC,
+} RpiStatus;
+
+/**
+ * How the names of an index file are decoded, passed as a `uint32_t` since C may hold any value
+ */
+typedef enum RpiEncoding {
+  RPI_ENCODING_UTF8 = 0,
+  /**
+   * Invalid UTF-8 sequences are replaced with U+FFFD
+   */
+  RPI_ENCODING_LOSSY,
+  /**
+   * Older NeL tools wrote Latin-1 names
+   */
+  RPI_ENCODING_LATIN1,
+} RpiEncoding;
+
+/**
+ * A product description read from an index file, inconsistencies are repaired and listed by
+ * [`rpi_index_issue_count`]
+ */
+typedef struct RpiIndex RpiIndex;
+
+/**
+ * The files of an install that are missing or not at their latest version
+ */
+typedef struct RpiPlan RpiPlan;
+
+/**
+ * The installed files whose hash is not the one of their latest version
+ */
+typedef struct RpiVerification RpiVerification;
+
+typedef struct RpiFile {
+  const char *name;
+  size_t version_count;
+} RpiFile;
+
+typedef struct RpiVersion {
+  uint32_t number;
+  uint32_t size;
+  /**
+   * Size of the LZMA packed file
+   */
+  uint32_t packed_size;
+  /**
+   * Seconds since the Unix epoch
+   */
+  uint32_t time;
+  /**
+   * Size of the patch from the previous version, 0 when there is none
+   */
+  uint32_t patch_size;
+  /**
+   * The SHA-1 digest as NeL stores it, usually five little endian words
+   */
+  const uint32_t *hash_key;
+  size_t hash_key_len;
+} RpiVersion;
+
+typedef struct RpiCategory {
+  const char *name;
+  bool optional;
+  bool incremental;
+  bool hidden;
+  /**
+   * Directory the files are unpacked to, null for the data directory
+   */
+  const char *unpack_to;
+  /**
+   * Category to install first, or null
+   */
+  const char *required;
+  size_t file_count;
+} RpiCategory;
+
+/**
+ * Called before every file of a plan or verification with the number of files checked, their
+ * total and the name of the file, and once more with an empty name when it is done. Returning
+ * false cancels it.
+ */
+typedef bool (*RpiProgress)(void *user_data, size_t done, size_t total, const char *name);
+
+typedef struct RpiUpdate {
+  /**
+   * Index of the file for [`rpi_index_file`]
+   */
+  size_t file;
+  /**
+   * Where the file is installed
+   */
+  const char *path;
+  /**
+   * Whether `from` is the version found on disk, otherwise the whole file is downloaded
+   */
+  bool installed;
+  uint32_t from;
+  uint32_t to;
+  /**
+   * Bytes to download
+   */
+  uint32_t download;
+} RpiUpdate;
+
+typedef struct RpiMismatch {
+  /**
+   * Index of the file for [`rpi_index_file`]
+   */
+  size_t file;
+  /**
+   * Where the file is installed
+   */
+  const char *path;
+  /**
+   * Whether `version` is the older version the file matches, otherwise it is damaged
+   */
+  bool outdated;
+  uint32_t version;
+} RpiMismatch;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Describes the last failure of the calling thread, the string is valid until its next failure
+ */
+const char *rpi_last_error(void);
+
+/**
+ * Reads an index file, in lenient mode broken parts are skipped and listed as warnings.
+ * `encoding` is one of the [`RpiEncoding`] values.
+ */
+enum RpiStatus rpi_index_open(const char *path,
+                              bool lenient,
+                              uint32_t encoding,
+                              struct RpiIndex **index);
+
+/**
+ * Reads an index file from memory, e.g. as downloaded from the patch server
+ */
+enum RpiStatus rpi_index_read(const uint8_t *data,
+                              size_t len,
+                              bool lenient,
+                              uint32_t encoding,
+                              struct RpiIndex **index);
+
+void rpi_index_free(struct RpiIndex *index);
+
+size_t rpi_index_file_count(const struct RpiIndex *index);
+
+enum RpiStatus rpi_index_file(const struct RpiIndex *index, size_t file, struct RpiFile *out);
+
+/**
+ * Versions are in ascending order, the last one is the latest
+ */
+enum RpiStatus rpi_index_version(const struct RpiIndex *index,
+                                 size_t file,
+                                 size_t version,
+                                 struct RpiVersion *out);
+
+size_t rpi_index_category_count(const struct RpiIndex *index);
+
+enum RpiStatus rpi_index_category(const struct RpiIndex *index,
+                                  size_t category,
+                                  struct RpiCategory *out);
+
+/**
+ * Writes the index of the `member`th file of a category, for [`rpi_index_file`]
+ */
+enum RpiStatus rpi_index_category_file(const struct RpiIndex *index,
+                                       size_t category,
+                                       size_t member,
+                                       size_t *file);
+
+/**
+ * Returns the number of inconsistencies found in the index itself, 0 for a null index. They were
+ * repaired when it was read: duplicate files and categories, versions out of order and unknown
+ * category members. Installed files are checked by [`rpi_verify`] instead.
+ */
+size_t rpi_index_issue_count(const struct RpiIndex *index);
+
+/**
+ * Describes an inconsistency counted by [`rpi_index_issue_count`], null past the end
+ */
+const char *rpi_index_issue(const struct RpiIndex *index, size_t issue);
+
+/**
+ * Number of broken parts skipped by lenient reading
+ */
+size_t rpi_index_warning_count(const struct RpiIndex *index);
+
+/**
+ * Describes a broken part skipped by lenient reading, null past the end
+ */
+const char *rpi_index_warning(const struct RpiIndex *index, size_t warning);
+
+/**
+ * Compares the install directory with the latest versions of the index. Files are installed in
+ * the `UnpackTo` directory of their category or in `data`, and recognized by their SHA-1 hash.
+ * Files that are only in optional categories are skipped unless `optional` is set. `progress`
+ * may be null.
+ */
+enum RpiStatus rpi_plan_create(const struct RpiIndex *index,
+                               const char *install_dir,
+                               bool optional,
+                               RpiProgress progress,
+                               void *user_data,
+                               struct RpiPlan **plan);
+
+void rpi_plan_free(struct RpiPlan *plan);
+
+size_t rpi_plan_count(const struct RpiPlan *plan);
+
+/**
+ * Bytes to download for the whole plan
+ */
+uint64_t rpi_plan_download(const struct RpiPlan *plan);
+
+enum RpiStatus rpi_plan_update(const struct RpiPlan *plan, size_t update, struct RpiUpdate *out);
+
+/**
+ * Hashes every installed file of the index, whatever its size, and lists those that are not at
+ * their latest version, see [`rpi_plan_create`] for the files checked. Missing files are left to
+ * the plan. `progress` may be null.
+ */
+enum RpiStatus rpi_verify(const struct RpiIndex *index,
+                          const char *install_dir,
+                          bool optional,
+                          RpiProgress progress,
+                          void *user_data,
+                          struct RpiVerification **verification);
+
+void rpi_verification_free(struct RpiVerification *verification);
+
+size_t rpi_verification_count(const struct RpiVerification *verification);
+
+enum RpiStatus rpi_verification_mismatch(const struct RpiVerification *verification,
+                                         size_t mismatch,
+                                         struct RpiMismatch *out);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RYZOM_PATCH_INFO_H */
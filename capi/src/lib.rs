@@ -0,0 +1,872 @@
+//! C API for launchers: opens an index, lists its files, versions and categories, plans the
+//! update of an install directory, verifies the hashes of its files and reports inconsistencies.
+//! `include/ryzom_patch_info.h` is generated from this file by the build.
+//!
+//! Functions return an [`RpiStatus`] instead of panicking, [`rpi_last_error`] describes the last
+//! failure of the calling thread. Pointers passed in must be null or valid, strings are null
+//! terminated UTF-8. Strings and arrays returned by an index, a plan or a verification live as
+//! long as it does.
+
+// every function documents the pointers it accepts in the crate documentation above
+#![allow(clippy::missing_safety_doc)]
+
+use ryzom_patch_info::error::ReadingError;
+use ryzom_patch_info::{patch, pd, product, update};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::ops::ControlFlow;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpiStatus {
+    Ok = 0,
+    /// A null pointer or a string that is not UTF-8
+    InvalidArgument,
+    /// An index, file or category past the end
+    OutOfRange,
+    /// The index file can not be read or decoded
+    InvalidIndex,
+    /// A file of the install can not be read
+    Io,
+    /// The progress callback returned false
+    Cancelled,
+    /// A bug of the library, the call had no effect
+    Panic,
+}
+
+/// How the names of an index file are decoded, passed as a `uint32_t` since C may hold any value
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpiEncoding {
+    Utf8 = 0,
+    /// Invalid UTF-8 sequences are replaced with U+FFFD
+    Lossy,
+    /// Older NeL tools wrote Latin-1 names
+    Latin1,
+}
+
+impl From<RpiEncoding> for pd::Encoding {
+    fn from(encoding: RpiEncoding) -> pd::Encoding {
+        match encoding {
+            RpiEncoding::Utf8 => pd::Encoding::Utf8,
+            RpiEncoding::Lossy => pd::Encoding::Lossy,
+            RpiEncoding::Latin1 => pd::Encoding::Latin1,
+        }
+    }
+}
+
+impl RpiEncoding {
+    /// The encoding a value from C stands for, unknown values are rejected
+    fn decode(value: u32) -> Result<pd::Encoding, Failure> {
+        [RpiEncoding::Utf8, RpiEncoding::Lossy, RpiEncoding::Latin1]
+            .into_iter()
+            .find(|&encoding| encoding as u32 == value)
+            .map(pd::Encoding::from)
+            .ok_or_else(|| {
+                Failure::new(
+                    RpiStatus::InvalidArgument,
+                    format!("there is no encoding {}", value),
+                )
+            })
+    }
+}
+
+#[repr(C)]
+pub struct RpiFile {
+    pub name: *const c_char,
+    pub version_count: usize,
+}
+
+#[repr(C)]
+pub struct RpiVersion {
+    pub number: u32,
+    pub size: u32,
+    /// Size of the LZMA packed file
+    pub packed_size: u32,
+    /// Seconds since the Unix epoch
+    pub time: u32,
+    /// Size of the patch from the previous version, 0 when there is none
+    pub patch_size: u32,
+    /// The SHA-1 digest as NeL stores it, usually five little endian words
+    pub hash_key: *const u32,
+    pub hash_key_len: usize,
+}
+
+#[repr(C)]
+pub struct RpiCategory {
+    pub name: *const c_char,
+    pub optional: bool,
+    pub incremental: bool,
+    pub hidden: bool,
+    /// Directory the files are unpacked to, null for the data directory
+    pub unpack_to: *const c_char,
+    /// Category to install first, or null
+    pub required: *const c_char,
+    pub file_count: usize,
+}
+
+#[repr(C)]
+pub struct RpiUpdate {
+    /// Index of the file for [`rpi_index_file`]
+    pub file: usize,
+    /// Where the file is installed
+    pub path: *const c_char,
+    /// Whether `from` is the version found on disk, otherwise the whole file is downloaded
+    pub installed: bool,
+    pub from: u32,
+    pub to: u32,
+    /// Bytes to download
+    pub download: u32,
+}
+
+#[repr(C)]
+pub struct RpiMismatch {
+    /// Index of the file for [`rpi_index_file`]
+    pub file: usize,
+    /// Where the file is installed
+    pub path: *const c_char,
+    /// Whether `version` is the older version the file matches, otherwise it is damaged
+    pub outdated: bool,
+    pub version: u32,
+}
+
+/// Called before every file of a plan or verification with the number of files checked, their
+/// total and the name of the file, and once more with an empty name when it is done. Returning
+/// false cancels it.
+pub type RpiProgress = Option<
+    unsafe extern "C" fn(
+        user_data: *mut c_void,
+        done: usize,
+        total: usize,
+        name: *const c_char,
+    ) -> bool,
+>;
+
+struct CategoryStrings {
+    name: CString,
+    unpack_to: Option<CString>,
+    required: Option<CString>,
+    files: Vec<usize>,
+}
+
+/// A product description read from an index file, inconsistencies are repaired and listed by
+/// [`rpi_index_issue_count`]
+pub struct RpiIndex {
+    product: product::ProductDescription,
+    names: Vec<CString>,
+    categories: Vec<CategoryStrings>,
+    issues: Vec<CString>,
+    warnings: Vec<CString>,
+}
+
+/// The files of an install that are missing or not at their latest version
+pub struct RpiPlan {
+    updates: Vec<update::Update>,
+    paths: Vec<CString>,
+}
+
+/// The installed files whose hash is not the one of their latest version
+pub struct RpiVerification {
+    mismatches: Vec<update::Mismatch>,
+    paths: Vec<CString>,
+}
+
+struct Failure {
+    status: RpiStatus,
+    message: String,
+}
+
+impl Failure {
+    fn new(status: RpiStatus, message: impl ToString) -> Failure {
+        Failure {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<ReadingError> for Failure {
+    fn from(error: ReadingError) -> Failure {
+        Failure::new(RpiStatus::InvalidIndex, error)
+    }
+}
+
+impl From<update::PlanError> for Failure {
+    fn from(error: update::PlanError) -> Failure {
+        let status = match error {
+            update::PlanError::Cancelled => RpiStatus::Cancelled,
+            update::PlanError::Io(..) => RpiStatus::Io,
+        };
+        Failure::new(status, error)
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Strings of the index are null terminated in the file and can not hold a null byte
+fn c_string(value: impl Into<Vec<u8>>) -> CString {
+    let mut bytes = value.into();
+    bytes.retain(|&byte| byte != 0);
+    CString::new(bytes).expect("null bytes are removed")
+}
+
+/// Runs `f`, turning its failure or panic into a status and the last error
+fn guard(f: impl FnOnce() -> Result<(), Failure>) -> RpiStatus {
+    let failure = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return RpiStatus::Ok,
+        Ok(Err(failure)) => failure,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Failure::new(RpiStatus::Panic, format!("internal error: {}", message))
+        }
+    };
+    LAST_ERROR.with(|last| *last.borrow_mut() = c_string(failure.message));
+
+    failure.status
+}
+
+unsafe fn reference<'a, T>(pointer: *const T, what: &str) -> Result<&'a T, Failure> {
+    pointer
+        .as_ref()
+        .ok_or_else(|| Failure::new(RpiStatus::InvalidArgument, format!("{} is null", what)))
+}
+
+unsafe fn string<'a>(pointer: *const c_char, what: &str) -> Result<&'a str, Failure> {
+    if pointer.is_null() {
+        return Err(Failure::new(
+            RpiStatus::InvalidArgument,
+            format!("{} is null", what),
+        ));
+    }
+    CStr::from_ptr(pointer)
+        .to_str()
+        .map_err(|_| Failure::new(RpiStatus::InvalidArgument, format!("{} is not UTF-8", what)))
+}
+
+unsafe fn write<T>(pointer: *mut T, value: T) -> Result<(), Failure> {
+    if pointer.is_null() {
+        return Err(Failure::new(
+            RpiStatus::InvalidArgument,
+            "the output is null",
+        ));
+    }
+    pointer.write(value);
+
+    Ok(())
+}
+
+/// The progress callback of the library, a null `progress` never cancels
+fn reporter(
+    progress: RpiProgress,
+    user_data: *mut c_void,
+) -> impl FnMut(usize, usize, &str) -> ControlFlow<()> {
+    move |done, total, name| match progress {
+        Some(progress) if !unsafe { progress(user_data, done, total, c_string(name).as_ptr()) } => {
+            ControlFlow::Break(())
+        }
+        _ => ControlFlow::Continue(()),
+    }
+}
+
+fn out_of_range(what: &str, index: usize) -> Failure {
+    Failure::new(
+        RpiStatus::OutOfRange,
+        format!("there is no {} {}", what, index),
+    )
+}
+
+impl RpiIndex {
+    fn read(data: &[u8], lenient: bool, encoding: u32) -> Result<RpiIndex, Failure> {
+        let encoding = RpiEncoding::decode(encoding)?;
+        let mut pdr = pd::read_index_file(data, lenient, pd::Limits::default(), encoding)?;
+        pdr.lenient = lenient;
+        let raw = patch::CProductDescriptionForClient::from(&mut pdr)?;
+        let (product, repairs) = product::ProductDescription::repaired(raw);
+
+        let categories = product
+            .categories()
+            .iter()
+            .map(|category| CategoryStrings {
+                name: c_string(category.name.as_str()),
+                unpack_to: category.unpack_to.as_deref().map(c_string),
+                required: category.required.as_deref().map(c_string),
+                files: product
+                    .category_files(category)
+                    .filter_map(|file| product.file_id(&file.name))
+                    .map(product::FileId::index)
+                    .collect(),
+            })
+            .collect();
+
+        Ok(RpiIndex {
+            names: product
+                .files()
+                .iter()
+                .map(|file| c_string(file.name.as_str()))
+                .collect(),
+            categories,
+            issues: repairs
+                .iter()
+                .map(|repair| c_string(repair.to_string()))
+                .collect(),
+            warnings: pdr
+                .warnings
+                .iter()
+                .map(|warning| c_string(warning.to_string()))
+                .collect(),
+            product,
+        })
+    }
+}
+
+/// Describes the last failure of the calling thread, the string is valid until its next failure
+#[no_mangle]
+pub extern "C" fn rpi_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+/// Reads an index file, in lenient mode broken parts are skipped and listed as warnings.
+/// `encoding` is one of the [`RpiEncoding`] values.
+#[no_mangle]
+pub unsafe extern "C" fn rpi_index_open(
+    path: *const c_char,
+    lenient: bool,
+    encoding: u32,
+    index: *mut *mut RpiIndex,
+) -> RpiStatus {
+    guard(|| {
+        let path = string(path, "the path")?;
+        let data = std::fs::read(path).map_err(|error| ReadingError::from(error).in_file(path))?;
+        let read = RpiIndex::read(&data, lenient, encoding)?;
+        write(index, Box::into_raw(Box::new(read)))
+    })
+}
+
+/// Reads an index file from memory, e.g. as downloaded from the patch server
+#[no_mangle]
+pub unsafe extern "C" fn rpi_index_read(
+    data: *const u8,
+    len: usize,
+    lenient: bool,
+    encoding: u32,
+    index: *mut *mut RpiIndex,
+) -> RpiStatus {
+    guard(|| {
+        let data = match len {
+            0 => &[],
+            _ => std::slice::from_raw_parts(reference(data, "the data")?, len),
+        };
+        let read = RpiIndex::read(data, lenient, encoding)?;
+        write(index, Box::into_raw(Box::new(read)))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rpi_index_free(index: *mut RpiIndex) {
+    if !index.is_null() {
+        drop(Box::from_raw(index));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rpi_index_file_count(index: *const RpiIndex) -> usize {
+    index.as_ref().map_or(0, |index| index.names.len())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rpi_index_file(
+    index: *const RpiIndex,
+    file: usize,
+    out: *mut RpiFile,
+) -> RpiStatus {
+    guard(|| {
+        let index = reference(index, "the index")?;
+        let entry = index
+            .product
+            .files()
+            .get(file)
+            .ok_or_else(|| out_of_range("file", file))?;
+        write(
+            out,
+            RpiFile {
+                name: index.names[file].as_ptr(),
+                version_count: entry.versions.len(),
+            },
+        )
+    })
+}
+
+/// Versions are in ascending order, the last one is the latest
+#[no_mangle]
+pub unsafe extern "C" fn rpi_index_version(
+    index: *const RpiIndex,
+    file: usize,
+    version: usize,
+    out: *mut RpiVersion,
+) -> RpiStatus {
+    guard(|| {
+        let index = reference(index, "the index")?;
+        let entry = index
+            .product
+            .files()
+            .get(file)
+            .ok_or_else(|| out_of_range("file", file))?;
+        let found = entry
+            .versions
+            .get(version)
+            .ok_or_else(|| out_of_range("version", version))?;
+        write(
+            out,
+            RpiVersion {
+                number: found.number,
+                size: found.size.0,
+                packed_size: found.packed_size.0,
+                time: found.time.0,
+                patch_size: found.patch_size.0,
+                hash_key: found.hash_key.as_ptr(),
+                hash_key_len: found.hash_key.len(),
+            },
+        )
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rpi_index_category_count(index: *const RpiIndex) -> usize {
+    index.as_ref().map_or(0, |index| index.categories.len())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rpi_index_category(
+    index: *const RpiIndex,
+    category: usize,
+    out: *mut RpiCategory,
+) -> RpiStatus {
+    guard(|| {
+        let index = reference(index, "the index")?;
+        let strings = index
+            .categories
+            .get(category)
+            .ok_or_else(|| out_of_range("category", category))?;
+        let found = &index.product.categories()[category];
+        let pointer = |value: &Option<CString>| value.as_ref().map_or(ptr::null(), |v| v.as_ptr());
+        write(
+            out,
+            RpiCategory {
+                name: strings.name.as_ptr(),
+                optional: found.optional,
+                incremental: found.incremental,
+                hidden: found.hidden,
+                unpack_to: pointer(&strings.unpack_to),
+                required: pointer(&strings.required),
+                file_count: strings.files.len(),
+            },
+        )
+    })
+}
+
+/// Writes the index of the `member`th file of a category, for [`rpi_index_file`]
+#[no_mangle]
+pub unsafe extern "C" fn rpi_index_category_file(
+    index: *const RpiIndex,
+    category: usize,
+    member: usize,
+    file: *mut usize,
+) -> RpiStatus {
+    guard(|| {
+        let index = reference(index, "the index")?;
+        let strings = index
+            .categories
+            .get(category)
+            .ok_or_else(|| out_of_range("category", category))?;
+        let found = strings
+            .files
+            .get(member)
+            .ok_or_else(|| out_of_range("member", member))?;
+        write(file, *found)
+    })
+}
+
+/// Returns the number of inconsistencies found in the index itself, 0 for a null index. They were
+/// repaired when it was read: duplicate files and categories, versions out of order and unknown
+/// category members. Installed files are checked by [`rpi_verify`] instead.
+#[no_mangle]
+pub unsafe extern "C" fn rpi_index_issue_count(index: *const RpiIndex) -> usize {
+    index.as_ref().map_or(0, |index| index.issues.len())
+}
+
+/// Describes an inconsistency counted by [`rpi_index_issue_count`], null past the end
+#[no_mangle]
+pub unsafe extern "C" fn rpi_index_issue(index: *const RpiIndex, issue: usize) -> *const c_char {
+    index
+        .as_ref()
+        .and_then(|index| index.issues.get(issue))
+        .map_or(ptr::null(), |issue| issue.as_ptr())
+}
+
+/// Number of broken parts skipped by lenient reading
+#[no_mangle]
+pub unsafe extern "C" fn rpi_index_warning_count(index: *const RpiIndex) -> usize {
+    index.as_ref().map_or(0, |index| index.warnings.len())
+}
+
+/// Describes a broken part skipped by lenient reading, null past the end
+#[no_mangle]
+pub unsafe extern "C" fn rpi_index_warning(
+    index: *const RpiIndex,
+    warning: usize,
+) -> *const c_char {
+    index
+        .as_ref()
+        .and_then(|index| index.warnings.get(warning))
+        .map_or(ptr::null(), |warning| warning.as_ptr())
+}
+
+/// Compares the install directory with the latest versions of the index. Files are installed in
+/// the `UnpackTo` directory of their category or in `data`, and recognized by their SHA-1 hash.
+/// Files that are only in optional categories are skipped unless `optional` is set. `progress`
+/// may be null.
+#[no_mangle]
+pub unsafe extern "C" fn rpi_plan_create(
+    index: *const RpiIndex,
+    install_dir: *const c_char,
+    optional: bool,
+    progress: RpiProgress,
+    user_data: *mut c_void,
+    plan: *mut *mut RpiPlan,
+) -> RpiStatus {
+    guard(|| {
+        let index = reference(index, "the index")?;
+        let install_dir = string(install_dir, "the install directory")?;
+        let report = reporter(progress, user_data);
+        let updates = update::plan(&index.product, Path::new(install_dir), optional, report)?;
+
+        let created = RpiPlan {
+            paths: updates
+                .iter()
+                .map(|update| c_string(update.path.to_string_lossy().as_bytes()))
+                .collect(),
+            updates,
+        };
+        write(plan, Box::into_raw(Box::new(created)))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rpi_plan_free(plan: *mut RpiPlan) {
+    if !plan.is_null() {
+        drop(Box::from_raw(plan));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rpi_plan_count(plan: *const RpiPlan) -> usize {
+    plan.as_ref().map_or(0, |plan| plan.updates.len())
+}
+
+/// Bytes to download for the whole plan
+#[no_mangle]
+pub unsafe extern "C" fn rpi_plan_download(plan: *const RpiPlan) -> u64 {
+    plan.as_ref().map_or(0, |plan| {
+        plan.updates
+            .iter()
+            .map(|update| update.download.0 as u64)
+            .sum()
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rpi_plan_update(
+    plan: *const RpiPlan,
+    update: usize,
+    out: *mut RpiUpdate,
+) -> RpiStatus {
+    guard(|| {
+        let plan = reference(plan, "the plan")?;
+        let found = plan
+            .updates
+            .get(update)
+            .ok_or_else(|| out_of_range("update", update))?;
+        write(
+            out,
+            RpiUpdate {
+                file: found.file.index(),
+                path: plan.paths[update].as_ptr(),
+                installed: found.from.is_some(),
+                from: found.from.unwrap_or(0),
+                to: found.to,
+                download: found.download.0,
+            },
+        )
+    })
+}
+
+/// Hashes every installed file of the index, whatever its size, and lists those that are not at
+/// their latest version, see [`rpi_plan_create`] for the files checked. Missing files are left to
+/// the plan. `progress` may be null.
+#[no_mangle]
+pub unsafe extern "C" fn rpi_verify(
+    index: *const RpiIndex,
+    install_dir: *const c_char,
+    optional: bool,
+    progress: RpiProgress,
+    user_data: *mut c_void,
+    verification: *mut *mut RpiVerification,
+) -> RpiStatus {
+    guard(|| {
+        let index = reference(index, "the index")?;
+        let install_dir = string(install_dir, "the install directory")?;
+        let report = reporter(progress, user_data);
+        let mismatches = update::verify(&index.product, Path::new(install_dir), optional, report)?;
+
+        let created = RpiVerification {
+            paths: mismatches
+                .iter()
+                .map(|mismatch| c_string(mismatch.path.to_string_lossy().as_bytes()))
+                .collect(),
+            mismatches,
+        };
+        write(verification, Box::into_raw(Box::new(created)))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rpi_verification_free(verification: *mut RpiVerification) {
+    if !verification.is_null() {
+        drop(Box::from_raw(verification));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rpi_verification_count(verification: *const RpiVerification) -> usize {
+    verification
+        .as_ref()
+        .map_or(0, |verification| verification.mismatches.len())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rpi_verification_mismatch(
+    verification: *const RpiVerification,
+    mismatch: usize,
+    out: *mut RpiMismatch,
+) -> RpiStatus {
+    guard(|| {
+        let verification = reference(verification, "the verification")?;
+        let found = verification
+            .mismatches
+            .get(mismatch)
+            .ok_or_else(|| out_of_range("mismatch", mismatch))?;
+        write(
+            out,
+            RpiMismatch {
+                file: found.file.index(),
+                path: verification.paths[mismatch].as_ptr(),
+                outdated: found.version.is_some(),
+                version: found.version.unwrap_or(0),
+            },
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use patch::{FileSize, FileTime};
+    use ryzom_patch_info::{edit, schema};
+    use std::mem::MaybeUninit;
+
+    fn index_file() -> Vec<u8> {
+        let version = |number: u32, data: &[u8]| patch::CBNPFileVersion {
+            _VersionNumber: number,
+            _FileSize: FileSize(data.len() as u32),
+            _7ZFileSize: FileSize(50),
+            _FileTime: FileTime(0),
+            _PatchSize: FileSize(5),
+            _HashKey: edit::hash_key(data),
+            extra: Vec::new(),
+        };
+        let raw = patch::CProductDescriptionForClient {
+            _Files: patch::CBNPFileSet {
+                _Files: vec![patch::CBNPFile {
+                    _FileName: "fauna_maps.bnp".to_string(),
+                    _Versions: vec![version(1, b"old"), version(2, b"new")],
+                    extra: Vec::new(),
+                }],
+            },
+            _Categories: patch::CBNPCategorySet {
+                _Category: vec![patch::CBNPCategory {
+                    _Name: "main".to_string(),
+                    _IsOptional: None,
+                    _UnpackTo: None,
+                    _IsIncremental: None,
+                    _CatRequired: None,
+                    _Hidden: None,
+                    _Files: vec!["fauna_maps.bnp".to_string(), "unknown.bnp".to_string()],
+                    extra: Vec::new(),
+                }],
+            },
+        };
+
+        schema::write(&schema::Document::Product(raw), pd::Writer::default()).unwrap()
+    }
+
+    unsafe extern "C" fn count(
+        user_data: *mut c_void,
+        _: usize,
+        _: usize,
+        _: *const c_char,
+    ) -> bool {
+        *(user_data as *mut usize) += 1;
+        *(user_data as *mut usize) < 2
+    }
+
+    #[test]
+    fn test_index_and_plan() {
+        let data = index_file();
+        let install_dir = std::env::temp_dir().join(format!("capi-{}", std::process::id()));
+        std::fs::create_dir_all(install_dir.join("data")).unwrap();
+        std::fs::write(install_dir.join("data/fauna_maps.bnp"), b"old").unwrap();
+        let install = c_string(install_dir.to_str().unwrap());
+
+        unsafe {
+            let mut index = ptr::null_mut();
+            let status = rpi_index_read(
+                data.as_ptr(),
+                data.len(),
+                false,
+                RpiEncoding::Utf8 as u32,
+                &mut index,
+            );
+            assert_eq!(RpiStatus::Ok, status);
+            assert_eq!(1, rpi_index_file_count(index));
+            assert_eq!(1, rpi_index_issue_count(index));
+            assert!(rpi_index_issue(index, 1).is_null());
+
+            let mut file = MaybeUninit::uninit();
+            assert_eq!(RpiStatus::Ok, rpi_index_file(index, 0, file.as_mut_ptr()));
+            let file = file.assume_init();
+            assert_eq!(c"fauna_maps.bnp", CStr::from_ptr(file.name));
+            assert_eq!(2, file.version_count);
+            let mut category = MaybeUninit::uninit();
+            assert_eq!(
+                RpiStatus::Ok,
+                rpi_index_category(index, 0, category.as_mut_ptr())
+            );
+            let category = category.assume_init();
+            assert_eq!(1, category.file_count);
+            assert!(category.unpack_to.is_null());
+
+            let mut plan = ptr::null_mut();
+            let status = rpi_plan_create(
+                index,
+                install.as_ptr(),
+                false,
+                None,
+                ptr::null_mut(),
+                &mut plan,
+            );
+            assert_eq!(RpiStatus::Ok, status);
+            assert_eq!(1, rpi_plan_count(plan));
+            assert_eq!(5, rpi_plan_download(plan));
+            let mut update = MaybeUninit::uninit();
+            assert_eq!(RpiStatus::Ok, rpi_plan_update(plan, 0, update.as_mut_ptr()));
+            let update = update.assume_init();
+            assert!(update.installed);
+            assert_eq!((0, 1, 2), (update.file, update.from, update.to));
+            rpi_plan_free(plan);
+
+            let mut calls = 0usize;
+            let mut plan = ptr::null_mut();
+            let user_data = &mut calls as *mut usize as *mut c_void;
+            let status = rpi_plan_create(
+                index,
+                install.as_ptr(),
+                false,
+                Some(count),
+                user_data,
+                &mut plan,
+            );
+            assert_eq!(RpiStatus::Cancelled, status);
+            assert_eq!(2, calls);
+            assert!(plan.is_null());
+
+            let mut verification = ptr::null_mut();
+            let status = rpi_verify(
+                index,
+                install.as_ptr(),
+                false,
+                None,
+                ptr::null_mut(),
+                &mut verification,
+            );
+            assert_eq!(RpiStatus::Ok, status);
+            assert_eq!(1, rpi_verification_count(verification));
+            let mut mismatch = MaybeUninit::uninit();
+            assert_eq!(
+                RpiStatus::Ok,
+                rpi_verification_mismatch(verification, 0, mismatch.as_mut_ptr())
+            );
+            let mismatch = mismatch.assume_init();
+            assert!(mismatch.outdated);
+            assert_eq!((0, 1), (mismatch.file, mismatch.version));
+            rpi_verification_free(verification);
+            rpi_index_free(index);
+        }
+        std::fs::remove_dir_all(install_dir).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        unsafe {
+            let mut index = ptr::null_mut();
+            let status = rpi_index_read(
+                b"PDR".as_ptr(),
+                3,
+                false,
+                RpiEncoding::Utf8 as u32,
+                &mut index,
+            );
+            assert_eq!(RpiStatus::InvalidIndex, status);
+            assert!(index.is_null());
+            assert!(!CStr::from_ptr(rpi_last_error()).is_empty());
+
+            let status = rpi_index_open(ptr::null(), false, RpiEncoding::Utf8 as u32, &mut index);
+            assert_eq!(RpiStatus::InvalidArgument, status);
+            assert_eq!(c"the path is null", CStr::from_ptr(rpi_last_error()));
+            let status = rpi_index_read(b"PDR".as_ptr(), 3, false, 3, &mut index);
+            assert_eq!(RpiStatus::InvalidArgument, status);
+            assert_eq!(c"there is no encoding 3", CStr::from_ptr(rpi_last_error()));
+
+            let data = index_file();
+            assert_eq!(
+                RpiStatus::Ok,
+                rpi_index_read(
+                    data.as_ptr(),
+                    data.len(),
+                    false,
+                    RpiEncoding::Utf8 as u32,
+                    &mut index
+                )
+            );
+            let mut file = MaybeUninit::uninit();
+            assert_eq!(
+                RpiStatus::OutOfRange,
+                rpi_index_file(index, 1, file.as_mut_ptr())
+            );
+            assert_eq!(
+                RpiStatus::InvalidArgument,
+                rpi_index_file(index, 0, ptr::null_mut())
+            );
+            assert_eq!(0, rpi_index_file_count(ptr::null()));
+            rpi_index_free(index);
+        }
+    }
+}
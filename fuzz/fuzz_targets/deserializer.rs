@@ -0,0 +1,28 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ryzom_patch_info::{format, pd};
+use serde::Deserialize;
+
+#[allow(non_snake_case, dead_code)]
+#[derive(Debug, Deserialize)]
+struct Version<'a> {
+    _VersionNumber: u32,
+    _FileName: &'a str,
+    _IsOptional: bool,
+    _Priority: i32,
+    _UnpackTo: String,
+    _FileSize: u64,
+    _FileTime: Option<i64>,
+    _Ratio: f32,
+    _Scale: Option<f64>,
+    _HashKey: Vec<u32>,
+    _Mirror: Option<&'a str>,
+}
+
+fuzz_target!(|data: &[u8]| {
+    let limits = pd::Limits::default();
+    if let Ok(pdr) = pd::read_index_file(data, false, limits, pd::Encoding::Lossy) {
+        let _ = format::from_pdr::<Version>(&pdr);
+    }
+});
@@ -0,0 +1,25 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ryzom_patch_info::error::ReadingError;
+use ryzom_patch_info::pd;
+
+fuzz_target!(|input: (u16, u16)| {
+    let (stored, string_count) = input;
+    let string_count = usize::from(string_count);
+
+    match pd::parse_token(stored, string_count) {
+        Ok(token) => {
+            assert!(usize::from(token.value()) < string_count);
+            assert_eq!(pd::unchecked_token(stored), token);
+        }
+        Err(error) => assert!(matches!(
+            error,
+            ReadingError::InvalidStringIndex(index) if index as usize >= string_count
+        )),
+    }
+
+    // the type bits and the name index make up the whole token
+    let (token_type, value) = pd::split_token(stored);
+    assert_eq!(stored, value << 3 | token_type);
+});
@@ -0,0 +1,33 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ryzom_patch_info::{pd, product, schema};
+
+fuzz_target!(|data: &[u8]| {
+    let limits = pd::Limits::default();
+    for lenient in [false, true] {
+        let Ok(mut pdr) = pd::read_index_file(data, lenient, limits, pd::Encoding::Lossy) else {
+            continue;
+        };
+        pdr.lenient = lenient;
+        let Ok(document) = schema::read(&mut pdr, schema::Schema::Auto) else {
+            continue;
+        };
+        if let schema::Document::Product(ref raw) = document {
+            let json = serde_json::to_value(raw).unwrap();
+            let _ = product::ProductDescription::repaired(serde_json::from_value(json).unwrap());
+        }
+
+        // what was read is written back with its original bytes and reads the same
+        let writer = pd::Writer::with_encoding(pd::Encoding::Utf8, pdr.originals);
+        let Ok(written) = schema::write(&document, writer) else {
+            continue;
+        };
+        let mut pdr = pd::read_index_file(&written, false, limits, pd::Encoding::Lossy).unwrap();
+        let read = schema::read(&mut pdr, schema::Schema::Auto).unwrap();
+        assert_eq!(
+            serde_json::to_value(&document).unwrap(),
+            serde_json::to_value(&read).unwrap()
+        );
+    }
+});
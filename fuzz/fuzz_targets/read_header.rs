@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ryzom_patch_info::error::Section;
+use ryzom_patch_info::pd;
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(header) = pd::read_header(data.len() as u64, &mut Cursor::new(data)) else {
+        return;
+    };
+
+    // an accepted header describes sections that fit into the file, one after the other
+    let end = header.strings_offset() + header.section_size(Section::StringTable);
+    assert_eq!(u64::from(header.total_size), header.expected_size());
+    assert_eq!(header.expected_size(), end);
+    assert!(end <= data.len() as u64);
+});
@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ryzom_patch_info::pd;
+use std::io::Cursor;
+
+fuzz_target!(|input: (u16, u16, &[u8])| {
+    let (start, max_length, data) = input;
+    let limits = pd::Limits {
+        string_length: u32::from(max_length),
+        ..pd::Limits::default()
+    };
+    let mut cursor = Cursor::new(data);
+    cursor.set_position(u64::from(start));
+
+    if let Ok(bytes) = pd::read_string(&mut cursor, limits) {
+        assert!(!bytes.contains(&0));
+        assert!(bytes.len() <= usize::from(max_length));
+        assert!(cursor.position() <= data.len() as u64);
+    }
+});
@@ -0,0 +1,202 @@
+//! Derive macros for the `pd::Readable` and `pd::Writable` traits of ryzom-patch-info.
+//!
+//! Every field is a property named like the field unless renamed:
+//!
+//! - `#[pdr(rename = "_7ZFileSize")]` reads and writes the property under another name
+//! - `#[pdr(repeated)]` stores a `Vec` as one property per item, like `read_prop_vec`
+//! - `#[pdr(default)]` or `#[pdr(default = "path")]` uses `Default::default()` or calls `path`
+//!   when the property is missing
+//! - `#[pdr(extra)]` collects the properties the struct does not know, see `take_extra`
+//!
+//! `Option` fields are optional without any attribute.
+//!
+//! The generated impls name the traits through `::ryzom_patch_info`, a crate that re-exports it
+//! under another name or path passes it with `#[pdr(crate = "path")]` on the struct.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Ident, LitStr, Path, Result};
+
+#[proc_macro_derive(PdrRead, attributes(pdr))]
+pub fn derive_read(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_read(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(PdrWrite, attributes(pdr))]
+pub fn derive_write(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_write(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+enum Kind {
+    Single,
+    Repeated,
+    Default(Option<Path>),
+    Extra,
+}
+
+struct Field {
+    ident: Ident,
+    name: String,
+    kind: Kind,
+}
+
+fn fields(input: &DeriveInput) -> Result<Vec<Field>> {
+    let Data::Struct(ref data) = input.data else {
+        return Err(Error::new_spanned(
+            input,
+            "only structs can be read from a PDR",
+        ));
+    };
+    let Fields::Named(ref named) = data.fields else {
+        return Err(Error::new_spanned(
+            &data.fields,
+            "only structs with named fields can be read from a PDR",
+        ));
+    };
+
+    named.named.iter().map(field).collect()
+}
+
+/// The path of the ryzom-patch-info crate, `#[pdr(crate = "...")]` on the struct overrides it
+fn crate_path(input: &DeriveInput) -> Result<Path> {
+    let mut path: Path = syn::parse_quote!(::ryzom_patch_info);
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("pdr"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("crate") {
+                path = meta.value()?.parse::<LitStr>()?.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("expected crate"))
+            }
+        })?;
+    }
+
+    Ok(path)
+}
+
+fn field(field: &syn::Field) -> Result<Field> {
+    let ident = field.ident.clone().expect("named fields have an ident");
+    let mut name = ident.to_string();
+    let mut kind = Kind::Single;
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("pdr"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                name = meta.value()?.parse::<LitStr>()?.value();
+            } else if meta.path.is_ident("repeated") {
+                kind = Kind::Repeated;
+            } else if meta.path.is_ident("default") {
+                kind = match meta.value() {
+                    Ok(value) => Kind::Default(Some(value.parse::<LitStr>()?.parse()?)),
+                    Err(_) => Kind::Default(None),
+                };
+            } else if meta.path.is_ident("extra") {
+                kind = Kind::Extra;
+            } else {
+                return Err(meta.error("expected rename, repeated, default or extra"));
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(Field { ident, name, kind })
+}
+
+fn expand_read(input: &DeriveInput) -> Result<TokenStream2> {
+    let krate = crate_path(input)?;
+    let fields = fields(input)?;
+    let ident = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    // the extra properties are collected once every known field is read
+    let (extra, known): (Vec<&Field>, Vec<&Field>) = fields
+        .iter()
+        .partition(|field| matches!(field.kind, Kind::Extra));
+    if extra.len() > 1 {
+        return Err(Error::new_spanned(
+            &extra[1].ident,
+            "only one field can collect the extra properties",
+        ));
+    }
+
+    let reads = known.iter().map(|field| {
+        let binding = &field.ident;
+        let name = &field.name;
+        let value = match field.kind {
+            Kind::Single => quote!(pdr.read(#name)?),
+            Kind::Repeated => quote!(pdr.read_prop_vec(#name)?),
+            Kind::Default(None) => {
+                quote!(pdr.read::<::core::option::Option<_>>(#name)?.unwrap_or_default())
+            }
+            Kind::Default(Some(ref path)) => {
+                quote!(pdr.read::<::core::option::Option<_>>(#name)?.unwrap_or_else(#path))
+            }
+            Kind::Extra => unreachable!("extra fields are read last"),
+        };
+        quote!(let #binding = #value;)
+    });
+    let extra_read = extra.first().map(|field| {
+        let binding = &field.ident;
+        quote!(let #binding = pdr.take_extra()?;)
+    });
+    let known_fields = extra.first().map(|_| {
+        let names = known.iter().map(|field| &field.name);
+        quote!(const FIELDS: &'static [&'static str] = &[#(#names),*];)
+    });
+    let bindings = fields.iter().map(|field| &field.ident);
+
+    Ok(quote! {
+        impl #impl_generics #krate::pd::Readable for #ident #type_generics #where_clause {
+            #known_fields
+
+            fn read(pdr: &mut #krate::pd::PersistentDataRecord) -> #krate::Result<Self> {
+                #(#reads)*
+                #extra_read
+
+                ::core::result::Result::Ok(#ident { #(#bindings),* })
+            }
+        }
+    })
+}
+
+fn expand_write(input: &DeriveInput) -> Result<TokenStream2> {
+    let krate = crate_path(input)?;
+    let fields = fields(input)?;
+    let ident = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let writes = fields.iter().map(|field| {
+        let member = &field.ident;
+        let name = &field.name;
+        match field.kind {
+            Kind::Single | Kind::Default(_) => quote!(pdr.write(#name, &self.#member);),
+            Kind::Repeated => quote!(pdr.write_prop_vec(#name, &self.#member);),
+            Kind::Extra => quote!(pdr.write_extra(&self.#member);),
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics #krate::pd::Writable for #ident #type_generics #where_clause {
+            fn write(&self, pdr: &mut #krate::pd::Writer) {
+                #(#writes)*
+            }
+        }
+    })
+}
@@ -0,0 +1,195 @@
+//! Python module reading, comparing, verifying and writing Ryzom patch indexes
+
+use clap::ValueEnum;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use ryzom_patch_info::error::ReadingError;
+use ryzom_patch_info::{changelog, output, patch, pd, product, schema};
+use std::path::PathBuf;
+
+create_exception!(
+    ryzom_patch_info,
+    PdrError,
+    PyException,
+    "An index file that can not be read or written"
+);
+
+fn pdr_error(error: ReadingError) -> PyErr {
+    PdrError::new_err(error.to_string())
+}
+
+fn parse_encoding(encoding: &str) -> PyResult<pd::Encoding> {
+    pd::Encoding::from_str(encoding, true).map_err(PyValueError::new_err)
+}
+
+/// Converts through JSON, the format the command line tool prints and reads back
+fn to_python<'py>(py: Python<'py>, value: &impl serde::Serialize) -> PyResult<Bound<'py, PyAny>> {
+    let json = serde_json::to_string(value)
+        .map_err(|error| pdr_error(ReadingError::InvalidJson(error)))?;
+
+    py.import("json")?.call_method1("loads", (json,))
+}
+
+/// A product description read from an index file, `CProductDescriptionForClient` in NeL
+#[pyclass(module = "ryzom_patch_info")]
+struct Index {
+    raw: patch::CProductDescriptionForClient,
+    /// Bytes of the strings that did not decode to themselves, written back unchanged
+    originals: pd::OriginalStrings,
+    encoding: pd::Encoding,
+    warnings: Vec<String>,
+}
+
+impl Index {
+    fn read(data: &[u8], lenient: bool, encoding: &str) -> PyResult<Index> {
+        let encoding = parse_encoding(encoding)?;
+        let mut pdr = pd::read_index_file(data, lenient, pd::Limits::default(), encoding)
+            .map_err(pdr_error)?;
+        pdr.lenient = lenient;
+        let raw = patch::CProductDescriptionForClient::from(&mut pdr).map_err(pdr_error)?;
+
+        Ok(Index {
+            raw,
+            originals: std::mem::take(&mut pdr.originals),
+            encoding,
+            warnings: pdr.warnings.iter().map(ToString::to_string).collect(),
+        })
+    }
+
+    /// The validated description, inconsistencies are repaired as in lenient mode
+    fn describe(&self) -> product::ProductDescription {
+        product::ProductDescription::repaired(self.raw.clone()).0
+    }
+}
+
+#[pymethods]
+impl Index {
+    /// Reads an index file, in lenient mode broken parts are skipped and listed in `warnings`
+    #[staticmethod]
+    #[pyo3(signature = (path, lenient = false, encoding = "utf8"))]
+    fn open(path: PathBuf, lenient: bool, encoding: &str) -> PyResult<Index> {
+        let data = std::fs::read(&path)
+            .map_err(|error| pdr_error(ReadingError::from(error).in_file(path.display())))?;
+
+        Index::read(&data, lenient, encoding)
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (data, lenient = false, encoding = "utf8"))]
+    fn from_bytes(data: &[u8], lenient: bool, encoding: &str) -> PyResult<Index> {
+        Index::read(data, lenient, encoding)
+    }
+
+    /// Builds an index from a dict shaped like the one `to_dict` returns
+    #[staticmethod]
+    fn from_dict(value: &Bound<'_, PyAny>) -> PyResult<Index> {
+        let json: String = value
+            .py()
+            .import("json")?
+            .call_method1("dumps", (value,))?
+            .extract()?;
+        let raw = serde_json::from_str(&json)
+            .map_err(|error| pdr_error(ReadingError::InvalidJson(error)))?;
+
+        Ok(Index {
+            raw,
+            originals: pd::OriginalStrings::default(),
+            encoding: pd::Encoding::default(),
+            warnings: Vec::new(),
+        })
+    }
+
+    /// What lenient reading skipped
+    #[getter]
+    fn warnings(&self) -> Vec<String> {
+        self.warnings.clone()
+    }
+
+    /// The description as stored, with the property names of the file
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_python(py, &self.raw)
+    }
+
+    /// One dict per file with its latest version and categories, as printed by `query`
+    fn files<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_python(py, &output::file_rows(&self.describe()))
+    }
+
+    /// The inconsistencies the command line tool rejects outside of lenient mode
+    fn verify(&self) -> Vec<String> {
+        let (_, repairs) = product::ProductDescription::repaired(self.raw.clone());
+
+        repairs.iter().map(ToString::to_string).collect()
+    }
+
+    /// The files that changed from this index to `newer` by category, as written by `changelog`
+    fn diff<'py>(&self, py: Python<'py>, newer: &Index) -> PyResult<Bound<'py, PyAny>> {
+        let changes = changelog::Changelog::new(&self.describe(), &newer.describe());
+        let files: Vec<serde_json::Value> = changes
+            .categories
+            .iter()
+            .flat_map(|category| {
+                category.files.iter().map(|change| {
+                    serde_json::json!({
+                        "category": category.name,
+                        "name": change.name,
+                        "old": change.old,
+                        "new": change.new,
+                        "download": change.download,
+                    })
+                })
+            })
+            .collect();
+
+        to_python(
+            py,
+            &serde_json::json!({
+                "files": files,
+                "added_optional": changes.added_optional,
+                "removed_optional": changes.removed_optional,
+                "download": changes.download(),
+            }),
+        )
+    }
+
+    /// Encodes the index, strings that were read keep their original bytes
+    #[pyo3(signature = (encoding = None))]
+    fn to_bytes<'py>(
+        &self,
+        py: Python<'py>,
+        encoding: Option<&str>,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let encoding = encoding.map_or(Ok(self.encoding), parse_encoding)?;
+        let writer = pd::Writer::with_encoding(encoding, self.originals.clone());
+        let document = schema::Document::Product(self.raw.clone());
+        let data = schema::write(&document, writer).map_err(pdr_error)?;
+
+        Ok(PyBytes::new(py, &data))
+    }
+
+    #[pyo3(signature = (path, encoding = None))]
+    fn save(&self, py: Python<'_>, path: PathBuf, encoding: Option<&str>) -> PyResult<()> {
+        let data = self.to_bytes(py, encoding)?;
+        std::fs::write(&path, data.as_bytes())
+            .map_err(|error| pdr_error(ReadingError::OutputError(error).in_file(path.display())))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<Index of {} files in {} categories>",
+            self.raw._Files._Files.len(),
+            self.raw._Categories._Category.len()
+        )
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "ryzom_patch_info")]
+fn init(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Index>()?;
+    m.add("PdrError", m.py().get_type::<PdrError>())?;
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+import unittest
+
+from ryzom_patch_info import Index, PdrError
+
+
+def version(number, size):
+    return {
+        "_VersionNumber": number,
+        "_FileSize": size,
+        "_7ZFileSize": size // 2,
+        "_FileTime": 1700000000 + number,
+        "_PatchSize": size // 10,
+        "_HashKey": [number, 2, 3, 4, 5],
+    }
+
+
+def description(sounds_versions):
+    return {
+        "_Files": {
+            "_Files": [
+                {"_FileName": "sounds.bnp", "_Versions": sounds_versions},
+            ]
+        },
+        "_Categories": {
+            "_Category": [
+                {"_Name": "sound", "_IsOptional": True, "_Files": ["sounds.bnp"]},
+            ]
+        },
+    }
+
+
+class IndexTest(unittest.TestCase):
+    def test_round_trip(self):
+        index = Index.from_dict(description([version(1, 1000)]))
+        read = Index.from_bytes(index.to_bytes())
+
+        self.assertEqual(index.to_dict(), read.to_dict())
+        self.assertEqual([], read.warnings)
+        self.assertEqual("sounds.bnp", read.files()[0]["name"])
+        self.assertEqual(["sound"], read.files()[0]["categories"])
+
+    def test_verify_and_diff(self):
+        old = Index.from_dict(description([version(1, 1000)]))
+        new = description([version(1, 1000), version(2, 2000)])
+        new["_Categories"]["_Category"][0]["_Files"].append("missing.bnp")
+        new = Index.from_dict(new)
+
+        self.assertEqual([], old.verify())
+        self.assertEqual(["category sound lists the unknown file missing.bnp"], new.verify())
+        changes = old.diff(new)
+        self.assertEqual(["sounds.bnp"], [change["name"] for change in changes["files"]])
+        self.assertEqual(2, changes["files"][0]["new"]["_VersionNumber"])
+        self.assertEqual(200, changes["download"])
+
+    def test_errors(self):
+        with self.assertRaises(PdrError):
+            Index.from_bytes(b"\0" * 8)
+        with self.assertRaises(ValueError):
+            Index.from_bytes(b"", encoding="ebcdic")
+
+
+if __name__ == "__main__":
+    unittest.main()
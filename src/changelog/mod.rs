@@ -0,0 +1,256 @@
+mod render;
+
+use crate::{patch, product};
+use clap::ValueEnum;
+use std::collections::{BTreeMap, HashMap};
+
+pub use render::{write_html, write_markdown};
+
+/// Category used for files that are not listed by any category
+pub const UNCATEGORIZED: &str = "uncategorized";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Markdown,
+    /// A standalone page
+    Html,
+}
+
+/// Differences between two product descriptions, grouped by category
+#[derive(Debug, PartialEq)]
+pub struct Changelog {
+    pub categories: Vec<CategoryChanges>,
+    pub added_optional: Vec<String>,
+    pub removed_optional: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CategoryChanges {
+    pub name: String,
+    pub files: Vec<FileChange>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    pub name: String,
+    pub old: Option<product::FileVersion>,
+    pub new: Option<product::FileVersion>,
+    /// Bytes a client at the old version has to download
+    pub download: patch::FileSize,
+}
+
+impl FileChange {
+    fn new(
+        old: Option<&product::FileEntry>,
+        new: Option<&product::FileEntry>,
+    ) -> Option<FileChange> {
+        let old_version = old.and_then(product::FileEntry::latest);
+        let new_version = new.and_then(product::FileEntry::latest);
+        if let (Some(old_version), Some(new_version)) = (old_version, new_version) {
+            if old_version.number == new_version.number
+                && old_version.hash_key == new_version.hash_key
+            {
+                return None;
+            }
+        }
+
+        Some(FileChange {
+            name: old.or(new)?.name.clone(),
+            old: old_version.cloned(),
+            new: new_version.cloned(),
+            download: new.map_or(patch::FileSize(0), |file| download_size(file, old_version)),
+        })
+    }
+
+    /// The time of the new version, removed files have none
+    pub fn time(&self) -> Option<patch::FileTime> {
+        self.new.as_ref().map(|version| version.time)
+    }
+}
+
+/// Sum of the patches from the old to the latest version, or the packed file if there is no
+/// old version or a patch is missing
+pub fn download_size(
+    file: &product::FileEntry,
+    old: Option<&product::FileVersion>,
+) -> patch::FileSize {
+    let Some(latest) = file.latest() else {
+        return patch::FileSize(0);
+    };
+    let full = latest.packed_size;
+    let Some(old) = old else {
+        return full;
+    };
+
+    let patches: Option<u32> = file
+        .versions
+        .iter()
+        .filter(|version| version.number > old.number)
+        .map(|version| (version.patch_size.0 > 0).then_some(version.patch_size.0))
+        .sum();
+
+    match patches {
+        Some(size) if size < full.0 => patch::FileSize(size),
+        _ => full,
+    }
+}
+
+impl Changelog {
+    pub fn new(old: &product::ProductDescription, new: &product::ProductDescription) -> Changelog {
+        let mut changes: Vec<(&product::ProductDescription, FileChange)> = new
+            .files()
+            .iter()
+            .filter_map(|file| FileChange::new(old.file(&file.name), Some(file)))
+            .map(|change| (new, change))
+            .collect();
+        changes.extend(
+            old.files()
+                .iter()
+                .filter(|file| new.file(&file.name).is_none())
+                .filter_map(|file| FileChange::new(Some(file), None))
+                .map(|change| (old, change)),
+        );
+
+        // removed files are grouped by the categories of the old index
+        let mut groups: BTreeMap<String, Vec<FileChange>> = BTreeMap::new();
+        for (product, change) in changes {
+            let mut names: Vec<String> = product
+                .file_id(&change.name)
+                .into_iter()
+                .flat_map(|id| product.file_categories(id))
+                .map(|category| category.name.clone())
+                .collect();
+            if names.is_empty() {
+                names.push(UNCATEGORIZED.to_string());
+            }
+            for name in names {
+                groups.entry(name).or_default().push(change.clone());
+            }
+        }
+
+        Changelog {
+            categories: groups
+                .into_iter()
+                .map(|(name, files)| CategoryChanges { name, files })
+                .collect(),
+            added_optional: optional_categories(new, old),
+            removed_optional: optional_categories(old, new),
+        }
+    }
+
+    /// Only keeps the changed files with a new version in the given time range, removed files are
+    /// always kept
+    pub fn retain_time(&mut self, since: Option<patch::FileTime>, until: Option<patch::FileTime>) {
+        for category in &mut self.categories {
+            category.files.retain(|change| match change.time() {
+                Some(time) => {
+                    since.is_none_or(|since| time >= since)
+                        && until.is_none_or(|until| time <= until)
+                }
+                None => true,
+            });
+        }
+        self.categories
+            .retain(|category| !category.files.is_empty());
+    }
+
+    /// Bytes a client at the old version has to download, files listed in several categories
+    /// only count once
+    pub fn download(&self) -> patch::FileSize {
+        let mut files: HashMap<&str, u64> = HashMap::new();
+        for change in self.categories.iter().flat_map(|category| &category.files) {
+            files.insert(&change.name, change.download.0 as u64);
+        }
+
+        patch::FileSize(files.values().sum::<u64>().min(u32::MAX as u64) as u32)
+    }
+}
+
+/// Optional categories of `product` that `other` does not have
+fn optional_categories(
+    product: &product::ProductDescription,
+    other: &product::ProductDescription,
+) -> Vec<String> {
+    product
+        .categories()
+        .iter()
+        .filter(|category| category.optional && other.category(&category.name).is_none())
+        .map(|category| category.name.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::fixture::{self, category, version};
+
+    fn product(
+        files: Vec<(&str, Vec<patch::CBNPFileVersion>)>,
+        categories: Vec<patch::CBNPCategory>,
+    ) -> product::ProductDescription {
+        let files = files
+            .into_iter()
+            .map(|(name, versions)| fixture::file(name, versions))
+            .collect();
+
+        product::ProductDescription::try_from(fixture::product(files, categories)).unwrap()
+    }
+
+    #[test]
+    fn test_changelog() {
+        let old = product(
+            vec![
+                ("maps.bnp", vec![version(1, 1000, 0)]),
+                ("old.bnp", vec![version(1, 10, 0)]),
+                ("same.bnp", vec![version(3, 10, 0)]),
+            ],
+            vec![category("main", &["maps.bnp", "old.bnp", "same.bnp"])],
+        );
+        let new = product(
+            vec![
+                (
+                    "maps.bnp",
+                    vec![
+                        version(1, 1000, 0),
+                        version(2, 1100, 40),
+                        version(3, 1200, 30),
+                    ],
+                ),
+                ("same.bnp", vec![version(3, 10, 0)]),
+                ("music.bnp", vec![version(1, 500, 0)]),
+            ],
+            vec![
+                category("main", &["maps.bnp", "same.bnp"]),
+                patch::CBNPCategory {
+                    _IsOptional: Some(true),
+                    ..category("music", &["music.bnp"])
+                },
+            ],
+        );
+
+        let changelog = Changelog::new(&old, &new);
+
+        let summary: Vec<(&str, Vec<(&str, u32)>)> = changelog
+            .categories
+            .iter()
+            .map(|category| {
+                let files = category
+                    .files
+                    .iter()
+                    .map(|change| (change.name.as_str(), change.download.0))
+                    .collect();
+                (category.name.as_str(), files)
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("main", vec![("maps.bnp", 70), ("old.bnp", 0)]),
+                ("music", vec![("music.bnp", 250)]),
+            ],
+            summary
+        );
+        assert_eq!(vec!["music".to_string()], changelog.added_optional);
+        assert!(changelog.removed_optional.is_empty());
+        assert_eq!(patch::FileSize(320), changelog.download());
+    }
+}
@@ -0,0 +1,138 @@
+use super::{Changelog, FileChange};
+use std::io::{self, Write};
+
+fn version_cell(change: &FileChange) -> String {
+    match (&change.old, &change.new) {
+        (Some(old), Some(new)) => format!("{} → {}", old.number, new.number),
+        (None, Some(new)) => format!("new in {}", new.number),
+        (Some(old), None) => format!("removed after {}", old.number),
+        (None, None) => String::new(),
+    }
+}
+
+fn size_cells(change: &FileChange) -> [String; 3] {
+    let size = |version: &Option<crate::product::FileVersion>| {
+        version
+            .as_ref()
+            .map_or_else(|| "-".to_string(), |version| version.size.to_string())
+    };
+
+    [
+        size(&change.old),
+        size(&change.new),
+        change.download.to_string(),
+    ]
+}
+
+/// Writes the release notes as a Markdown document with one table per category
+pub fn write_markdown(
+    output: &mut impl Write,
+    title: &str,
+    changelog: &Changelog,
+) -> io::Result<()> {
+    writeln!(output, "# {}", title)?;
+    writeln!(output)?;
+    writeln!(output, "Total download: {}", changelog.download())?;
+
+    for (heading, names) in [
+        ("New optional categories", &changelog.added_optional),
+        ("Removed optional categories", &changelog.removed_optional),
+    ] {
+        if names.is_empty() {
+            continue;
+        }
+        writeln!(output)?;
+        writeln!(output, "## {}", heading)?;
+        writeln!(output)?;
+        for name in names {
+            writeln!(output, "- {}", name)?;
+        }
+    }
+
+    for category in &changelog.categories {
+        writeln!(output)?;
+        writeln!(output, "## {}", category.name)?;
+        writeln!(output)?;
+        writeln!(
+            output,
+            "| File | Version | Old size | New size | Download |"
+        )?;
+        writeln!(output, "| --- | --- | ---: | ---: | ---: |")?;
+        for change in &category.files {
+            let [old, new, download] = size_cells(change);
+            writeln!(
+                output,
+                "| {} | {} | {} | {} | {} |",
+                change.name.replace('|', "\\|"),
+                version_cell(change),
+                old,
+                new,
+                download
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Writes the release notes as a standalone HTML page with one table per category
+pub fn write_html(output: &mut impl Write, title: &str, changelog: &Changelog) -> io::Result<()> {
+    writeln!(output, "<!DOCTYPE html>")?;
+    writeln!(output, "<html>")?;
+    writeln!(output, "<head>")?;
+    writeln!(output, "<meta charset=\"utf-8\">")?;
+    writeln!(output, "<title>{}</title>", escape(title))?;
+    writeln!(output, "</head>")?;
+    writeln!(output, "<body>")?;
+    writeln!(output, "<h1>{}</h1>", escape(title))?;
+    writeln!(output, "<p>Total download: {}</p>", changelog.download())?;
+
+    for (heading, names) in [
+        ("New optional categories", &changelog.added_optional),
+        ("Removed optional categories", &changelog.removed_optional),
+    ] {
+        if names.is_empty() {
+            continue;
+        }
+        writeln!(output, "<h2>{}</h2>", heading)?;
+        writeln!(output, "<ul>")?;
+        for name in names {
+            writeln!(output, "<li>{}</li>", escape(name))?;
+        }
+        writeln!(output, "</ul>")?;
+    }
+
+    for category in &changelog.categories {
+        writeln!(output, "<h2>{}</h2>", escape(&category.name))?;
+        writeln!(output, "<table>")?;
+        writeln!(
+            output,
+            "<tr><th>File</th><th>Version</th><th>Old size</th><th>New size</th><th>Download</th></tr>"
+        )?;
+        for change in &category.files {
+            let [old, new, download] = size_cells(change);
+            writeln!(
+                output,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&change.name),
+                version_cell(change),
+                old,
+                new,
+                download
+            )?;
+        }
+        writeln!(output, "</table>")?;
+    }
+
+    writeln!(output, "</body>")?;
+    writeln!(output, "</html>")?;
+
+    Ok(())
+}
@@ -0,0 +1,444 @@
+use crate::patch::{self, FileSize, FileTime};
+use clap::Subcommand;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use xz2::stream::{LzmaOptions, Stream};
+use xz2::write::XzEncoder;
+
+/// Preset of the LZMA stream used to compute the packed size of a new version
+const LZMA_PRESET: u32 = 9;
+
+#[derive(Subcommand, Debug)]
+pub enum Action {
+    /// Add a file without versions, optionally to a category
+    AddFile {
+        /// Name of the file, e.g. `fauna_maps.bnp`
+        name: String,
+
+        #[arg(long)]
+        category: Option<String>,
+    },
+    /// Remove a file with its versions and category memberships
+    RemoveFile { name: String },
+    /// Append a version computed from a local file: size, SHA-1 hash, modification time and
+    /// LZMA packed size
+    AddVersion {
+        /// Path to the new content of the file
+        path: String,
+
+        /// Name of the file in the index, defaults to the file name of `path`
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Version number, defaults to one more than the latest version
+        #[arg(long)]
+        version: Option<u32>,
+
+        /// Size of the patch from the previous version in bytes
+        #[arg(long, default_value_t = 0)]
+        patch_size: u32,
+    },
+    /// Move a file into another category
+    MoveFile {
+        name: String,
+
+        /// Category to move the file to
+        #[arg(long)]
+        to: String,
+
+        /// Only leave this category, by default the file leaves all its categories
+        #[arg(long)]
+        from: Option<String>,
+    },
+    /// Change the flags and paths of a category, an empty path clears it
+    SetCategory {
+        name: String,
+
+        #[arg(long)]
+        optional: Option<bool>,
+
+        #[arg(long)]
+        hidden: Option<bool>,
+
+        #[arg(long)]
+        incremental: Option<bool>,
+
+        #[arg(long)]
+        unpack_to: Option<String>,
+
+        /// Category that has to be installed before this one
+        #[arg(long)]
+        required: Option<String>,
+    },
+}
+
+#[derive(Debug)]
+pub enum EditError {
+    UnknownFile(String),
+    DuplicateFile(String),
+    UnknownCategory(String),
+    /// A new version has to be above the latest one
+    VersionNotNewer {
+        file: String,
+        version: u32,
+        latest: u32,
+    },
+    /// The latest version is the highest number a version can have
+    NoVersionAfter {
+        file: String,
+        latest: u32,
+    },
+    /// Sizes and times are stored in 32 bits
+    TooLarge(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EditError::UnknownFile(ref name) => write!(f, "file {} is not in the index", name),
+            EditError::DuplicateFile(ref name) => {
+                write!(f, "file {} is already in the index", name)
+            }
+            EditError::UnknownCategory(ref name) => {
+                write!(f, "category {} is not in the index", name)
+            }
+            EditError::VersionNotNewer {
+                ref file,
+                ref version,
+                ref latest,
+            } => write!(
+                f,
+                "version {} of {} is not newer than the latest version {}",
+                version, file, latest
+            ),
+            EditError::NoVersionAfter {
+                ref file,
+                ref latest,
+            } => write!(
+                f,
+                "{} has no version number left after its latest version {}",
+                file, latest
+            ),
+            EditError::TooLarge(ref path) => {
+                write!(
+                    f,
+                    "{} is too large or too new to be stored in 32 bits",
+                    path
+                )
+            }
+            EditError::Io(ref cause) => write!(f, "{}", cause),
+        }
+    }
+}
+
+impl Error for EditError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            EditError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for EditError {
+    fn from(err: io::Error) -> EditError {
+        EditError::Io(err)
+    }
+}
+
+/// Applies an edit, the description is left unchanged when it fails
+pub fn apply(
+    product: &mut patch::CProductDescriptionForClient,
+    action: &Action,
+) -> Result<(), EditError> {
+    match *action {
+        Action::AddFile {
+            ref name,
+            ref category,
+        } => {
+            if file_index(product, name).is_some() {
+                return Err(EditError::DuplicateFile(name.clone()));
+            }
+            if let Some(category) = category {
+                category_mut(product, category)?._Files.push(name.clone());
+            }
+            product._Files._Files.push(patch::CBNPFile {
+                _FileName: name.clone(),
+                _Versions: Vec::new(),
+                extra: Vec::new(),
+            });
+        }
+        Action::RemoveFile { ref name } => {
+            let index = file_index(product, name).ok_or_else(|| unknown_file(name))?;
+            product._Files._Files.remove(index);
+            for category in &mut product._Categories._Category {
+                category._Files.retain(|file| file != name);
+            }
+        }
+        Action::AddVersion {
+            ref path,
+            ref name,
+            version,
+            patch_size,
+        } => {
+            let name = match name {
+                Some(name) => name.clone(),
+                None => file_name(path)?,
+            };
+            let index = file_index(product, &name).ok_or_else(|| unknown_file(&name))?;
+            let versions = &mut product._Files._Files[index]._Versions;
+            // versions read leniently may be out of order
+            let latest = versions.iter().map(|version| version._VersionNumber).max();
+            let number = match (version, latest) {
+                (Some(number), _) => number,
+                (None, None) => 1,
+                (None, Some(latest)) => {
+                    latest
+                        .checked_add(1)
+                        .ok_or_else(|| EditError::NoVersionAfter {
+                            file: name.clone(),
+                            latest,
+                        })?
+                }
+            };
+            if let Some(latest) = latest.filter(|&latest| number <= latest) {
+                return Err(EditError::VersionNotNewer {
+                    file: name,
+                    version: number,
+                    latest,
+                });
+            }
+
+            let mut new_version = file_version(Path::new(path), number)?;
+            new_version._PatchSize = FileSize(patch_size);
+            versions.push(new_version);
+        }
+        Action::MoveFile {
+            ref name,
+            ref to,
+            ref from,
+        } => {
+            if file_index(product, name).is_none() {
+                return Err(unknown_file(name));
+            }
+            // check both categories before changing either
+            category_mut(product, to)?;
+            if let Some(from) = from {
+                category_mut(product, from)?;
+            }
+
+            for category in &mut product._Categories._Category {
+                if from.as_ref().is_none_or(|from| *from == category._Name) {
+                    category._Files.retain(|file| file != name);
+                }
+            }
+            let to = category_mut(product, to)?;
+            if !to._Files.contains(name) {
+                to._Files.push(name.clone());
+            }
+        }
+        Action::SetCategory {
+            ref name,
+            optional,
+            hidden,
+            incremental,
+            ref unpack_to,
+            ref required,
+        } => {
+            if let Some(required) = required.as_ref().filter(|required| !required.is_empty()) {
+                category_mut(product, required)?;
+            }
+            let category = category_mut(product, name)?;
+            if optional.is_some() {
+                category._IsOptional = optional;
+            }
+            if hidden.is_some() {
+                category._Hidden = hidden;
+            }
+            if incremental.is_some() {
+                category._IsIncremental = incremental;
+            }
+            if let Some(unpack_to) = unpack_to {
+                category._UnpackTo = Some(unpack_to.clone()).filter(|path| !path.is_empty());
+            }
+            if let Some(required) = required {
+                category._CatRequired = Some(required.clone()).filter(|name| !name.is_empty());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Describes the current content of a local file as a version of it
+pub fn file_version(path: &Path, number: u32) -> Result<patch::CBNPFileVersion, EditError> {
+    let too_large = || EditError::TooLarge(path.display().to_string());
+    let data = fs::read(path)?;
+    let modified = fs::metadata(path)?
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| too_large())?;
+
+    Ok(patch::CBNPFileVersion {
+        _VersionNumber: number,
+        _FileSize: FileSize(u32::try_from(data.len()).map_err(|_| too_large())?),
+        _7ZFileSize: FileSize(u32::try_from(packed_size(&data)?).map_err(|_| too_large())?),
+        _FileTime: FileTime(u32::try_from(modified.as_secs()).map_err(|_| too_large())?),
+        _PatchSize: FileSize(0),
+        _HashKey: hash_key(&data),
+        extra: Vec::new(),
+    })
+}
+
+/// The SHA-1 digest of the data as NeL stores it, five little endian words
+pub fn hash_key(data: &[u8]) -> Vec<u32> {
+    sha1_smol::Sha1::from(data)
+        .digest()
+        .bytes()
+        .chunks_exact(size_of::<u32>())
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect()
+}
+
+/// Size of the data packed as an LZMA stream like the `.lzma` files of the patch server
+fn packed_size(data: &[u8]) -> io::Result<usize> {
+    let options = LzmaOptions::new_preset(LZMA_PRESET).map_err(io::Error::other)?;
+    let stream = Stream::new_lzma_encoder(&options).map_err(io::Error::other)?;
+    let mut encoder = XzEncoder::new_stream(Vec::new(), stream);
+    encoder.write_all(data)?;
+
+    Ok(encoder.finish()?.len())
+}
+
+fn file_name(path: &str) -> Result<String, EditError> {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| unknown_file(path))
+}
+
+fn file_index(product: &patch::CProductDescriptionForClient, name: &str) -> Option<usize> {
+    product
+        ._Files
+        ._Files
+        .iter()
+        .position(|file| file._FileName == name)
+}
+
+fn category_mut<'a>(
+    product: &'a mut patch::CProductDescriptionForClient,
+    name: &str,
+) -> Result<&'a mut patch::CBNPCategory, EditError> {
+    product
+        ._Categories
+        ._Category
+        .iter_mut()
+        .find(|category| category._Name == name)
+        .ok_or_else(|| EditError::UnknownCategory(name.to_string()))
+}
+
+fn unknown_file(name: &str) -> EditError {
+    EditError::UnknownFile(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::fixture::{self, category};
+
+    #[test]
+    fn test_move_and_set_category() {
+        let mut product =
+            fixture::product(vec![], vec![category("main", &[]), category("sound", &[])]);
+        let add = |name: &str| Action::AddFile {
+            name: name.to_string(),
+            category: Some("main".to_string()),
+        };
+        apply(&mut product, &add("sounds.bnp")).unwrap();
+        assert!(matches!(
+            apply(&mut product, &add("sounds.bnp")),
+            Err(EditError::DuplicateFile(_))
+        ));
+
+        let move_file = Action::MoveFile {
+            name: "sounds.bnp".to_string(),
+            to: "sound".to_string(),
+            from: None,
+        };
+        apply(&mut product, &move_file).unwrap();
+        let set = Action::SetCategory {
+            name: "sound".to_string(),
+            optional: Some(true),
+            hidden: None,
+            incremental: None,
+            unpack_to: Some(String::new()),
+            required: Some("main".to_string()),
+        };
+        apply(&mut product, &set).unwrap();
+
+        let [main, sound] = &product._Categories._Category[..] else {
+            panic!("expected two categories");
+        };
+        assert!(main._Files.is_empty());
+        assert_eq!(vec!["sounds.bnp".to_string()], sound._Files);
+        assert_eq!(Some(true), sound._IsOptional);
+        assert_eq!(None, sound._UnpackTo);
+        assert_eq!(Some("main".to_string()), sound._CatRequired);
+    }
+
+    #[test]
+    fn test_add_version_number() {
+        let path = std::env::temp_dir().join(format!("edit-{}-add.bnp", std::process::id()));
+        fs::write(&path, b"abc").unwrap();
+        let add = Action::AddVersion {
+            path: path.to_str().unwrap().to_string(),
+            name: Some("sounds.bnp".to_string()),
+            version: None,
+            patch_size: 0,
+        };
+        let versions = |numbers: &[u32]| {
+            let versions = numbers
+                .iter()
+                .map(|&number| fixture::version(number, 3, 0))
+                .collect();
+            fixture::product(vec![fixture::file("sounds.bnp", versions)], vec![])
+        };
+
+        let mut product = versions(&[5, 3]);
+        apply(&mut product, &add).unwrap();
+        let mut full = versions(&[u32::MAX, 1]);
+        let error = apply(&mut full, &add).unwrap_err();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(6, product._Files._Files[0]._Versions[2]._VersionNumber);
+        assert!(matches!(
+            error,
+            EditError::NoVersionAfter {
+                latest: u32::MAX,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_file_version() {
+        let path = std::env::temp_dir().join(format!("edit-{}.bnp", std::process::id()));
+        fs::write(&path, b"abc").unwrap();
+
+        let version = file_version(&path, 3).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(3, version._VersionNumber);
+        assert_eq!(FileSize(3), version._FileSize);
+        // sha1("abc") = a9993e36 4706816a ba3e2571 7850c26c 9cd0d89d
+        assert_eq!(
+            vec![0x363e99a9, 0x6a810647, 0x71253eba, 0x6cc25078, 0x9dd8d09c],
+            version._HashKey
+        );
+    }
+}
@@ -1,3 +1,6 @@
+use crate::{edit, merge, pd, product, query, schema};
+use clap::ValueEnum;
+use serde::Serialize;
 use std::error::Error;
 use std::{fmt, io};
 
@@ -6,8 +9,251 @@ pub enum ReadingError {
     InvalidFileFormat,
     UnsupportedVersion(u32),
     ContentTooSmall(u32, u64),
-    ContentWrongSize(u32, usize),
+    ContentWrongSize(u32, u64),
+    /// A count or size above the configured bounds, `max` is the bound
+    LimitExceeded {
+        limit: pd::Limit,
+        value: u64,
+        max: u64,
+    },
+    /// A section ends before all of its entries are read
+    Truncated(Section),
+    /// A token name or string value refers past the end of the string table
+    InvalidStringIndex(u32),
+    InvalidString(std::str::Utf8Error),
+    UnexpectedToken {
+        expected: String,
+        found: String,
+    },
+    /// Tokens passed over by lenient reading
+    SkippedTokens(usize),
+    /// A string the chosen encoding can not represent
+    UnencodableString(String, pd::Encoding),
+    /// A token name written past the part of the string table tokens can refer to
+    NameOutOfRange(String),
+    /// An XML or text file that does not parse, with the line of the problem
+    InvalidSyntax {
+        encoding: pd::FileEncoding,
+        line: usize,
+        message: String,
+    },
+    /// A property name the XML and text encodings can not store
+    UnwritableName(String, pd::FileEncoding),
+    InvalidQuery(query::ParseError),
+    InvalidProduct(product::ValidationError),
+    InvalidJson(serde_json::Error),
+    InvalidEdit(edit::EditError),
+    InvalidMerge(merge::MergeError),
+    /// A command needs a product description but the file holds another schema
+    WrongSchema(schema::Schema),
+    DatabaseError(rusqlite::Error),
+    /// Files a command passed over after reporting why they could not be read
+    FailedFiles(usize),
+    /// Lenient reading skipped parts of a file that would be written back without them
+    IncompleteInput(usize),
+    /// Streaming needs to seek, the string table is stored after the tables that use it
+    NotSeekable,
     IoError(io::Error),
+    OutputError(io::Error),
+    /// Another error together with where in which file it happened
+    Located(Box<ReadingError>, Location),
+}
+
+/// The parts of a PDR file in the order they are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Section {
+    Header,
+    TokenTable,
+    ArgTable,
+    StringTable,
+}
+
+/// Where an error happened, every part is optional as not every error knows all of them
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Location {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Byte offset from the start of the file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub section: Option<Section>,
+    /// Names of the enclosing structs and the property, e.g. `_Files/_Files[12]/_Versions[3]/_HashKey`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ErrorFormat {
+    /// Readable diagnostics
+    Text,
+    /// One JSON object with the error kind, message and location
+    Json,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Section::Header => "header",
+            Section::TokenTable => "token table",
+            Section::ArgTable => "arg table",
+            Section::StringTable => "string table",
+        })
+    }
+}
+
+impl Location {
+    pub fn at(section: Section, offset: u64) -> Location {
+        Location {
+            section: Some(section),
+            offset: Some(offset),
+            ..Location::default()
+        }
+    }
+
+    pub fn with_path(self, path: String) -> Location {
+        Location {
+            path: Some(path),
+            ..self
+        }
+    }
+
+    /// Fills the parts this location does not know from `other`
+    fn merge(self, other: Location) -> Location {
+        Location {
+            file: self.file.or(other.file),
+            offset: self.offset.or(other.offset),
+            section: self.section.or(other.section),
+            path: self.path.or(other.path),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts: Vec<String> = Vec::new();
+        if let Some(ref file) = self.file {
+            parts.push(file.clone());
+        }
+        match (self.offset, self.section) {
+            (Some(offset), Some(section)) => {
+                parts.push(format!("byte {} in the {}", offset, section))
+            }
+            (Some(offset), None) => parts.push(format!("byte {}", offset)),
+            (None, Some(section)) => parts.push(format!("in the {}", section)),
+            (None, None) => {}
+        }
+        if let Some(ref path) = self.path {
+            parts.push(format!("at {}", path));
+        }
+
+        f.write_str(&parts.join(", "))
+    }
+}
+
+impl ReadingError {
+    /// Attaches a location, parts already known to the error take precedence
+    pub fn at(self, location: Location) -> ReadingError {
+        match self {
+            ReadingError::Located(error, known) => {
+                ReadingError::Located(error, known.merge(location))
+            }
+            error => ReadingError::Located(Box::new(error), location),
+        }
+    }
+
+    pub fn in_file(self, file: impl fmt::Display) -> ReadingError {
+        self.at(Location {
+            file: Some(file.to_string()),
+            ..Location::default()
+        })
+    }
+
+    pub fn location(&self) -> Option<&Location> {
+        match *self {
+            ReadingError::Located(_, ref location) => Some(location),
+            _ => None,
+        }
+    }
+
+    /// The error without its location
+    pub fn unlocated(&self) -> &ReadingError {
+        match *self {
+            ReadingError::Located(ref error, _) => error.unlocated(),
+            ref error => error,
+        }
+    }
+
+    /// Stable identifier of the kind of error for machine readable output
+    pub fn kind(&self) -> &'static str {
+        match *self.unlocated() {
+            ReadingError::InvalidFileFormat => "invalid_file_format",
+            ReadingError::UnsupportedVersion(..) => "unsupported_version",
+            ReadingError::ContentTooSmall(..) => "content_too_small",
+            ReadingError::ContentWrongSize(..) => "content_wrong_size",
+            ReadingError::LimitExceeded { .. } => "limit_exceeded",
+            ReadingError::Truncated(..) => "truncated",
+            ReadingError::InvalidStringIndex(..) => "invalid_string_index",
+            ReadingError::InvalidString(..) => "invalid_string",
+            ReadingError::UnexpectedToken { .. } => "unexpected_token",
+            ReadingError::SkippedTokens(..) => "skipped_tokens",
+            ReadingError::UnencodableString(..) => "unencodable_string",
+            ReadingError::NameOutOfRange(..) => "name_out_of_range",
+            ReadingError::InvalidSyntax { .. } => "invalid_syntax",
+            ReadingError::UnwritableName(..) => "unwritable_name",
+            ReadingError::InvalidQuery(..) => "invalid_query",
+            ReadingError::InvalidProduct(..) => "invalid_product",
+            ReadingError::InvalidJson(..) => "invalid_json",
+            ReadingError::InvalidEdit(..) => "invalid_edit",
+            ReadingError::InvalidMerge(..) => "invalid_merge",
+            ReadingError::WrongSchema(..) => "wrong_schema",
+            ReadingError::DatabaseError(..) => "database_error",
+            ReadingError::FailedFiles(..) => "failed_files",
+            ReadingError::IncompleteInput(..) => "incomplete_input",
+            ReadingError::NotSeekable => "not_seekable",
+            ReadingError::IoError(..) => "io_error",
+            ReadingError::OutputError(..) => "output_error",
+            ReadingError::Located(..) => unreachable!("the cause is never located"),
+        }
+    }
+
+    /// Formats the error for printing to stderr
+    pub fn report(&self, format: ErrorFormat) -> String {
+        self.diagnostic("error", format)
+    }
+
+    /// Formats the error as a warning about data that was skipped
+    pub fn report_warning(&self, format: ErrorFormat) -> String {
+        self.diagnostic("warning", format)
+    }
+
+    fn diagnostic(&self, severity: &str, format: ErrorFormat) -> String {
+        #[derive(Serialize)]
+        struct Report<'a> {
+            severity: &'a str,
+            error: &'static str,
+            message: String,
+            #[serde(flatten)]
+            location: Option<&'a Location>,
+        }
+
+        match format {
+            ErrorFormat::Text => match self.location() {
+                Some(location) => {
+                    format!("{}: {}\n  --> {}", severity, self.unlocated(), location)
+                }
+                None => format!("{}: {}", severity, self),
+            },
+            ErrorFormat::Json => serde_json::to_string(&Report {
+                severity,
+                error: self.kind(),
+                message: self.unlocated().to_string(),
+                location: self.location(),
+            })
+            .unwrap_or_default(),
+        }
+    }
 }
 
 impl fmt::Display for ReadingError {
@@ -17,7 +263,16 @@ impl fmt::Display for ReadingError {
                 write!(f, "Invalid File Format")
             }
             ReadingError::UnsupportedVersion(ref version) => {
-                write!(f, "Unsupported version {}", version)
+                let known: Vec<String> = pd::FORMAT_VERSIONS
+                    .iter()
+                    .map(|format| format.version.to_string())
+                    .collect();
+                write!(
+                    f,
+                    "Unsupported version {}, known versions are {}",
+                    version,
+                    known.join(", ")
+                )
             }
             ReadingError::ContentTooSmall(ref total_size, ref file_size) => {
                 write!(
@@ -33,7 +288,77 @@ impl fmt::Display for ReadingError {
                     total_size, expected_size
                 )
             }
+            ReadingError::LimitExceeded {
+                ref limit,
+                ref value,
+                ref max,
+            } => write!(
+                f,
+                "The file holds {} {}, more than the limit of {}",
+                value, limit, max
+            ),
+            ReadingError::Truncated(ref section) => write!(f, "The {} ends too early", section),
+            ReadingError::InvalidStringIndex(ref index) => {
+                write!(f, "String index {} is out of range", index)
+            }
+            ReadingError::InvalidString(ref cause) => write!(f, "Invalid string: {}", cause),
+            ReadingError::UnexpectedToken {
+                ref expected,
+                ref found,
+            } => write!(f, "Expected {} token but found {}", expected, found),
+            ReadingError::SkippedTokens(ref count) => {
+                write!(f, "Skipped {} unknown or malformed tokens", count)
+            }
+            ReadingError::UnencodableString(ref value, ref encoding) => {
+                write!(f, "String {:?} can not be encoded as {}", value, encoding)
+            }
+            ReadingError::NameOutOfRange(ref name) => write!(
+                f,
+                "Token name {} is past the first 8192 strings tokens can refer to",
+                name
+            ),
+            ReadingError::InvalidSyntax {
+                ref encoding,
+                ref line,
+                ref message,
+            } => write!(f, "Invalid {} PDR on line {}: {}", encoding, line, message),
+            ReadingError::UnwritableName(ref name, ref encoding) => write!(
+                f,
+                "Property name {:?} can not be written as {}",
+                name, encoding
+            ),
+            ReadingError::InvalidQuery(ref cause) => write!(f, "Invalid query: {}", cause),
+            ReadingError::InvalidProduct(ref cause) => {
+                write!(f, "Invalid product description: {}", cause)
+            }
+            ReadingError::InvalidJson(ref cause) => write!(f, "Invalid JSON document: {}", cause),
+            ReadingError::InvalidEdit(ref cause) => write!(f, "Can not edit the index: {}", cause),
+            ReadingError::InvalidMerge(ref cause) => {
+                write!(f, "Can not merge the indexes: {}", cause)
+            }
+            ReadingError::WrongSchema(ref schema) => write!(
+                f,
+                "Expected a product description but the file holds a {} document",
+                schema
+            ),
+            ReadingError::DatabaseError(ref cause) => write!(f, "Database error: {}", cause),
+            ReadingError::FailedFiles(ref count) => {
+                write!(f, "Files that could not be read: {}", count)
+            }
+            ReadingError::NotSeekable => write!(
+                f,
+                "Streaming needs a file it can seek in, not a pipe: the string table is stored last"
+            ),
+            ReadingError::IncompleteInput(ref count) => write!(
+                f,
+                "Refusing to write an index without the {} parts lenient reading skipped",
+                count
+            ),
             ReadingError::IoError(ref cause) => write!(f, "Could not read file {:?}", cause),
+            ReadingError::OutputError(ref cause) => write!(f, "Could not write output: {}", cause),
+            ReadingError::Located(ref error, ref location) => {
+                write!(f, "{} ({})", error, location)
+            }
         }
     }
 }
@@ -45,7 +370,29 @@ impl Error for ReadingError {
             ReadingError::UnsupportedVersion(..) => None,
             ReadingError::ContentTooSmall(..) => None,
             ReadingError::ContentWrongSize(..) => None,
+            ReadingError::LimitExceeded { .. } => None,
+            ReadingError::Truncated(..) => None,
+            ReadingError::InvalidStringIndex(..) => None,
+            ReadingError::InvalidString(ref e) => Some(e),
+            ReadingError::UnexpectedToken { .. } => None,
+            ReadingError::SkippedTokens(..) => None,
+            ReadingError::UnencodableString(..) => None,
+            ReadingError::NameOutOfRange(..) => None,
+            ReadingError::InvalidSyntax { .. } => None,
+            ReadingError::UnwritableName(..) => None,
+            ReadingError::InvalidQuery(..) => None,
+            ReadingError::InvalidProduct(ref e) => Some(e),
+            ReadingError::InvalidJson(ref e) => Some(e),
+            ReadingError::InvalidEdit(ref e) => Some(e),
+            ReadingError::InvalidMerge(ref e) => Some(e),
+            ReadingError::WrongSchema(..) => None,
+            ReadingError::DatabaseError(ref e) => Some(e),
+            ReadingError::FailedFiles(..) => None,
+            ReadingError::IncompleteInput(..) => None,
+            ReadingError::NotSeekable => None,
             ReadingError::IoError(ref e) => Some(e),
+            ReadingError::OutputError(ref e) => Some(e),
+            ReadingError::Located(ref e, _) => Some(e.as_ref()),
         }
     }
 }
@@ -55,3 +402,38 @@ impl From<io::Error> for ReadingError {
         ReadingError::IoError(err)
     }
 }
+
+impl From<rusqlite::Error> for ReadingError {
+    fn from(err: rusqlite::Error) -> ReadingError {
+        ReadingError::DatabaseError(err)
+    }
+}
+
+impl From<product::ValidationError> for ReadingError {
+    fn from(err: product::ValidationError) -> ReadingError {
+        ReadingError::InvalidProduct(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let error = ReadingError::Truncated(Section::ArgTable)
+            .at(Location::at(Section::ArgTable, 96)
+                .with_path("_Files/_Files[1]/_FileName".to_string()))
+            .in_file("ryzom_01028.idx");
+
+        assert_eq!(
+            "error: The arg table ends too early\n  \
+             --> ryzom_01028.idx, byte 96 in the arg table, at _Files/_Files[1]/_FileName",
+            error.report(ErrorFormat::Text)
+        );
+        assert_eq!(
+            r#"{"severity":"error","error":"truncated","message":"The arg table ends too early","file":"ryzom_01028.idx","offset":96,"section":"arg_table","path":"_Files/_Files[1]/_FileName"}"#,
+            error.report(ErrorFormat::Json)
+        );
+    }
+}
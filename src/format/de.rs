@@ -1,5 +1,4 @@
 use std::collections::VecDeque;
-use std::ops::{AddAssign, MulAssign, Neg};
 
 use crate::format::error::{Error, Result};
 use crate::pd;
@@ -9,13 +8,13 @@ use serde::de::{
 use serde::Deserialize;
 
 pub struct Deserializer<'input> {
-    input: &'input pd::PersistentDataRecord,
+    input: &'input pd::PersistentDataRecord<'input>,
     token_offset: usize,
     arg_offset: usize,
 }
 
 impl<'input> Deserializer<'input> {
-    pub fn from_pdr(input: &'input pd::PersistentDataRecord) -> Self {
+    pub fn from_pdr(input: &'input pd::PersistentDataRecord<'input>) -> Self {
         Deserializer {
             input,
             token_offset: 0,
@@ -24,7 +23,7 @@ impl<'input> Deserializer<'input> {
     }
 }
 
-pub fn from_pdr<'input, T>(input: &'input pd::PersistentDataRecord) -> Result<T>
+pub fn from_pdr<'input, T>(input: &'input pd::PersistentDataRecord<'input>) -> Result<T>
 where
     T: Deserialize<'input>,
 {
@@ -35,19 +34,23 @@ where
 }
 
 impl<'input> Deserializer<'input> {
-    fn peek_token(&self) -> Result<&'input pd::Tokens> {
+    fn peek_token(&self) -> Result<pd::Tokens> {
         if self.token_offset < self.input.tokens.len() {
-            Ok(&self.input.tokens[self.token_offset])
+            Ok(self.input.tokens[self.token_offset])
         } else {
             Err(Error::NoMoreTokens)
         }
     }
 
+    fn peek_name(&self) -> Result<&'input str> {
+        Ok(self.input.name(self.peek_token()?))
+    }
+
     fn has_token(&self, name: &str) -> Result<bool> {
-        Ok(self.peek_token()?.value() == name)
+        Ok(self.peek_name()? == name)
     }
 
-    fn pop_token(&mut self) -> Result<&'input pd::Tokens> {
+    fn pop_token(&mut self) -> Result<pd::Tokens> {
         let value = self.peek_token()?;
 
         self.token_offset += 1;
@@ -66,18 +69,67 @@ impl<'input> Deserializer<'input> {
         Ok(arg)
     }
 
-    fn parse_bool(&mut self) -> Result<bool> {
-        if let pd::Tokens::SINT_TOKEN(_) = *self.pop_token()? {
-            Ok(self.pop_arg()? != 0)
-        } else {
-            Err(Error::Message("expected bool".to_string()))
+    /// The next value token, after an EXTEND token the 64-bit one it extends
+    fn peek_value_token(&self) -> Result<(pd::Tokens, bool)> {
+        match self.peek_token()? {
+            pd::Tokens::EXTEND_TOKEN(_) => self
+                .input
+                .tokens
+                .get(self.token_offset + 1)
+                .map(|&token| (token, true))
+                .ok_or(Error::NoMoreTokens),
+            token => Ok((token, false)),
+        }
+    }
+
+    fn pop_value_token(&mut self) -> Result<(pd::Tokens, bool)> {
+        let (token, extended) = self.peek_value_token()?;
+
+        self.token_offset += 1 + usize::from(extended);
+
+        Ok((token, extended))
+    }
+
+    /// 64-bit values are stored in two args, the low half first
+    fn pop_arg64(&mut self) -> Result<u64> {
+        let low = u64::from(self.pop_arg()?);
+        let high = u64::from(self.pop_arg()?);
+
+        Ok(high << 32 | low)
+    }
+
+    fn parse_i64(&mut self) -> Result<i64> {
+        match self.pop_value_token()? {
+            (pd::Tokens::SINT_TOKEN(_), true) => Ok(self.pop_arg64()? as i64),
+            (pd::Tokens::SINT_TOKEN(_), false) => Ok(i64::from(self.pop_arg()? as i32)),
+            _ => Err(Error::ExpectedSintToken),
+        }
+    }
+
+    fn parse_u64(&mut self) -> Result<u64> {
+        match self.pop_value_token()? {
+            (pd::Tokens::UINT_TOKEN(_), true) => self.pop_arg64(),
+            (pd::Tokens::UINT_TOKEN(_), false) => Ok(u64::from(self.pop_arg()?)),
+            _ => Err(Error::ExpectedUintToken),
+        }
+    }
+
+    fn parse_f64(&mut self) -> Result<f64> {
+        match self.pop_value_token()? {
+            (pd::Tokens::FLOAT_TOKEN(_), true) => Ok(f64::from_bits(self.pop_arg64()?)),
+            (pd::Tokens::FLOAT_TOKEN(_), false) => Ok(f64::from(f32::from_bits(self.pop_arg()?))),
+            _ => Err(Error::ExpectedFloatToken),
         }
     }
 
     fn parse_string(&mut self) -> Result<&'input str> {
-        if let pd::Tokens::STRING_TOKEN(_) = *self.pop_token()? {
+        if let pd::Tokens::STRING_TOKEN(_) = self.pop_token()? {
             let arg = self.pop_arg()?;
-            Ok(&self.input.strings[arg as usize])
+            self.input
+                .strings
+                .get(arg as usize)
+                .map(|value| value.as_ref())
+                .ok_or(Error::InvalidFormat)
         } else {
             Err(Error::ExpectedString)
         }
@@ -94,162 +146,152 @@ impl<'child, 'input> ChildDeserializer<'child, 'input> {
         Self { de, field }
     }
 
-    fn peek_token(&self) -> Result<&'input pd::Tokens> {
-        self.de.peek_token()
+    fn peek_name(&self) -> Result<&'input str> {
+        self.de.peek_name()
     }
 }
 impl<'a, 'child, 'input> de::Deserializer<'input> for &'a mut ChildDeserializer<'child, 'input> {
     type Error = Error;
 
-    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        de::Deserializer::deserialize_any(&mut *self.de, visitor)
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        visitor.visit_bool(self.de.parse_bool()?)
+        de::Deserializer::deserialize_bool(&mut *self.de, visitor)
     }
 
     fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        de::Deserializer::deserialize_i8(&mut *self.de, visitor)
     }
 
     fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        de::Deserializer::deserialize_i16(&mut *self.de, visitor)
     }
 
     fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        if let pd::Tokens::SINT_TOKEN(_) = *self.de.pop_token()? {
-            let arg = self.de.pop_arg()?;
-            visitor.visit_i32(arg as i32)
-        } else {
-            Err(Error::ExpectedSintToken)
-        }
+        de::Deserializer::deserialize_i32(&mut *self.de, visitor)
     }
 
     fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        de::Deserializer::deserialize_i64(&mut *self.de, visitor)
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        de::Deserializer::deserialize_u8(&mut *self.de, visitor)
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        de::Deserializer::deserialize_u16(&mut *self.de, visitor)
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        if let pd::Tokens::UINT_TOKEN(_) = *self.de.pop_token()? {
-            visitor.visit_u32(self.de.pop_arg()?)
-        } else {
-            Err(Error::ExpectedUintToken)
-        }
+        de::Deserializer::deserialize_u32(&mut *self.de, visitor)
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        de::Deserializer::deserialize_u64(&mut *self.de, visitor)
     }
 
-    // Float parsing is stupidly hard.
-    fn deserialize_f32<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        de::Deserializer::deserialize_f32(&mut *self.de, visitor)
     }
 
-    fn deserialize_f64<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        de::Deserializer::deserialize_f64(&mut *self.de, visitor)
     }
 
-    fn deserialize_char<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        de::Deserializer::deserialize_char(&mut *self.de, visitor)
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        visitor.visit_borrowed_str(self.de.parse_string()?)
+        de::Deserializer::deserialize_str(&mut *self.de, visitor)
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        self.deserialize_str(visitor)
+        de::Deserializer::deserialize_string(&mut *self.de, visitor)
     }
 
-    fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        de::Deserializer::deserialize_bytes(&mut *self.de, visitor)
     }
 
-    fn deserialize_byte_buf<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        de::Deserializer::deserialize_byte_buf(&mut *self.de, visitor)
     }
 
-    fn deserialize_option<V>(self, _visitor: V) -> Result<V::Value>
+    // only the fields whose property is there are read
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        visitor.visit_some(self)
     }
 
-    fn deserialize_unit<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        de::Deserializer::deserialize_unit(&mut *self.de, visitor)
     }
 
-    // Unit struct means a named value containing no data.
-    fn deserialize_unit_struct<V>(self, _name: &'static str, _visitor: V) -> Result<V::Value>
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        de::Deserializer::deserialize_unit_struct(&mut *self.de, name, visitor)
     }
 
     fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
@@ -287,11 +329,11 @@ impl<'a, 'child, 'input> de::Deserializer<'input> for &'a mut ChildDeserializer<
         self.deserialize_seq(visitor)
     }
 
-    fn deserialize_map<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        de::Deserializer::deserialize_map(&mut *self.de, visitor)
     }
 
     // Structs look just like maps in JSON.
@@ -302,34 +344,33 @@ impl<'a, 'child, 'input> de::Deserializer<'input> for &'a mut ChildDeserializer<
     // the fields cannot be known ahead of time is probably a map.
     fn deserialize_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        de::Deserializer::deserialize_struct(&mut *self.de, name, fields, visitor)
     }
 
     fn deserialize_enum<V>(
         self,
-        _name: &'static str,
-        _variants: &'static [&'static str],
+        name: &'static str,
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        unimplemented!()
+        de::Deserializer::deserialize_enum(&mut *self.de, name, variants, visitor)
     }
 
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'input>,
     {
-        let token = self.de.peek_token()?;
-        let value = visitor.visit_borrowed_str(token.value())?;
+        let value = visitor.visit_borrowed_str(self.de.peek_name()?)?;
         Ok(value)
     }
 
@@ -352,21 +393,31 @@ impl<'a, 'child, 'input> de::Deserializer<'input> for &'a mut ChildDeserializer<
     }
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     type Error = Error;
 
-    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        // the token tells the stored type, but not what a struct holds
+        match self.peek_value_token()? {
+            (pd::Tokens::SINT_TOKEN(_), _) => self.deserialize_i64(visitor),
+            (pd::Tokens::UINT_TOKEN(_), _) => self.deserialize_u64(visitor),
+            (pd::Tokens::FLOAT_TOKEN(_), _) => self.deserialize_f64(visitor),
+            (pd::Tokens::STRING_TOKEN(_), false) => self.deserialize_str(visitor),
+            (pd::Tokens::FLAG_TOKEN(_), _) => self.deserialize_unit(visitor),
+            _ => Err(Error::Unsupported(
+                "a struct or extended value of unknown type",
+            )),
+        }
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        if let pd::Tokens::SINT_TOKEN(_) = *self.pop_token()? {
+        if let pd::Tokens::SINT_TOKEN(_) = self.pop_token()? {
             let arg = self.pop_arg()?;
             visitor.visit_bool(arg != 0)
         } else {
@@ -378,21 +429,21 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        self.deserialize_i32(visitor)
     }
 
     fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        self.deserialize_i32(visitor)
     }
 
     fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        if let pd::Tokens::SINT_TOKEN(_) = *self.pop_token()? {
+        if let pd::Tokens::SINT_TOKEN(_) = self.pop_token()? {
             let arg = self.pop_arg()?;
             visitor.visit_i32(arg as i32)
         } else {
@@ -404,28 +455,28 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        visitor.visit_i64(self.parse_i64()?)
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        self.deserialize_u32(visitor)
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        self.deserialize_u32(visitor)
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        if let pd::Tokens::UINT_TOKEN(_) = *self.pop_token()? {
+        if let pd::Tokens::UINT_TOKEN(_) = self.pop_token()? {
             visitor.visit_u32(self.pop_arg()?)
         } else {
             Err(Error::ExpectedUintToken)
@@ -436,29 +487,32 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        visitor.visit_u64(self.parse_u64()?)
     }
 
-    // Float parsing is stupidly hard.
-    fn deserialize_f32<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        if let (pd::Tokens::FLOAT_TOKEN(_), false) = self.pop_value_token()? {
+            visitor.visit_f32(f32::from_bits(self.pop_arg()?))
+        } else {
+            Err(Error::ExpectedFloatToken)
+        }
     }
 
-    fn deserialize_f64<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        visitor.visit_f64(self.parse_f64()?)
     }
 
     fn deserialize_char<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        Err(Error::Unsupported("char"))
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
@@ -479,36 +533,44 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        Err(Error::Unsupported("bytes"))
     }
 
     fn deserialize_byte_buf<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        Err(Error::Unsupported("bytes"))
     }
 
-    fn deserialize_option<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        // a property that is not there is absent, also at the end of the record
+        match self.peek_token() {
+            Err(Error::NoMoreTokens) => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
     }
 
-    fn deserialize_unit<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        if let pd::Tokens::FLAG_TOKEN(_) = self.pop_token()? {
+            visitor.visit_unit()
+        } else {
+            Err(Error::ExpectedFlagToken)
+        }
     }
 
     // Unit struct means a named value containing no data.
-    fn deserialize_unit_struct<V>(self, _name: &'static str, _visitor: V) -> Result<V::Value>
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        self.deserialize_unit(visitor)
     }
 
     fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
@@ -518,11 +580,11 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_seq<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        Err(Error::Unsupported("a sequence outside of a struct"))
     }
 
     fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
@@ -548,7 +610,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        Err(Error::Unsupported("map"))
     }
 
     // Structs look just like maps in JSON.
@@ -604,8 +666,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let token = self.peek_token()?;
-        let value = visitor.visit_borrowed_str(token.value())?;
+        let value = visitor.visit_borrowed_str(self.peek_name()?)?;
         Ok(value)
     }
 
@@ -646,10 +707,11 @@ impl<'a, 'child, 'de> SeqAccess<'de> for SameField<'a, 'child, 'de> {
     where
         T: DeserializeSeed<'de>,
     {
-        if self.de.peek_token()?.value() != self.field {
-            Ok(None)
-        } else {
-            seed.deserialize(&mut *self.de).map(Some)
+        // the repeated property may be the last one of the record
+        match self.de.peek_name() {
+            Ok(name) if name == self.field => seed.deserialize(&mut *self.de).map(Some),
+            Ok(_) | Err(Error::NoMoreTokens) => Ok(None),
+            Err(error) => Err(error),
         }
     }
 }
@@ -657,6 +719,8 @@ impl<'a, 'child, 'de> SeqAccess<'de> for SameField<'a, 'child, 'de> {
 struct StructAccess<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
     fields: VecDeque<&'static str>,
+    /// The field whose value is read next
+    field: &'static str,
 }
 
 impl<'a, 'de> StructAccess<'a, 'de> {
@@ -664,6 +728,7 @@ impl<'a, 'de> StructAccess<'a, 'de> {
         Self {
             de,
             fields: fields.iter().cloned().collect(),
+            field: "",
         }
     }
 }
@@ -675,24 +740,28 @@ impl<'de, 'a> de::MapAccess<'de> for StructAccess<'a, 'de> {
     where
         K: DeserializeSeed<'de>,
     {
-        let field = self.fields.pop_front();
-        if let Some(name) = field {
-            if self.de.has_token(name)? {
-                seed.deserialize(&mut *self.de).map(Some)
-            } else {
-                Ok(None)
+        // fields without a property are skipped, serde reads them as missing
+        while let Some(name) = self.fields.pop_front() {
+            match self.de.has_token(name) {
+                Ok(true) => {
+                    self.field = name;
+                    return seed.deserialize(&mut *self.de).map(Some);
+                }
+                Ok(false) => {}
+                Err(Error::NoMoreTokens) => return Ok(None),
+                Err(error) => return Err(error),
             }
-        } else {
-            Ok(None)
         }
+
+        Ok(None)
     }
 
     fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
     where
         V: DeserializeSeed<'de>,
     {
-        let token = self.de.peek_token()?;
-        seed.deserialize(&mut *self.de)
+        // a sequence takes every following property with the name of the field
+        seed.deserialize(&mut ChildDeserializer::new(&mut *self.de, self.field))
     }
 }
 
@@ -760,9 +829,11 @@ impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
 }
 
 ////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
 mod tests {
     use super::*;
-    use crate::pd::{PersistentDataRecord, Tokens};
+    use crate::pd::PdrBuilder;
+
     #[test]
     fn test_simple_values() {
         #[derive(serde::Deserialize, PartialEq, Debug)]
@@ -772,17 +843,12 @@ mod tests {
             b: String,
         }
 
-        let j = PersistentDataRecord {
-            _TokenOffset: 0,
-            _ArgOffset: 0,
-            tokens: vec![
-                Tokens::UINT_TOKEN("int".to_string()),
-                Tokens::STRING_TOKEN("a".to_string()),
-                Tokens::STRING_TOKEN("b".to_string()),
-            ],
-            args: vec![1, 1, 2],
-            strings: vec!["int".to_string(), "a".to_string(), "b".to_string()],
-        };
+        let j = PdrBuilder::new()
+            .uint("int", 1)
+            .string("a", "a")
+            .string("b", "b")
+            .build()
+            .unwrap();
         let expected = Test {
             int: 1,
             a: "a".to_owned(),
@@ -791,7 +857,6 @@ mod tests {
         assert_eq!(expected, from_pdr(&j).unwrap());
     }
 
-    /*
     #[test]
     fn test_struct() {
         #[derive(serde::Deserialize, PartialEq, Debug)]
@@ -800,22 +865,12 @@ mod tests {
             seq: Vec<String>,
         }
 
-        let j = PersistentDataRecord {
-            _TokenOffset: 0,
-            _ArgOffset: 0,
-            tokens: vec![
-                Tokens::UINT_TOKEN("int".to_string()),
-                Tokens::STRING_TOKEN("seq".to_string()),
-                Tokens::STRING_TOKEN("seq".to_string()),
-            ],
-            args: vec![1, 2, 3],
-            strings: vec![
-                "int".to_string(),
-                "seq".to_string(),
-                "a".to_string(),
-                "b".to_string(),
-            ],
-        };
+        let j = PdrBuilder::new()
+            .uint("int", 1)
+            .string("seq", "a")
+            .string("seq", "b")
+            .build()
+            .unwrap();
         let expected = Test {
             int: 1,
             seq: vec!["a".to_owned(), "b".to_owned()],
@@ -823,6 +878,113 @@ mod tests {
         assert_eq!(expected, from_pdr(&j).unwrap());
     }
 
+    #[test]
+    fn test_option_64_bit_and_float_values() {
+        #[derive(serde::Deserialize, PartialEq, Debug)]
+        struct Test {
+            size: u64,
+            offset: Option<i64>,
+            ratio: f32,
+            scale: Option<f64>,
+            parts: Vec<u8>,
+            flag: Option<()>,
+            mirror: Option<String>,
+        }
+
+        let j = PdrBuilder::new()
+            .uint64("size", 1 << 40)
+            .float("ratio", 0.5)
+            .float64("scale", 1.25)
+            .uint("parts", 1)
+            .uint("parts", 255)
+            .flag("flag")
+            .build()
+            .unwrap();
+        let expected = Test {
+            size: 1 << 40,
+            offset: None,
+            ratio: 0.5,
+            scale: Some(1.25),
+            parts: vec![1, 255],
+            flag: Some(()),
+            mirror: None,
+        };
+        assert_eq!(expected, from_pdr(&j).unwrap());
+
+        let j = PdrBuilder::new().uint("parts", 256).build().unwrap();
+        assert!(from_pdr::<Vec<u8>>(&j).is_err());
+    }
+
+    #[test]
+    fn test_wide_values_of_fuzz_target() {
+        // fuzz/corpus/deserializer/wide_values.pdr, it panicked on the 64-bit _FileSize
+        #[allow(non_snake_case)]
+        #[derive(serde::Deserialize, PartialEq, Debug)]
+        struct Version<'a> {
+            _VersionNumber: u32,
+            _FileName: &'a str,
+            _IsOptional: bool,
+            _Priority: i32,
+            _UnpackTo: String,
+            _FileSize: u64,
+            _FileTime: Option<i64>,
+            _Ratio: f32,
+            _Scale: Option<f64>,
+            _HashKey: Vec<u32>,
+            _Mirror: Option<&'a str>,
+        }
+
+        let j = PdrBuilder::new()
+            .uint("_VersionNumber", 1028)
+            .string("_FileName", "ryzom_01028.idx")
+            .sint("_IsOptional", 0)
+            .sint("_Priority", 2)
+            .string("_UnpackTo", "data")
+            .uint64("_FileSize", 1 << 40)
+            .float("_Ratio", 0.5)
+            .float64("_Scale", 1.25)
+            .uint("_HashKey", 1)
+            .uint("_HashKey", 2)
+            .build()
+            .unwrap();
+        let version: Version = from_pdr(&j).unwrap();
+        assert_eq!(
+            (1 << 40, None, Some(1.25)),
+            (version._FileSize, version._FileTime, version._Scale)
+        );
+        assert_eq!(vec![1, 2], version._HashKey);
+
+        // an EXTEND token cut off before its value or second arg
+        use pd::Tokens::*;
+        let cut = pd::PersistentDataRecord::new(vec![EXTEND_TOKEN(0)], vec![], vec!["_FileSize"]);
+        assert!(matches!(from_pdr::<u64>(&cut), Err(Error::NoMoreTokens)));
+        let cut = pd::PersistentDataRecord::new(
+            vec![EXTEND_TOKEN(0), UINT_TOKEN(0)],
+            vec![1],
+            vec!["_FileSize"],
+        );
+        assert!(matches!(from_pdr::<u64>(&cut), Err(Error::NoMoreArgs)));
+    }
+
+    #[test]
+    fn test_unsupported_types() {
+        let j = PdrBuilder::new().string("a", "a").build().unwrap();
+
+        let error = from_pdr::<std::collections::HashMap<String, String>>(&j).unwrap_err();
+        assert_eq!("map can not be read from a PDR", error.to_string());
+        assert!(matches!(from_pdr::<char>(&j), Err(Error::Unsupported(_))));
+        assert!(matches!(
+            from_pdr::<Vec<u32>>(&j),
+            Err(Error::Unsupported(_))
+        ));
+        assert_eq!(Some("a"), from_pdr::<Option<&str>>(&j).unwrap());
+        assert!(matches!(
+            from_pdr::<f32>(&j),
+            Err(Error::ExpectedFloatToken)
+        ));
+    }
+
+    /*
     #[test]
     fn test_enum() {
         #[derive(Deserialize, PartialEq, Debug)]
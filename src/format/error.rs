@@ -1,4 +1,3 @@
-use std;
 use std::fmt::{self, Display};
 
 use serde::{de, ser};
@@ -12,10 +11,14 @@ pub enum Error {
     ExpectedString,
     ExpectedUintToken,
     ExpectedSintToken,
+    ExpectedFloatToken,
+    ExpectedFlagToken,
     ExpectedEnum,
     ExpectedTokenWithName(String),
     ExpectedBeginToken,
     ExpectedEndToken,
+    /// A type of the serde data model that has no representation in a PDR
+    Unsupported(&'static str),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -44,10 +47,13 @@ impl Display for Error {
             Error::ExpectedEndToken => formatter.write_str("expected end token"),
             Error::ExpectedSintToken => formatter.write_str("expected sint token"),
             Error::ExpectedUintToken => formatter.write_str("expected uint token"),
+            Error::ExpectedFloatToken => formatter.write_str("expected float token"),
+            Error::ExpectedFlagToken => formatter.write_str("expected flag token"),
             Error::ExpectedEnum => formatter.write_str("expected enum"),
             Error::ExpectedTokenWithName(name) => {
                 write!(formatter, "expected token with name({})", name)
             }
+            Error::Unsupported(what) => write!(formatter, "{} can not be read from a PDR", what),
         }
     }
 }
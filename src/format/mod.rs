@@ -1,3 +1,4 @@
-mod de;
-mod error;
+pub mod de;
+pub mod error;
 
+pub use de::{from_pdr, Deserializer};
@@ -0,0 +1,228 @@
+use crate::output::{self, Row};
+use crate::patch;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::path::Path;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS indexes (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    number INTEGER
+);
+CREATE TABLE IF NOT EXISTS files (
+    index_id INTEGER NOT NULL REFERENCES indexes (id),
+    name TEXT NOT NULL,
+    PRIMARY KEY (index_id, name)
+);
+CREATE TABLE IF NOT EXISTS versions (
+    index_id INTEGER NOT NULL REFERENCES indexes (id),
+    file TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    packed_size INTEGER NOT NULL,
+    time INTEGER NOT NULL,
+    patch_size INTEGER NOT NULL,
+    hash_key TEXT NOT NULL,
+    PRIMARY KEY (index_id, file, version)
+);
+CREATE TABLE IF NOT EXISTS category_files (
+    index_id INTEGER NOT NULL REFERENCES indexes (id),
+    category TEXT NOT NULL,
+    file TEXT NOT NULL,
+    PRIMARY KEY (index_id, category, file)
+);
+CREATE INDEX IF NOT EXISTS versions_by_file ON versions (file, version);
+";
+
+/// Database of the files, versions and category memberships of many indexes
+pub struct History {
+    connection: Connection,
+}
+
+/// A file version together with the first index that carried it
+#[derive(Debug, PartialEq, Serialize)]
+pub struct HistoryRow {
+    pub version: u32,
+    pub size: patch::FileSize,
+    pub packed_size: patch::FileSize,
+    pub patch_size: patch::FileSize,
+    pub time: patch::FileTime,
+    pub hash_key: String,
+    pub first_index: String,
+}
+
+impl History {
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<History> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+
+        Ok(History { connection })
+    }
+
+    /// Stores an index under its file name, returns `false` if it was ingested before
+    pub fn ingest(
+        &mut self,
+        name: &str,
+        product: &patch::CProductDescriptionForClient,
+    ) -> rusqlite::Result<bool> {
+        let transaction = self.connection.transaction()?;
+        let inserted = transaction.execute(
+            "INSERT OR IGNORE INTO indexes (name, number) VALUES (?1, ?2)",
+            params![name, index_number(name)],
+        )?;
+        if inserted == 0 {
+            return Ok(false);
+        }
+        let index_id = transaction.last_insert_rowid();
+
+        {
+            let mut insert_file = transaction
+                .prepare("INSERT OR IGNORE INTO files (index_id, name) VALUES (?1, ?2)")?;
+            let mut insert_version = transaction.prepare(
+                "INSERT OR IGNORE INTO versions
+                 (index_id, file, version, size, packed_size, time, patch_size, hash_key)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
+            let mut insert_member = transaction.prepare(
+                "INSERT OR IGNORE INTO category_files (index_id, category, file) VALUES (?1, ?2, ?3)",
+            )?;
+
+            for file in &product._Files._Files {
+                insert_file.execute(params![index_id, file._FileName])?;
+                for version in &file._Versions {
+                    insert_version.execute(params![
+                        index_id,
+                        file._FileName,
+                        version._VersionNumber,
+                        version._FileSize.0,
+                        version._7ZFileSize.0,
+                        version._FileTime.0,
+                        version._PatchSize.0,
+                        hash_key(&version._HashKey),
+                    ])?;
+                }
+            }
+            for category in &product._Categories._Category {
+                for file in &category._Files {
+                    insert_member.execute(params![index_id, category._Name, file])?;
+                }
+            }
+        }
+        transaction.commit()?;
+
+        Ok(true)
+    }
+
+    /// Every known version of a file, oldest first
+    pub fn file_history(&self, file: &str) -> rusqlite::Result<Vec<HistoryRow>> {
+        let mut statement = self.connection.prepare(
+            "SELECT version, size, packed_size, patch_size, time, hash_key, name FROM (
+                 SELECT versions.*, indexes.name, ROW_NUMBER() OVER (
+                     PARTITION BY versions.version
+                     ORDER BY indexes.number IS NULL, indexes.number, indexes.name
+                 ) AS rank
+                 FROM versions JOIN indexes ON indexes.id = versions.index_id
+                 WHERE versions.file = ?1
+             )
+             WHERE rank = 1
+             ORDER BY version",
+        )?;
+        let rows = statement.query_map([file], |row| {
+            Ok(HistoryRow {
+                version: row.get(0)?,
+                size: patch::FileSize(row.get(1)?),
+                packed_size: patch::FileSize(row.get(2)?),
+                patch_size: patch::FileSize(row.get(3)?),
+                time: patch::FileTime(row.get(4)?),
+                hash_key: row.get(5)?,
+                first_index: row.get(6)?,
+            })
+        })?;
+
+        rows.collect()
+    }
+}
+
+impl Row for HistoryRow {
+    const TABLE: &'static str = "versions";
+    const COLUMNS: &'static [(&'static str, bool)] = &[
+        ("version", true),
+        ("size", true),
+        ("packed_size", true),
+        ("patch_size", true),
+        ("time", true),
+        ("first_index", false),
+    ];
+
+    fn cells(&self, raw: bool) -> Vec<String> {
+        vec![
+            self.version.to_string(),
+            output::size_cell(Some(self.size), raw),
+            output::size_cell(Some(self.packed_size), raw),
+            output::size_cell(Some(self.patch_size), raw),
+            output::time_cell(Some(self.time), raw),
+            self.first_index.clone(),
+        ]
+    }
+}
+
+/// The release number of an index named like `ryzom_01028.idx`
+pub fn index_number(name: &str) -> Option<u32> {
+    let stem = name.strip_suffix(".idx").unwrap_or(name);
+    let digits = stem.rsplit('_').next()?;
+
+    digits.parse().ok()
+}
+
+fn hash_key(words: &[u32]) -> String {
+    words.iter().map(|word| format!("{:08x}", word)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::fixture;
+
+    fn product(versions: &[(u32, u32)]) -> patch::CProductDescriptionForClient {
+        let versions = versions
+            .iter()
+            .map(|&(number, size)| fixture::version(number, size, size / 10))
+            .collect();
+
+        fixture::product(vec![fixture::file("fauna_maps.bnp", versions)], vec![])
+    }
+
+    #[test]
+    fn test_file_history() {
+        let mut history = History::open(":memory:").unwrap();
+
+        assert!(history
+            .ingest("ryzom_01021.idx", &product(&[(1, 100), (1021, 200)]))
+            .unwrap());
+        assert!(history
+            .ingest("ryzom_01020.idx", &product(&[(1, 100)]))
+            .unwrap());
+        assert!(!history
+            .ingest("ryzom_01020.idx", &product(&[(1, 100)]))
+            .unwrap());
+
+        let rows = history.file_history("fauna_maps.bnp").unwrap();
+        let summary: Vec<(u32, &str)> = rows
+            .iter()
+            .map(|row| (row.version, row.first_index.as_str()))
+            .collect();
+
+        assert_eq!(
+            vec![(1, "ryzom_01020.idx"), (1021, "ryzom_01021.idx")],
+            summary
+        );
+        assert_eq!("0000000100000001000000010000000100000001", rows[0].hash_key);
+    }
+
+    #[test]
+    fn test_index_number() {
+        assert_eq!(Some(1028), index_number("ryzom_01028.idx"));
+        assert_eq!(None, index_number("ryzom_live.idx"));
+    }
+}
@@ -0,0 +1,494 @@
+use crate::error::Section;
+use crate::output::{self, Row};
+use crate::pd;
+use serde::Serialize;
+use std::io::{self, Cursor, Write};
+
+/// Layout of a PDR file as stored, decoded without trusting the header
+#[derive(Debug, Serialize)]
+pub struct Inspection {
+    pub file_size: u64,
+    /// Name and version of the detected format, `None` for an unknown version
+    pub format: Option<String>,
+    /// Places where the file disagrees with its header or with itself
+    pub problems: Vec<String>,
+    pub header: Option<pd::Header>,
+    pub tokens: Vec<TokenEntry>,
+    pub args: Vec<ArgEntry>,
+    pub strings: Vec<StringEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HeaderField {
+    pub offset: u64,
+    pub field: &'static str,
+    pub value: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenEntry {
+    pub index: usize,
+    pub offset: u64,
+    pub raw: pd::Token,
+    /// The low three bits, see [`pd::parse_token`]
+    pub type_bits: u16,
+    pub type_name: &'static str,
+    pub string_index: pd::StringId,
+    /// `None` if the string index is out of range
+    pub name: Option<String>,
+    /// Index of the first arg of the token and how many it uses
+    pub first_arg: usize,
+    pub arg_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArgEntry {
+    pub index: usize,
+    pub offset: u64,
+    pub value: pd::Arg,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StringEntry {
+    pub index: usize,
+    pub offset: u64,
+    /// Invalid UTF-8 is replaced
+    pub value: String,
+}
+
+const TYPE_NAMES: [&str; 8] = [
+    "BEGIN", "END", "SINT", "UINT", "FLOAT", "STRING", "FLAG", "EXTEND",
+];
+
+pub fn inspect(data: &[u8]) -> Inspection {
+    let file_size = data.len() as u64;
+    let mut inspection = Inspection {
+        file_size,
+        format: None,
+        problems: Vec::new(),
+        header: None,
+        tokens: Vec::new(),
+        args: Vec::new(),
+        strings: Vec::new(),
+    };
+    let problems = &mut inspection.problems;
+
+    let file_encoding = pd::FileEncoding::detect(data);
+    if file_encoding != pd::FileEncoding::Binary {
+        inspection.format = Some(format!("{} PDR", file_encoding));
+        problems.push(format!(
+            "the file is {} PDR, only binary files have tables to inspect",
+            file_encoding
+        ));
+        return inspection;
+    }
+
+    let Ok(header) = pd::Header::read(&mut Cursor::new(data)) else {
+        problems.push(format!(
+            "the file holds {} bytes, too few for the {} byte header",
+            file_size,
+            pd::HEADER_SIZE
+        ));
+        return inspection;
+    };
+    match header.format() {
+        Some(format) => {
+            inspection.format = Some(format!("{}, version {}", format.name, format.version));
+        }
+        None => problems.push(format!(
+            "version {} is not supported, the sections are assumed to use the layout of version {}",
+            header.version,
+            header.layout().version
+        )),
+    }
+    if header.total_size as u64 != file_size {
+        problems.push(format!(
+            "total_size is {} bytes but the file holds {} bytes",
+            header.total_size, file_size
+        ));
+    }
+    if header.expected_size() != header.total_size as u64 {
+        problems.push(format!(
+            "the sections add up to {} bytes but total_size is {} bytes",
+            header.expected_size(),
+            header.total_size
+        ));
+    }
+
+    let token_bytes = section(
+        data,
+        header.tokens_offset(),
+        header.section_end(Section::TokenTable),
+        "token table",
+        problems,
+    );
+    let arg_bytes = section(
+        data,
+        header.args_offset(),
+        header.section_end(Section::ArgTable),
+        "arg table",
+        problems,
+    );
+    let string_bytes = section(
+        data,
+        header.strings_offset(),
+        header.section_end(Section::StringTable),
+        "string table",
+        problems,
+    );
+
+    inspection.args = arg_bytes
+        .chunks_exact(size_of::<pd::Arg>())
+        .enumerate()
+        .map(|(index, bytes)| ArgEntry {
+            index,
+            offset: header.args_offset() + (index * size_of::<pd::Arg>()) as u64,
+            value: pd::Arg::from_le_bytes(bytes.try_into().unwrap()),
+        })
+        .collect();
+
+    let mut offset = header.strings_offset();
+    for (index, bytes) in split_strings(string_bytes).enumerate() {
+        inspection.strings.push(StringEntry {
+            index,
+            offset,
+            value: String::from_utf8_lossy(bytes).into_owned(),
+        });
+        offset += bytes.len() as u64 + 1;
+    }
+    if inspection.strings.len() != header.string_count as usize {
+        problems.push(format!(
+            "the string table holds {} strings but string_count is {}",
+            inspection.strings.len(),
+            header.string_count
+        ));
+    }
+
+    inspection.tokens = decode_tokens(
+        &header,
+        token_bytes,
+        &inspection.args,
+        &inspection.strings,
+        problems,
+    );
+    let used_args = inspection
+        .tokens
+        .last()
+        .map_or(0, |token| token.first_arg + token.arg_count);
+    if used_args != header.arg_count as usize {
+        problems.push(format!(
+            "the tokens use {} args but arg_count is {}",
+            used_args, header.arg_count
+        ));
+    }
+
+    inspection.header = Some(header);
+    inspection
+}
+
+/// The bytes of a section that are present in the file
+fn section<'a>(
+    data: &'a [u8],
+    start: u64,
+    end: u64,
+    name: &str,
+    problems: &mut Vec<String>,
+) -> &'a [u8] {
+    let size = data.len() as u64;
+    if end > size {
+        problems.push(format!(
+            "the {} should end at byte {} but the file ends at byte {}",
+            name, end, size
+        ));
+    }
+
+    &data[start.min(size) as usize..end.min(size) as usize]
+}
+
+/// Null terminated strings, the terminator of the last one is optional
+fn split_strings(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+    bytes.split(|&b| b == 0).filter(move |_| !bytes.is_empty())
+}
+
+fn decode_tokens(
+    header: &pd::Header,
+    bytes: &[u8],
+    args: &[ArgEntry],
+    strings: &[StringEntry],
+    problems: &mut Vec<String>,
+) -> Vec<TokenEntry> {
+    let mut tokens = Vec::with_capacity(bytes.len() / size_of::<pd::Token>());
+    let mut next_arg = 0;
+    let mut extended = false;
+    let mut open: Vec<pd::StringId> = Vec::new();
+    let mut out_of_range: Vec<usize> = Vec::new();
+
+    for (index, bytes) in bytes.chunks_exact(size_of::<pd::Token>()).enumerate() {
+        let raw = pd::Token::from_le_bytes(bytes.try_into().unwrap());
+        let (type_bits, string_index) = pd::split_token(raw);
+        let name = strings
+            .get(string_index as usize)
+            .map(|string| string.value.clone());
+        if name.is_none() {
+            out_of_range.push(index);
+        }
+
+        if type_bits == 0 {
+            open.push(string_index);
+        } else if type_bits == 1 && open.pop() != Some(string_index) {
+            problems.push(format!("token {} ends a struct that is not open", index));
+        }
+        let extend_type = args.get(next_arg).map(|arg| arg.value);
+        let arg_count = pd::arg_count(&pd::unchecked_token(raw), extended, extend_type)
+            .unwrap_or_else(|| {
+                if let Some(extend_type) = extend_type {
+                    problems.push(format!(
+                        "token {} has the unknown extend type {}",
+                        index, extend_type
+                    ));
+                }
+                1
+            });
+        if extended && !(2..=5).contains(&type_bits) {
+            problems.push(format!(
+                "token {} follows an EXTEND token but cannot be extended",
+                index
+            ));
+        }
+        extended = type_bits == 7;
+
+        tokens.push(TokenEntry {
+            index,
+            offset: header.tokens_offset() + (index * size_of::<pd::Token>()) as u64,
+            raw,
+            type_bits,
+            type_name: TYPE_NAMES[type_bits as usize],
+            string_index,
+            name,
+            first_arg: next_arg,
+            arg_count,
+        });
+        next_arg += arg_count;
+    }
+    if let Some(first) = out_of_range.first() {
+        problems.push(format!(
+            "{} tokens refer to strings past the {} in the string table, the first is token {}",
+            out_of_range.len(),
+            strings.len(),
+            first
+        ));
+    }
+    if !open.is_empty() {
+        problems.push(format!("{} structs are not closed", open.len()));
+    }
+
+    tokens
+}
+
+fn header_fields(header: &pd::Header) -> Vec<HeaderField> {
+    [
+        ("version", header.version),
+        ("total_size", header.total_size),
+        ("token_count", header.token_count),
+        ("arg_count", header.arg_count),
+        ("string_count", header.string_count),
+        ("strings_size", header.strings_size),
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(index, (field, value))| HeaderField {
+        offset: (index * size_of::<u32>()) as u64,
+        field,
+        value,
+    })
+    .collect()
+}
+
+/// Prints every section as a table followed by the problems found
+pub fn write_report(output: &mut impl Write, inspection: &Inspection) -> io::Result<()> {
+    writeln!(output, "file size: {} bytes", inspection.file_size)?;
+    if let Some(ref format) = inspection.format {
+        writeln!(output, "format: {}", format)?;
+    }
+    if let Some(ref header) = inspection.header {
+        writeln!(output)?;
+        writeln!(output, "header:")?;
+        output::write_rows(output, &header_fields(header), output::Format::Table, true)?;
+        writeln!(output)?;
+        writeln!(
+            output,
+            "tokens: {} at byte {}",
+            inspection.tokens.len(),
+            header.tokens_offset()
+        )?;
+        output::write_rows(output, &inspection.tokens, output::Format::Table, true)?;
+        writeln!(output)?;
+        writeln!(
+            output,
+            "args: {} at byte {}",
+            inspection.args.len(),
+            header.args_offset()
+        )?;
+        output::write_rows(output, &inspection.args, output::Format::Table, true)?;
+        writeln!(output)?;
+        writeln!(
+            output,
+            "strings: {} at byte {}",
+            inspection.strings.len(),
+            header.strings_offset()
+        )?;
+        output::write_rows(output, &inspection.strings, output::Format::Table, true)?;
+    }
+
+    writeln!(output)?;
+    if inspection.problems.is_empty() {
+        writeln!(output, "no problems found")?;
+    } else {
+        writeln!(output, "problems:")?;
+        for problem in &inspection.problems {
+            writeln!(output, "  - {}", problem)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl Row for HeaderField {
+    const TABLE: &'static str = "header";
+    const COLUMNS: &'static [(&'static str, bool)] =
+        &[("offset", true), ("field", false), ("value", true)];
+
+    fn cells(&self, _raw: bool) -> Vec<String> {
+        vec![
+            self.offset.to_string(),
+            self.field.to_string(),
+            self.value.to_string(),
+        ]
+    }
+}
+
+impl Row for TokenEntry {
+    const TABLE: &'static str = "tokens";
+    const COLUMNS: &'static [(&'static str, bool)] = &[
+        ("index", true),
+        ("offset", true),
+        ("raw", false),
+        ("type", false),
+        ("string", true),
+        ("name", false),
+        ("args", false),
+    ];
+
+    fn cells(&self, _raw: bool) -> Vec<String> {
+        let args = match self.arg_count {
+            0 => String::new(),
+            1 => self.first_arg.to_string(),
+            count => format!("{}..{}", self.first_arg, self.first_arg + count - 1),
+        };
+
+        vec![
+            self.index.to_string(),
+            self.offset.to_string(),
+            format!("{:#06x}", self.raw),
+            format!("{} ({})", self.type_name, self.type_bits),
+            self.string_index.to_string(),
+            self.name
+                .clone()
+                .unwrap_or_else(|| "<out of range>".to_string()),
+            args,
+        ]
+    }
+}
+
+impl Row for ArgEntry {
+    const TABLE: &'static str = "args";
+    const COLUMNS: &'static [(&'static str, bool)] = &[
+        ("index", true),
+        ("offset", true),
+        ("value", true),
+        ("hex", false),
+    ];
+
+    fn cells(&self, _raw: bool) -> Vec<String> {
+        vec![
+            self.index.to_string(),
+            self.offset.to_string(),
+            self.value.to_string(),
+            format!("{:#010x}", self.value),
+        ]
+    }
+}
+
+impl Row for StringEntry {
+    const TABLE: &'static str = "strings";
+    const COLUMNS: &'static [(&'static str, bool)] =
+        &[("index", true), ("offset", true), ("value", false)];
+
+    fn cells(&self, _raw: bool) -> Vec<String> {
+        vec![
+            self.index.to_string(),
+            self.offset.to_string(),
+            format!("{:?}", self.value),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect_version_1() {
+        let mut data: Vec<u8> = Vec::new();
+        for value in [1u32, 40, 1, 1, 1, 10] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(b"_FileSize\0");
+        data.extend_from_slice(&3u16.to_le_bytes());
+        data.extend_from_slice(&10u32.to_le_bytes());
+
+        let inspection = inspect(&data);
+
+        assert!(inspection.problems.is_empty(), "{:?}", inspection.problems);
+        assert_eq!(36, inspection.args[0].offset);
+        assert_eq!(Some("_FileSize"), inspection.tokens[0].name.as_deref());
+    }
+
+    #[test]
+    fn test_inspect_truncated() {
+        let mut data: Vec<u8> = Vec::new();
+        for value in [0u32, 42, 2, 1, 2, 10] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        // a uint token and a begin token that refers past the string table
+        data.extend_from_slice(&3u16.to_le_bytes());
+        data.extend_from_slice(&(2u16 << 3).to_le_bytes());
+        data.extend_from_slice(&10u32.to_le_bytes());
+        data.extend_from_slice(b"_FileSi");
+
+        let inspection = inspect(&data);
+
+        assert_eq!(2, inspection.tokens.len());
+        assert_eq!(Some("_FileSi"), inspection.tokens[0].name.as_deref());
+        assert_eq!(
+            vec![10],
+            inspection
+                .args
+                .iter()
+                .map(|arg| arg.value)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                "total_size is 42 bytes but the file holds 39 bytes".to_string(),
+                "the string table should end at byte 42 but the file ends at byte 39".to_string(),
+                "the string table holds 1 strings but string_count is 2".to_string(),
+                "1 tokens refer to strings past the 1 in the string table, the first is token 1"
+                    .to_string(),
+                "1 structs are not closed".to_string(),
+            ],
+            inspection.problems
+        );
+    }
+}
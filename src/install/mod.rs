@@ -0,0 +1,126 @@
+use crate::pd;
+use pdr_derive::{PdrRead, PdrWrite};
+use serde::{Deserialize, Serialize};
+
+/// The patcher's local version file: the version an install was last patched to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, PdrRead, PdrWrite)]
+pub struct CLocalVersion {
+    pub _InstalledVersion: u32,
+    #[pdr(default)]
+    #[serde(default)]
+    pub _ProductName: String,
+    /// The index the installed version was read from
+    #[pdr(default)]
+    #[serde(default)]
+    pub _IndexFileName: String,
+    /// Properties this schema does not know, in file order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[pdr(extra)]
+    pub extra: Vec<pd::Property>,
+}
+
+/// The patcher's state file, what is left of a patch that was interrupted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, PdrRead, PdrWrite)]
+pub struct CPatchState {
+    /// The version the install is being patched to
+    pub _TargetVersion: u32,
+    #[pdr(repeated)]
+    #[serde(default)]
+    pub _PendingFile: Vec<CPendingFile>,
+    /// Properties this schema does not know, in file order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[pdr(extra)]
+    pub extra: Vec<pd::Property>,
+}
+
+/// A file the interrupted patch has not finished
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, PdrRead, PdrWrite)]
+pub struct CPendingFile {
+    pub _FileName: String,
+    /// The installed version, 0 if the file is new
+    #[pdr(default)]
+    #[serde(default)]
+    pub _FromVersion: u32,
+    pub _ToVersion: u32,
+    /// Whether the patch or packed file has been downloaded completely
+    #[pdr(default)]
+    #[serde(default)]
+    pub _Downloaded: bool,
+    /// Properties this schema does not know, in file order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[pdr(extra)]
+    pub extra: Vec<pd::Property>,
+}
+
+/// A client config file stored as PDR, a list of named variables
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, PdrRead, PdrWrite)]
+pub struct CClientConfig {
+    /// Written even without variables, it is how the schema is detected
+    #[pdr(default)]
+    #[serde(default)]
+    pub _Variables: CConfigVariableSet,
+    /// Properties this schema does not know, in file order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[pdr(extra)]
+    pub extra: Vec<pd::Property>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, PdrRead, PdrWrite)]
+pub struct CConfigVariableSet {
+    #[pdr(repeated)]
+    #[serde(default)]
+    pub _Variable: Vec<CConfigVariable>,
+}
+
+/// A config variable with its values as text, arrays have more than one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, PdrRead, PdrWrite)]
+pub struct CConfigVariable {
+    pub _Name: String,
+    #[pdr(repeated)]
+    #[serde(default)]
+    pub _Value: Vec<String>,
+    /// Properties this schema does not know, in file order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[pdr(extra)]
+    pub extra: Vec<pd::Property>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patch_state_round_trip() {
+        let state = CPatchState {
+            _TargetVersion: 1028,
+            _PendingFile: vec![
+                CPendingFile {
+                    _FileName: "fauna_maps.bnp".to_string(),
+                    _FromVersion: 1021,
+                    _ToVersion: 1028,
+                    _Downloaded: true,
+                    extra: Vec::new(),
+                },
+                CPendingFile {
+                    _FileName: "shard.bnp".to_string(),
+                    _FromVersion: 0,
+                    _ToVersion: 1028,
+                    _Downloaded: false,
+                    extra: vec![pd::Property {
+                        name: "_Mirror".to_string(),
+                        value: pd::Value::String("https://cdn.ryzom.com".to_string()),
+                    }],
+                },
+            ],
+            extra: Vec::new(),
+        };
+        let mut writer = pd::Writer::default();
+        pd::Writable::write(&state, &mut writer);
+        let mut pdr = writer.into_record().unwrap();
+
+        assert_eq!(
+            state,
+            <CPatchState as pd::Readable>::read(&mut pdr).unwrap()
+        );
+    }
+}
@@ -0,0 +1,26 @@
+//! Reads, queries and writes the patch index files of Ryzom, NeL persistent data records (PDR)
+//! describing the files a client downloads.
+
+// names of the patch and pd types mirror NeL's C++ sources
+#![allow(non_snake_case, non_camel_case_types, clippy::upper_case_acronyms)]
+
+// the derived pd impls name the crate by its name, also inside of it
+extern crate self as ryzom_patch_info;
+
+pub mod changelog;
+pub mod edit;
+pub mod error;
+pub mod format;
+pub mod history;
+pub mod inspect;
+pub mod install;
+pub mod merge;
+pub mod output;
+pub mod patch;
+pub mod pd;
+pub mod product;
+pub mod query;
+pub mod schema;
+pub mod update;
+
+pub type Result<T> = std::result::Result<T, error::ReadingError>;
@@ -1,153 +1,632 @@
-mod error;
-mod format;
-mod patch;
-mod pd;
-
-use crate::error::ReadingError::InvalidFileFormat;
-use clap::Parser;
-use error::ReadingError;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, Read, Seek};
-
-pub type Result<T> = std::result::Result<T, ReadingError>;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use memmap2::Mmap;
+use ryzom_patch_info::error::{self, ReadingError};
+use ryzom_patch_info::{
+    changelog, edit, history, inspect, merge, output, patch, pd, product, query, schema, Result,
+};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek};
+use std::path::Path;
+use std::process::ExitCode;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
     /// Path to the file to read
     #[arg(short, long)]
-    index_file: String,
+    index_file: Option<String>,
+
+    /// Only print the value of every property with this name, streaming through the file. The file
+    /// has to be seekable, a pipe can not be streamed.
+    #[arg(short, long)]
+    property: Option<String>,
+
+    /// How to read the file, query needs a product description, from-json reads its input with it
+    #[arg(long, value_enum, default_value = "auto")]
+    schema: schema::Schema,
+
+    /// Format of the printed output, defaults to json or to table for the query command
+    #[arg(short, long, value_enum, global = true)]
+    output_format: Option<output::Format>,
+
+    /// Print sizes and times as plain numbers instead of human-readable values in table output
+    #[arg(long, global = true)]
+    raw: bool,
+
+    /// Recover what can be read from corrupted or truncated index files, printing warnings about
+    /// the skipped parts to stderr
+    #[arg(long, global = true)]
+    lenient: bool,
+
+    /// Format of the error messages printed to stderr
+    #[arg(long, value_enum, global = true, default_value = "text")]
+    error_format: error::ErrorFormat,
+
+    /// How to decode strings that are not UTF-8, files written back keep their original bytes
+    #[arg(long, value_enum, global = true, default_value = "utf8")]
+    encoding: pd::Encoding,
+
+    #[command(flatten)]
+    limits: pd::Limits,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List the files matching a filter expression, e.g. `category = main and size > 10MB`
+    Query {
+        /// Filter on the name, category, unpack_to, version, size, packed_size, patch_size, time,
+        /// optional, hidden and incremental fields, combined with and, or, not and parentheses
+        filter: Option<String>,
+    },
+    /// Print the header, token, arg and string tables of the index file and where they disagree,
+    /// without parsing it as a product description
+    Inspect,
+    /// Load every `.idx` file of a directory into a history database, known indexes are skipped
+    Ingest {
+        /// Directory holding the index files
+        directory: String,
+
+        /// Path to the SQLite history database
+        #[arg(short, long, default_value = "history.sqlite")]
+        database: String,
+    },
+    /// Print every version of a file with the first index that carried it
+    History {
+        /// Name of the file, e.g. `fauna_maps.bnp`
+        file: String,
+
+        /// Path to the SQLite history database
+        #[arg(short, long, default_value = "history.sqlite")]
+        database: String,
+    },
+    /// Change the index file and write it back, in place unless `--output-file` is given
+    Edit {
+        #[command(subcommand)]
+        action: edit::Action,
+
+        /// Path of the index file to write
+        #[arg(long, global = true)]
+        output_file: Option<String>,
+    },
+    /// Combine product descriptions into one, every index laid over the ones before it, and
+    /// print the conflicts that were resolved
+    Merge {
+        /// Paths to the index files, the base first
+        #[arg(required = true, num_args = 2..)]
+        inputs: Vec<String>,
+
+        /// Path of the index file to write
+        #[arg(long)]
+        output_file: String,
+
+        /// What to do with a file or category that differs between indexes
+        #[arg(long, value_enum, default_value = "overlay")]
+        on_conflict: merge::Rule,
+    },
+    /// Encode a JSON document as printed by this tool back into a PDR file
+    FromJson {
+        /// Path to the JSON file, `-` reads stdin
+        input: String,
+
+        /// Path of the PDR file to write
+        output: String,
+
+        /// How the PDR file stores its properties, reading detects it
+        #[arg(long, value_enum, default_value = "binary")]
+        file_encoding: pd::FileEncoding,
+    },
+    /// Write release notes for the changes between two consecutive indexes
+    Changelog {
+        /// Path to the older index file
+        old: String,
+
+        /// Path to the newer index file
+        new: String,
+
+        /// Format of the release notes
+        #[arg(short, long, value_enum, default_value = "markdown")]
+        format: changelog::Format,
+
+        /// Only list files whose new version is from this time on, e.g. `2024-01-31`
+        #[arg(long, value_parser = parse_time)]
+        since: Option<patch::FileTime>,
+
+        /// Only list files whose new version is from this time or earlier, a date includes the
+        /// whole day
+        #[arg(long, value_parser = parse_end_time)]
+        until: Option<patch::FileTime>,
+    },
 }
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let args = Args::parse();
+    let error_format = args.error_format;
 
-    let mut pdr = read_index_file(args.index_file)?;
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        // the reader of the output went away, e.g. `head`
+        Err(ReadingError::OutputError(error)) if error.kind() == io::ErrorKind::BrokenPipe => {
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("{}", error.report(error_format));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Loads index files, strictly or recovering what it can
+struct Loader {
+    lenient: bool,
+    error_format: error::ErrorFormat,
+    limits: pd::Limits,
+    encoding: pd::Encoding,
+}
 
-    let patch = patch::CProductDescriptionForClient::from(&mut pdr);
-    let json = serde_json::to_string_pretty(&patch).map_err(|_| InvalidFileFormat)?;
+impl Loader {
+    /// Reads a PDR file with `read`, warnings of lenient reading are printed
+    fn load<T>(
+        &self,
+        filepath: impl AsRef<Path>,
+        read: impl FnOnce(&mut pd::PersistentDataRecord) -> Result<T>,
+    ) -> Result<T> {
+        let filepath = filepath.as_ref();
+        let in_file = |error: ReadingError| error.in_file(filepath.display());
+        let index = map_index_file(filepath).map_err(in_file)?;
+        let mut pdr = pd::read_index_file(&index, self.lenient, self.limits, self.encoding)
+            .map_err(in_file)?;
+        pdr.lenient = self.lenient;
 
-    println!("{}", json);
+        let value = read(&mut pdr).map_err(in_file)?;
+        for warning in pdr.warnings {
+            self.warn(in_file(warning));
+        }
 
-    Ok(())
+        Ok(value)
+    }
+
+    fn load_product(
+        &self,
+        filepath: impl AsRef<Path>,
+    ) -> Result<patch::CProductDescriptionForClient> {
+        self.load(filepath, patch::CProductDescriptionForClient::from)
+    }
+
+    fn load_description(&self, filepath: impl AsRef<Path>) -> Result<product::ProductDescription> {
+        let raw = self.load_product(&filepath)?;
+
+        self.describe(filepath, raw)
+    }
+
+    /// Validates a product description, in lenient mode inconsistencies are repaired
+    fn describe(
+        &self,
+        filepath: impl AsRef<Path>,
+        raw: patch::CProductDescriptionForClient,
+    ) -> Result<product::ProductDescription> {
+        let filepath = filepath.as_ref();
+        if !self.lenient {
+            return product::ProductDescription::try_from(raw)
+                .map_err(|error| ReadingError::from(error).in_file(filepath.display()));
+        }
+
+        let (product, repairs) = product::ProductDescription::repaired(raw);
+        for repair in repairs {
+            self.warn(ReadingError::InvalidProduct(repair).in_file(filepath.display()));
+        }
+
+        Ok(product)
+    }
+
+    fn warn(&self, warning: ReadingError) {
+        eprintln!("{}", warning.report_warning(self.error_format));
+    }
 }
 
-fn read_index_file(filepath: String) -> Result<pd::PersistentDataRecord> {
-    let file = File::open(filepath)?;
-    let file_size = file.metadata()?.len();
-    if file_size < 24 {
-        return Err(ReadingError::InvalidFileFormat);
+fn run(args: Args) -> Result<()> {
+    let loader = Loader {
+        lenient: args.lenient,
+        error_format: args.error_format,
+        limits: args.limits,
+        encoding: args.encoding,
+    };
+
+    match &args.command {
+        Some(Command::Ingest {
+            directory,
+            database,
+        }) => return ingest(&loader, directory, database),
+        Some(Command::History { file, database }) => {
+            let format = args.output_format.unwrap_or(output::Format::Table);
+            return print_history(database, file, format, args.raw);
+        }
+        Some(Command::Changelog {
+            old,
+            new,
+            format,
+            since,
+            until,
+        }) => return print_changelog(&loader, old, new, *format, *since, *until),
+        Some(Command::Merge {
+            inputs,
+            output_file,
+            on_conflict,
+        }) => return merge_indexes(&loader, inputs, output_file, *on_conflict),
+        Some(Command::FromJson {
+            input,
+            output,
+            file_encoding,
+        }) => return from_json(input, output, *file_encoding, args.schema, args.encoding),
+        _ => {}
+    }
+
+    let Some(index_file) = args.index_file else {
+        Args::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "the following required arguments were not provided:\n  --index-file <INDEX_FILE>",
+            )
+            .exit();
+    };
+
+    if let Some(Command::Inspect) = args.command {
+        return print_inspection(&index_file, args.output_format);
     }
-    let mut reader = BufReader::new(file);
 
-    let header = read_header(file_size, &mut reader)?;
-    let mut packed_tokens: Vec<pd::Token> = Vec::with_capacity(header.token_count as usize);
+    if let Some(Command::Edit {
+        action,
+        output_file,
+    }) = args.command
+    {
+        let output_file = output_file.as_deref().unwrap_or(&index_file);
+        return edit_index(&loader, &index_file, &action, output_file);
+    }
 
-    for _ in 0..header.token_count {
-        packed_tokens.push(read_u16(&mut reader)?);
+    if let Some(property) = args.property {
+        return print_property(&index_file, &property, args.limits, args.encoding);
     }
 
-    let mut args: Vec<pd::Arg> = Vec::with_capacity(header.arg_count as usize);
-    for _ in 0..header.arg_count {
-        args.push(read_u32(&mut reader)?);
+    let (document, version) = loader.load(&index_file, |pdr| {
+        let version = pdr
+            .header
+            .as_ref()
+            .map_or(pd::WRITTEN_VERSION, |header| header.version);
+        Ok((schema::read(pdr, args.schema)?, version))
+    })?;
+    let format = args.output_format;
+    let raw = match document {
+        schema::Document::Product(raw)
+            if matches!(args.command, Some(Command::Query { .. }))
+                || matches!(
+                    format,
+                    Some(output::Format::Table | output::Format::Csv | output::Format::JsonLines)
+                ) =>
+        {
+            raw
+        }
+        document if matches!(args.command, Some(Command::Query { .. })) => {
+            return Err(ReadingError::WrongSchema(document.schema()).in_file(&index_file));
+        }
+        document => return print_document(&document, version, format),
+    };
+
+    if let Some(Command::Query { filter }) = args.command {
+        let patch = loader.describe(&index_file, raw)?;
+        let format = args.output_format.unwrap_or(output::Format::Table);
+        return print_query(&patch, filter.as_deref(), format, args.raw);
     }
 
-    let mut strings: Vec<String> = Vec::with_capacity(header.string_count as usize);
-    for _ in 0..header.string_count {
-        strings.push(read_string(&mut reader)?);
+    let output = &mut io::stdout().lock();
+    match format {
+        // only the rows need the validated model
+        Some(format @ (output::Format::Table | output::Format::Csv)) => {
+            let patch = loader.describe(&index_file, raw)?;
+            output::write_rows(output, &output::file_rows(&patch), format, args.raw)
+        }
+        // one line per file, the other formats printed the whole document above
+        _ => output::write_product(output, &raw, output::Format::JsonLines),
     }
+    .map_err(ReadingError::OutputError)?;
 
-    let tokens: Vec<pd::Tokens> = packed_tokens
-        .iter()
-        .map(|&x| parse_token(x, &strings))
+    Ok(())
+}
+
+fn print_property(
+    filepath: &str,
+    property: &str,
+    limits: pd::Limits,
+    encoding: pd::Encoding,
+) -> Result<()> {
+    let in_file = |error: ReadingError| error.in_file(filepath);
+    let mut file = File::open(filepath)?;
+    let mut start = Vec::new();
+    file.by_ref().take(64).read_to_end(&mut start)?;
+    file.rewind()
+        .map_err(|error| match error.kind() {
+            io::ErrorKind::NotSeekable => ReadingError::NotSeekable,
+            _ => ReadingError::from(error),
+        })
+        .map_err(in_file)?;
+    if pd::FileEncoding::detect(&start) != pd::FileEncoding::Binary {
+        // XML and text files are read whole and streamed as the binary file they stand for
+        let data = fs::read(filepath)?;
+        let mut pdr = pd::read_index_file(&data, false, limits, encoding).map_err(in_file)?;
+        let mut writer = pd::Writer::default();
+        writer.write_extra(&pdr.read_properties().map_err(in_file)?);
+        let binary = io::Cursor::new(writer.into_bytes().map_err(in_file)?);
+        let reader = pd::Reader::new(binary, limits, pd::Encoding::Utf8).map_err(in_file)?;
+        return print_values(reader, property).map_err(in_file);
+    }
+
+    let reader = pd::Reader::new(file, limits, encoding).map_err(in_file)?;
+    print_values(reader, property).map_err(in_file)
+}
+
+fn print_values(mut reader: pd::Reader<impl Read + Seek>, property: &str) -> Result<()> {
+    while let Some(event) = reader.next_event()? {
+        if let pd::Event::Value(name, value) = event {
+            if name == property {
+                println!("{}", value);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_inspection(filepath: &str, format: Option<output::Format>) -> Result<()> {
+    let index = map_index_file(filepath).map_err(|error| error.in_file(filepath))?;
+    let inspection = inspect::inspect(&index);
+
+    let output = &mut io::stdout().lock();
+    match format {
+        None | Some(output::Format::Table) => inspect::write_report(output, &inspection),
+        Some(format @ (output::Format::Csv | output::Format::JsonLines)) => unsupported_format(
+            "the inspection",
+            format,
+            "table, json, json-compact, yaml or toml",
+        ),
+        Some(format) => output::write_document(output, &inspection, format),
+    }
+    .map_err(ReadingError::OutputError)
+}
+
+/// Prints a document as it is stored together with the format version of its file
+fn print_document(
+    document: &schema::Document,
+    format_version: u32,
+    format: Option<output::Format>,
+) -> Result<()> {
+    let format = match format.unwrap_or(output::Format::Json) {
+        format @ (output::Format::Table | output::Format::Csv | output::Format::JsonLines) => {
+            let name = format!("a {} document", document.schema());
+            unsupported_format(&name, format, "json, json-compact, yaml or toml")
+        }
+        format => format,
+    };
+    let dump = schema::Dump {
+        format_version,
+        document,
+    };
+
+    output::write_document(&mut io::stdout().lock(), &dump, format)
+        .map_err(ReadingError::OutputError)
+}
+
+fn unsupported_format(what: &str, format: output::Format, supported: &str) -> ! {
+    Args::command()
+        .error(
+            ErrorKind::InvalidValue,
+            format!(
+                "{} can not be printed as {}, use {}",
+                what,
+                format.to_possible_value().unwrap().get_name(),
+                supported
+            ),
+        )
+        .exit()
+}
+
+fn print_query(
+    patch: &product::ProductDescription,
+    filter: Option<&str>,
+    format: output::Format,
+    raw: bool,
+) -> Result<()> {
+    let filter = filter
+        .map(query::parse)
+        .transpose()
+        .map_err(ReadingError::InvalidQuery)?;
+    let rows: Vec<output::FileRow> = output::file_rows(patch)
+        .into_iter()
+        .filter(|row| filter.as_ref().is_none_or(|filter| filter.matches(row)))
         .collect();
 
-    Ok(pd::PersistentDataRecord {
-        _TokenOffset: 0,
-        _ArgOffset: 0,
-        tokens,
-        args,
-        strings,
-    })
+    output::write_rows(&mut io::stdout().lock(), &rows, format, raw)
+        .map_err(ReadingError::OutputError)?;
+
+    Ok(())
+}
+
+fn ingest(loader: &Loader, directory: &str, database: &str) -> Result<()> {
+    let mut history = history::History::open(database)?;
+
+    let mut paths: Vec<_> = fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<_>>()?;
+    paths.retain(|path| path.extension().is_some_and(|extension| extension == "idx"));
+    paths.sort();
+
+    let mut failed = 0;
+    for path in paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        // one broken index does not keep the later ones out of the history
+        let patch = match loader.load_product(&path) {
+            Ok(patch) => patch,
+            Err(error) => {
+                eprintln!("{}", error.report(loader.error_format));
+                failed += 1;
+                continue;
+            }
+        };
+
+        if history.ingest(&name, &patch)? {
+            println!("ingested {}", name);
+        } else {
+            println!("skipped {}, already ingested", name);
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        failed => Err(ReadingError::FailedFiles(failed)),
+    }
+}
+
+fn print_history(database: &str, file: &str, format: output::Format, raw: bool) -> Result<()> {
+    let history = history::History::open(database)?;
+    let rows = history.file_history(file)?;
+
+    output::write_rows(&mut io::stdout().lock(), &rows, format, raw)
+        .map_err(ReadingError::OutputError)?;
+
+    Ok(())
 }
 
-fn parse_token(stored_token: pd::Token, strings: &Vec<String>) -> pd::Tokens {
-    let token_type = stored_token & 0x7;
-    let token_value = stored_token >> 3;
-    let token_name = strings[token_value as usize].clone();
+fn print_changelog(
+    loader: &Loader,
+    old: &str,
+    new: &str,
+    format: changelog::Format,
+    since: Option<patch::FileTime>,
+    until: Option<patch::FileTime>,
+) -> Result<()> {
+    let title = format!("Release notes {} → {}", file_name(old), file_name(new));
+    let old = loader.load_description(old)?;
+    let new = loader.load_description(new)?;
+    let mut notes = changelog::Changelog::new(&old, &new);
+    notes.retain_time(since, until);
 
-    match token_type {
-        0 => pd::Tokens::BEGIN_TOKEN(token_name),
-        1 => pd::Tokens::END_TOKEN(token_name),
-        2 => pd::Tokens::SINT_TOKEN(token_name),
-        3 => pd::Tokens::UINT_TOKEN(token_name),
-        4 => pd::Tokens::FLOAT_TOKEN(token_name),
-        5 => pd::Tokens::STRING_TOKEN(token_name),
-        6 => pd::Tokens::FLAG_TOKEN(token_name),
-        7 => pd::Tokens::EXTEND_TOKEN(token_name),
-        _ => panic!("Unknown token type"),
+    let output = &mut io::stdout().lock();
+    match format {
+        changelog::Format::Markdown => changelog::write_markdown(output, &title, &notes),
+        changelog::Format::Html => changelog::write_html(output, &title, &notes),
     }
+    .map_err(ReadingError::OutputError)?;
+
+    Ok(())
 }
 
-fn read_header<Stream>(size: u64, mut file: &mut Stream) -> Result<pd::Header>
-where
-    Stream: Read + Seek,
-{
-    let version: u32 = read_u32(&mut file)?;
-    if version > 0 {
-        return Err(ReadingError::UnsupportedVersion(version));
+fn edit_index(
+    loader: &Loader,
+    index_file: &str,
+    action: &edit::Action,
+    output_file: &str,
+) -> Result<()> {
+    let (mut raw, originals, skipped) = loader.load(index_file, |pdr| {
+        let raw = patch::CProductDescriptionForClient::from(pdr)?;
+        Ok((raw, std::mem::take(&mut pdr.originals), pdr.warnings.len()))
+    })?;
+    // what lenient reading skipped would be gone from the written index
+    if skipped > 0 {
+        return Err(ReadingError::IncompleteInput(skipped).in_file(index_file));
     }
+    edit::apply(&mut raw, action)
+        .map_err(|error| ReadingError::InvalidEdit(error).in_file(index_file))?;
+
+    let writer = pd::Writer::with_encoding(loader.encoding, originals);
+    let data = schema::write(&schema::Document::Product(raw), writer)?;
+    write_index_file(output_file, &data)
+}
 
-    let total_size: u32 = read_u32(&mut file)?;
-    if total_size > size as u32 {
-        return Err(ReadingError::ContentTooSmall(total_size, size));
+fn merge_indexes(
+    loader: &Loader,
+    inputs: &[String],
+    output_file: &str,
+    rule: merge::Rule,
+) -> Result<()> {
+    let mut products = Vec::with_capacity(inputs.len());
+    let mut originals = pd::OriginalStrings::default();
+    for input in inputs {
+        let (product, skipped) = loader.load(input, |pdr| {
+            originals.extend(std::mem::take(&mut pdr.originals));
+            let product = patch::CProductDescriptionForClient::from(pdr)?;
+            Ok((product, pdr.warnings.len()))
+        })?;
+        // the merged index would silently lack what lenient reading skipped
+        if skipped > 0 {
+            return Err(ReadingError::IncompleteInput(skipped).in_file(input));
+        }
+        products.push((input.clone(), product));
     }
-    let token_count = read_u32(&mut file)?;
-    let arg_count = read_u32(&mut file)?;
-    let string_count = read_u32(&mut file)?;
-    let strings_size = read_u32(&mut file)?;
+    let merged = merge::merge(products, rule).map_err(ReadingError::InvalidMerge)?;
 
-    let offset = file.stream_position()?;
-    let expected_size = offset as usize
-        + token_count as usize * size_of::<pd::Token>()
-        + arg_count as usize * size_of::<pd::Arg>()
-        + strings_size as usize;
-    if total_size as usize != expected_size {
-        return Err(ReadingError::ContentWrongSize(total_size, expected_size));
+    let writer = pd::Writer::with_encoding(loader.encoding, originals);
+    let data = schema::write(&schema::Document::Product(merged.product), writer)?;
+    write_index_file(output_file, &data)?;
+    for conflict in merged.conflicts {
+        println!("{}", conflict);
     }
 
-    Ok(pd::Header {
-        version,
-        total_size,
-        token_count,
-        arg_count,
-        string_count,
-        strings_size,
-    })
+    Ok(())
 }
 
-fn read_u16(input_stream: &mut impl Read) -> io::Result<u16> {
-    let mut buffer = [0; 2];
-    input_stream.read_exact(&mut buffer[..])?;
+fn from_json(
+    input: &str,
+    output: &str,
+    file_encoding: pd::FileEncoding,
+    schema: schema::Schema,
+    encoding: pd::Encoding,
+) -> Result<()> {
+    let json = if input == "-" {
+        io::read_to_string(io::stdin())?
+    } else {
+        fs::read_to_string(input).map_err(|error| ReadingError::from(error).in_file(input))?
+    };
+    let document = schema::from_json(&json, schema).map_err(|error| error.in_file(input))?;
+
+    let writer = pd::Writer::with_encoding(encoding, pd::OriginalStrings::default());
+    write_index_file(output, &schema::write_as(&document, writer, file_encoding)?)
+}
 
-    Ok(u16::from_le_bytes(buffer))
+fn parse_time(value: &str) -> std::result::Result<patch::FileTime, String> {
+    patch::FileTime::parse(value)
+        .ok_or_else(|| "expected a date like 2024-01-31 or an RFC 3339 time".to_string())
 }
 
-fn read_u32(input_stream: &mut impl Read) -> io::Result<u32> {
-    let mut buffer = [0; 4];
-    input_stream.read_exact(&mut buffer[..])?;
+fn parse_end_time(value: &str) -> std::result::Result<patch::FileTime, String> {
+    patch::FileTime::parse_end(value)
+        .ok_or_else(|| "expected a date like 2024-01-31 or an RFC 3339 time".to_string())
+}
 
-    Ok(u32::from_le_bytes(buffer))
+fn file_name(path: &str) -> std::borrow::Cow<'_, str> {
+    Path::new(path)
+        .file_name()
+        .map_or(path.into(), |name| name.to_string_lossy())
 }
 
-fn read_string(input_stream: &mut impl BufRead) -> io::Result<String> {
-    let mut bytes = Vec::new();
-    input_stream.read_until(0, &mut bytes)?;
+/// Writes next to the file and renames it into place, so a failed write leaves the old file
+fn write_index_file(path: &str, data: &[u8]) -> Result<()> {
+    let in_file = |error| ReadingError::OutputError(error).in_file(path);
+    let target = Path::new(path);
+    let temporary =
+        target.with_file_name(format!(".{}.{}.tmp", file_name(path), std::process::id()));
 
-    // remove the trailing null byte if present
-    if let Some(&0) = bytes.last() {
-        bytes.pop();
+    let written = fs::write(&temporary, data).and_then(|()| fs::rename(&temporary, target));
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary);
     }
 
-    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    written.map_err(in_file)
+}
+
+fn map_index_file(filepath: impl AsRef<Path>) -> Result<Mmap> {
+    let file = File::open(filepath)?;
+
+    // SAFETY: the index files are only read, modifying them while mapped is not supported
+    Ok(unsafe { Mmap::map(&file)? })
 }
@@ -0,0 +1,325 @@
+use crate::patch;
+use clap::ValueEnum;
+use std::error::Error;
+use std::fmt;
+
+/// How to resolve a file or category that differs between two inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Rule {
+    /// Keep the entry of the later input
+    Overlay,
+    /// Stop at the first conflict
+    Fail,
+    /// Keep the file whose latest version is the highest, the later input on a tie
+    Highest,
+}
+
+/// A file or category that was taken from one input over another
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    pub name: String,
+    /// Name of the input whose entry was kept
+    pub kept: String,
+    /// Name of the input whose entry was replaced
+    pub dropped: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictKind {
+    /// The versions of a file differ, with the latest version of each side
+    File {
+        kept: Option<u32>,
+        dropped: Option<u32>,
+    },
+    /// The flags or paths of a category differ, the set ones of the later input win
+    Category,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ConflictKind::File { kept, dropped } => write!(
+                f,
+                "file {}: kept {} from {} over {} from {}",
+                self.name,
+                Version(kept),
+                self.kept,
+                Version(dropped),
+                self.dropped
+            ),
+            ConflictKind::Category => write!(
+                f,
+                "category {}: settings of {} replace those of {}",
+                self.name, self.kept, self.dropped
+            ),
+        }
+    }
+}
+
+struct Version(Option<u32>);
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(number) => write!(f, "version {}", number),
+            None => f.write_str("no version"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MergeError(pub Conflict);
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let conflict = &self.0;
+        let kind = match conflict.kind {
+            ConflictKind::File { .. } => "file",
+            ConflictKind::Category => "category",
+        };
+        write!(
+            f,
+            "{} {} differs between {} and {}",
+            kind, conflict.name, conflict.dropped, conflict.kept
+        )
+    }
+}
+
+impl Error for MergeError {}
+
+/// The combined product description and the conflicts resolved on the way
+#[derive(Debug)]
+pub struct Merge {
+    pub product: patch::CProductDescriptionForClient,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Combines named product descriptions, each one laid over the ones before it
+///
+/// Files and categories keep the position of their first appearance. The file lists of
+/// categories with the same name are joined.
+pub fn merge(
+    inputs: Vec<(String, patch::CProductDescriptionForClient)>,
+    rule: Rule,
+) -> Result<Merge, MergeError> {
+    let mut product = patch::CProductDescriptionForClient {
+        _Files: patch::CBNPFileSet::default(),
+        _Categories: patch::CBNPCategorySet::default(),
+    };
+    // input each file and category was last taken from
+    let mut file_sources: Vec<usize> = Vec::new();
+    let mut category_sources: Vec<usize> = Vec::new();
+    let mut conflicts = Vec::new();
+
+    for (input, (_, overlay)) in inputs.iter().enumerate() {
+        for file in &overlay._Files._Files {
+            let Some(index) = product
+                ._Files
+                ._Files
+                .iter()
+                .position(|known| known._FileName == file._FileName)
+            else {
+                product._Files._Files.push(file.clone());
+                file_sources.push(input);
+                continue;
+            };
+            let known = &mut product._Files._Files[index];
+            if known == file {
+                continue;
+            }
+
+            let overlay_wins = match rule {
+                Rule::Highest => latest(file) >= latest(known),
+                Rule::Overlay | Rule::Fail => true,
+            };
+            let (kept, dropped) = if overlay_wins {
+                (input, file_sources[index])
+            } else {
+                (file_sources[index], input)
+            };
+            let conflict = Conflict {
+                kind: ConflictKind::File {
+                    kept: latest(if overlay_wins { file } else { known }),
+                    dropped: latest(if overlay_wins { known } else { file }),
+                },
+                name: file._FileName.clone(),
+                kept: inputs[kept].0.clone(),
+                dropped: inputs[dropped].0.clone(),
+            };
+            if rule == Rule::Fail {
+                return Err(MergeError(conflict));
+            }
+            if overlay_wins {
+                *known = file.clone();
+                file_sources[index] = input;
+            }
+            conflicts.push(conflict);
+        }
+
+        for category in &overlay._Categories._Category {
+            let categories = &mut product._Categories._Category;
+            let Some(index) = categories
+                .iter()
+                .position(|known| known._Name == category._Name)
+            else {
+                categories.push(category.clone());
+                category_sources.push(input);
+                continue;
+            };
+            let known = &mut categories[index];
+            if settings_differ(known, category) {
+                let conflict = Conflict {
+                    kind: ConflictKind::Category,
+                    name: category._Name.clone(),
+                    kept: inputs[input].0.clone(),
+                    dropped: inputs[category_sources[index]].0.clone(),
+                };
+                if rule == Rule::Fail {
+                    return Err(MergeError(conflict));
+                }
+                conflicts.push(conflict);
+            }
+            merge_category(known, category);
+            category_sources[index] = input;
+        }
+    }
+
+    Ok(Merge { product, conflicts })
+}
+
+fn latest(file: &patch::CBNPFile) -> Option<u32> {
+    file._Versions
+        .iter()
+        .map(|version| version._VersionNumber)
+        .max()
+}
+
+/// Whether both categories set a flag or path to different values
+fn settings_differ(known: &patch::CBNPCategory, overlay: &patch::CBNPCategory) -> bool {
+    fn differ<T: PartialEq>(known: &Option<T>, overlay: &Option<T>) -> bool {
+        matches!((known, overlay), (Some(known), Some(overlay)) if known != overlay)
+    }
+
+    differ(&known._IsOptional, &overlay._IsOptional)
+        || differ(&known._UnpackTo, &overlay._UnpackTo)
+        || differ(&known._IsIncremental, &overlay._IsIncremental)
+        || differ(&known._CatRequired, &overlay._CatRequired)
+        || differ(&known._Hidden, &overlay._Hidden)
+}
+
+fn merge_category(known: &mut patch::CBNPCategory, overlay: &patch::CBNPCategory) {
+    fn set<T: Clone>(known: &mut Option<T>, overlay: &Option<T>) {
+        if overlay.is_some() {
+            known.clone_from(overlay);
+        }
+    }
+
+    set(&mut known._IsOptional, &overlay._IsOptional);
+    set(&mut known._UnpackTo, &overlay._UnpackTo);
+    set(&mut known._IsIncremental, &overlay._IsIncremental);
+    set(&mut known._CatRequired, &overlay._CatRequired);
+    set(&mut known._Hidden, &overlay._Hidden);
+    for file in &overlay._Files {
+        if !known._Files.contains(file) {
+            known._Files.push(file.clone());
+        }
+    }
+    for property in &overlay.extra {
+        match known
+            .extra
+            .iter_mut()
+            .find(|known| known.name == property.name)
+        {
+            Some(known) => known.clone_from(property),
+            None => known.extra.push(property.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::fixture;
+
+    fn file(name: &str, versions: &[u32]) -> patch::CBNPFile {
+        let versions = versions
+            .iter()
+            .map(|&number| fixture::version(number, 1024, 0))
+            .collect();
+
+        fixture::file(name, versions)
+    }
+
+    fn product(
+        files: Vec<patch::CBNPFile>,
+        category: &str,
+        hidden: Option<bool>,
+    ) -> patch::CProductDescriptionForClient {
+        let names: Vec<&str> = files.iter().map(|file| file._FileName.as_str()).collect();
+        let category = patch::CBNPCategory {
+            _Hidden: hidden,
+            ..fixture::category(category, &names)
+        };
+
+        fixture::product(files, vec![category])
+    }
+
+    fn inputs() -> Vec<(String, patch::CProductDescriptionForClient)> {
+        vec![
+            (
+                "official.idx".to_string(),
+                product(
+                    vec![file("sounds.bnp", &[1, 2]), file("fauna_maps.bnp", &[4])],
+                    "main",
+                    Some(false),
+                ),
+            ),
+            (
+                "shard.idx".to_string(),
+                product(
+                    vec![file("sounds.bnp", &[1]), file("shard.bnp", &[1])],
+                    "main",
+                    Some(true),
+                ),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_merge_rules() {
+        let overlay = merge(inputs(), Rule::Overlay).unwrap();
+        let files = &overlay.product._Files._Files;
+        assert_eq!(
+            vec!["sounds.bnp", "fauna_maps.bnp", "shard.bnp"],
+            files.iter().map(|file| &file._FileName).collect::<Vec<_>>()
+        );
+        assert_eq!(1, files[0]._Versions.len());
+        let [main] = &overlay.product._Categories._Category[..] else {
+            panic!("expected one category");
+        };
+        assert_eq!(3, main._Files.len());
+        assert_eq!(Some(true), main._Hidden);
+        assert_eq!(
+            vec![
+                "file sounds.bnp: kept version 1 from shard.idx over version 2 from official.idx",
+                "category main: settings of shard.idx replace those of official.idx",
+            ],
+            overlay
+                .conflicts
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        );
+
+        let highest = merge(inputs(), Rule::Highest).unwrap();
+        assert_eq!(2, highest.product._Files._Files[0]._Versions.len());
+        assert_eq!("official.idx", highest.conflicts[0].kept);
+
+        let error = merge(inputs(), Rule::Fail).unwrap_err();
+        assert_eq!(
+            "file sounds.bnp differs between official.idx and shard.idx",
+            error.to_string()
+        );
+    }
+}
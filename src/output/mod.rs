@@ -0,0 +1,317 @@
+use crate::{patch, product};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Aligned columns for reading in a terminal, one row per file and its latest version
+    Table,
+    /// Comma separated values, one row per file and its latest version
+    Csv,
+    /// Pretty printed JSON
+    Json,
+    /// JSON on a single line
+    JsonCompact,
+    /// One JSON document per file and line
+    JsonLines,
+    Yaml,
+    Toml,
+}
+
+/// Flat view of a file with its latest version and the categories it belongs to
+#[derive(Debug, Serialize)]
+pub struct FileRow {
+    pub name: String,
+    pub version: Option<u32>,
+    pub size: Option<patch::FileSize>,
+    pub packed_size: Option<patch::FileSize>,
+    pub patch_size: Option<patch::FileSize>,
+    pub time: Option<patch::FileTime>,
+    pub categories: Vec<String>,
+    pub unpack_to: Option<String>,
+    pub optional: bool,
+    pub hidden: bool,
+    pub incremental: bool,
+}
+
+impl FileRow {
+    pub fn new<'a>(
+        file: &product::FileEntry,
+        categories: impl IntoIterator<Item = &'a product::Category>,
+    ) -> FileRow {
+        let categories: Vec<&product::Category> = categories.into_iter().collect();
+        let latest = file.latest();
+
+        FileRow {
+            name: file.name.clone(),
+            version: latest.map(|version| version.number),
+            size: latest.map(|version| version.size),
+            packed_size: latest.map(|version| version.packed_size),
+            patch_size: latest.map(|version| version.patch_size),
+            time: latest.map(|version| version.time),
+            categories: categories
+                .iter()
+                .map(|category| category.name.clone())
+                .collect(),
+            unpack_to: categories
+                .iter()
+                .find_map(|category| category.unpack_to.clone()),
+            optional: categories.iter().any(|category| category.optional),
+            hidden: categories.iter().any(|category| category.hidden),
+            incremental: categories.iter().any(|category| category.incremental),
+        }
+    }
+}
+
+pub fn file_rows(product: &product::ProductDescription) -> Vec<FileRow> {
+    product
+        .entries()
+        .map(|(id, file)| FileRow::new(file, product.file_categories(id)))
+        .collect()
+}
+
+/// Writes the product description as stored, absent properties stay null so the output turns back
+/// into the same file. Tables and CSV list the rows of the validated model with [`write_rows`].
+pub fn write_product(
+    output: &mut impl Write,
+    product: &patch::CProductDescriptionForClient,
+    format: Format,
+) -> io::Result<()> {
+    match format {
+        Format::JsonLines => write_json_lines(output, &product._Files._Files),
+        Format::Table | Format::Csv => {
+            unreachable!("{:?} lists the rows of the validated model", format)
+        }
+        _ => write_document(output, product, format),
+    }
+}
+
+/// A flat record that can be printed as table or CSV in addition to the serde formats
+pub trait Row: Serialize {
+    /// Name of the top level table for formats that do not allow a list as document
+    const TABLE: &'static str;
+    /// Column names and whether the column is right aligned in tables
+    const COLUMNS: &'static [(&'static str, bool)];
+
+    /// The cells of the table and CSV columns, `raw` prints sizes and times as plain numbers
+    fn cells(&self, raw: bool) -> Vec<String>;
+}
+
+impl Row for FileRow {
+    const TABLE: &'static str = "files";
+    const COLUMNS: &'static [(&'static str, bool)] = &[
+        ("name", false),
+        ("version", true),
+        ("size", true),
+        ("packed_size", true),
+        ("patch_size", true),
+        ("time", true),
+        ("categories", false),
+    ];
+
+    fn cells(&self, raw: bool) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            cell(self.version),
+            size_cell(self.size, raw),
+            size_cell(self.packed_size, raw),
+            size_cell(self.patch_size, raw),
+            time_cell(self.time, raw),
+            self.categories.join(";"),
+        ]
+    }
+}
+
+pub fn cell(value: Option<impl fmt::Display>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+pub fn size_cell(value: Option<patch::FileSize>, raw: bool) -> String {
+    match raw {
+        true => cell(value.map(|size| size.0)),
+        false => cell(value),
+    }
+}
+
+pub fn time_cell(value: Option<patch::FileTime>, raw: bool) -> String {
+    match raw {
+        true => cell(value.map(|time| time.0)),
+        false => cell(value),
+    }
+}
+
+pub fn write_rows<R: Row>(
+    output: &mut impl Write,
+    rows: &[R],
+    format: Format,
+    raw: bool,
+) -> io::Result<()> {
+    match format {
+        Format::Table => write_table(output, rows, raw),
+        Format::Csv => write_csv(output, rows),
+        Format::JsonLines => write_json_lines(output, rows),
+        // TOML documents have to be a table at the top level
+        Format::Toml => write_document(output, &BTreeMap::from([(R::TABLE, rows)]), format),
+        _ => write_document(output, rows, format),
+    }
+}
+
+/// Writes a single value, only for the json, json-compact, yaml and toml formats
+pub fn write_document(
+    output: &mut impl Write,
+    value: &(impl Serialize + ?Sized),
+    format: Format,
+) -> io::Result<()> {
+    match format {
+        Format::Json => serde_json::to_writer_pretty(&mut *output, value)?,
+        Format::JsonCompact => serde_json::to_writer(&mut *output, value)?,
+        Format::Yaml => serde_yaml_ng::to_writer(&mut *output, value).map_err(io::Error::other)?,
+        Format::Toml => {
+            let document = toml::to_string_pretty(value).map_err(io::Error::other)?;
+            return output.write_all(document.as_bytes());
+        }
+        Format::Table | Format::Csv | Format::JsonLines => {
+            unreachable!("{:?} is not a document format", format)
+        }
+    }
+    if matches!(format, Format::Json | Format::JsonCompact) {
+        writeln!(output)?;
+    }
+
+    Ok(())
+}
+
+fn write_json_lines<T: Serialize>(output: &mut impl Write, items: &[T]) -> io::Result<()> {
+    for item in items {
+        serde_json::to_writer(&mut *output, item)?;
+        writeln!(output)?;
+    }
+
+    Ok(())
+}
+
+fn write_csv<R: Row>(output: &mut impl Write, rows: &[R]) -> io::Result<()> {
+    let mut writer = csv::Writer::from_writer(output);
+    writer.write_record(R::COLUMNS.iter().map(|(name, _)| name))?;
+    for row in rows {
+        writer.write_record(row.cells(true))?;
+    }
+    writer.flush()
+}
+
+fn write_table<R: Row>(output: &mut impl Write, rows: &[R], raw: bool) -> io::Result<()> {
+    let header: Vec<String> = R::COLUMNS
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect();
+    let cells: Vec<Vec<String>> = rows.iter().map(|row| row.cells(raw)).collect();
+    let mut widths: Vec<usize> = header.iter().map(String::len).collect();
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    for row in std::iter::once(&header).chain(&cells) {
+        let mut line = String::new();
+        for (index, (cell, width)) in row.iter().zip(&widths).enumerate() {
+            if index > 0 {
+                line.push_str("  ");
+            }
+            if R::COLUMNS[index].1 {
+                line.push_str(&format!("{:>width$}", cell));
+            } else {
+                line.push_str(&format!("{:<width$}", cell));
+            }
+        }
+        writeln!(output, "{}", line.trim_end())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows() -> Vec<FileRow> {
+        let file = product::FileEntry {
+            name: "fauna_maps.bnp".to_string(),
+            versions: vec![product::FileVersion {
+                number: 1021,
+                size: patch::FileSize(2048),
+                packed_size: patch::FileSize(1024),
+                time: patch::FileTime(1700000000),
+                patch_size: patch::FileSize(512),
+                hash_key: vec![1, 2, 3, 4, 5],
+                extra: Vec::new(),
+            }],
+            extra: Vec::new(),
+        };
+
+        vec![FileRow::new(&file, [])]
+    }
+
+    #[test]
+    fn test_write_table() {
+        let mut output = Vec::new();
+        write_rows(&mut output, &rows(), Format::Table, false).unwrap();
+
+        assert_eq!(
+            "name            version     size  packed_size  patch_size                  time  categories\n\
+             fauna_maps.bnp     1021  2.0 KiB      1.0 KiB       512 B  2023-11-14T22:13:20Z\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        let mut output = Vec::new();
+        write_rows(&mut output, &rows(), Format::Table, true).unwrap();
+
+        assert_eq!(
+            "name            version  size  packed_size  patch_size        time  categories\n\
+             fauna_maps.bnp     1021  2048         1024         512  1700000000\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_json_lines() {
+        let mut output = Vec::new();
+        write_rows(&mut output, &rows(), Format::JsonLines, false).unwrap();
+
+        assert_eq!(
+            "{\"name\":\"fauna_maps.bnp\",\"version\":1021,\"size\":2048,\"packed_size\":1024,\
+             \"patch_size\":512,\"time\":1700000000,\"categories\":[],\"unpack_to\":null,\
+             \"optional\":false,\"hidden\":false,\"incremental\":false}\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_product_keeps_absent_properties() {
+        let product = patch::CProductDescriptionForClient {
+            _Files: patch::CBNPFileSet { _Files: vec![] },
+            _Categories: patch::CBNPCategorySet {
+                _Category: vec![patch::CBNPCategory {
+                    _Name: "main".to_string(),
+                    _IsOptional: None,
+                    _UnpackTo: None,
+                    _IsIncremental: Some(false),
+                    _CatRequired: None,
+                    _Hidden: None,
+                    // an unknown member is printed as stored, not dropped by validation
+                    _Files: vec!["missing.bnp".to_string()],
+                    extra: Vec::new(),
+                }],
+            },
+        };
+        let mut output = Vec::new();
+        write_product(&mut output, &product, Format::JsonCompact).unwrap();
+
+        let json = String::from_utf8(output).unwrap();
+        assert!(json.contains(r#""_IsOptional":null,"_UnpackTo":null,"_IsIncremental":false"#));
+        assert_eq!(product, serde_json::from_str(&json).unwrap());
+    }
+}
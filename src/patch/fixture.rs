@@ -0,0 +1,51 @@
+//! Patch index values for the tests of the modules built on the patch structs, fields a test
+//! does not care about get fixed values and can be changed with struct update syntax
+
+use super::*;
+
+/// A version whose packed size and hash follow from its number and size
+pub fn version(number: u32, size: u32, patch_size: u32) -> CBNPFileVersion {
+    CBNPFileVersion {
+        _VersionNumber: number,
+        _FileSize: FileSize(size),
+        _7ZFileSize: FileSize(size / 2),
+        _FileTime: FileTime(1700000000),
+        _PatchSize: FileSize(patch_size),
+        _HashKey: vec![number; 5],
+        extra: Vec::new(),
+    }
+}
+
+pub fn file(name: &str, versions: Vec<CBNPFileVersion>) -> CBNPFile {
+    CBNPFile {
+        _FileName: name.to_string(),
+        _Versions: versions,
+        extra: Vec::new(),
+    }
+}
+
+/// A category without flags or paths
+pub fn category(name: &str, files: &[&str]) -> CBNPCategory {
+    CBNPCategory {
+        _Name: name.to_string(),
+        _IsOptional: None,
+        _UnpackTo: None,
+        _IsIncremental: None,
+        _CatRequired: None,
+        _Hidden: None,
+        _Files: files.iter().map(|file| file.to_string()).collect(),
+        extra: Vec::new(),
+    }
+}
+
+pub fn product(
+    files: Vec<CBNPFile>,
+    categories: Vec<CBNPCategory>,
+) -> CProductDescriptionForClient {
+    CProductDescriptionForClient {
+        _Files: CBNPFileSet { _Files: files },
+        _Categories: CBNPCategorySet {
+            _Category: categories,
+        },
+    }
+}
@@ -1,48 +1,42 @@
+#[cfg(test)]
+pub mod fixture;
+mod package;
+mod units;
+
 use crate::pd;
-use serde::Serialize;
+use crate::Result;
+use pdr_derive::{PdrRead, PdrWrite};
+use serde::{Deserialize, Serialize};
+
+pub use package::*;
+pub use units::*;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, PdrWrite)]
 pub struct CProductDescriptionForClient {
-    _Files: CBNPFileSet,          // read_struct
-    _Categories: CBNPCategorySet, // read_struct
+    pub _Files: CBNPFileSet,          // read_struct
+    pub _Categories: CBNPCategorySet, // read_struct
 }
 
 impl CProductDescriptionForClient {
-    pub fn from(pdr: &mut pd::PersistentDataRecord) -> CProductDescriptionForClient {
-        CProductDescriptionForClient {
-            _Files: pdr.read("_Files"),
-            _Categories: pdr.read("_Categories"),
-        }
+    pub fn from(pdr: &mut pd::PersistentDataRecord) -> Result<CProductDescriptionForClient> {
+        Ok(CProductDescriptionForClient {
+            _Files: pdr.read_or_default("_Files")?,
+            _Categories: pdr.read_or_default("_Categories")?,
+        })
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, PdrRead, PdrWrite)]
 pub struct CBNPFileSet {
     pub _Files: Vec<CBNPFile>, // read_struct_vec
 }
 
-impl pd::Readable for CBNPFileSet {
-    fn read(pdr: &mut pd::PersistentDataRecord) -> CBNPFileSet {
-        CBNPFileSet {
-            _Files: pdr.read("_Files"),
-        }
-    }
-}
-
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, PdrRead, PdrWrite)]
 pub struct CBNPCategorySet {
     pub _Category: Vec<CBNPCategory>,
 }
 
-impl pd::Readable for CBNPCategorySet {
-    fn read(pdr: &mut pd::PersistentDataRecord) -> CBNPCategorySet {
-        CBNPCategorySet {
-            _Category: pdr.read("_Category"),
-        }
-    }
-}
-
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, PdrRead, PdrWrite)]
 pub struct CBNPCategory {
     pub _Name: String,
     pub _IsOptional: Option<bool>,
@@ -50,57 +44,153 @@ pub struct CBNPCategory {
     pub _IsIncremental: Option<bool>,
     pub _CatRequired: Option<String>,
     pub _Hidden: Option<bool>,
+    #[pdr(repeated)]
     pub _Files: Vec<String>,
+    /// Properties added by newer servers, in file order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[pdr(extra)]
+    pub extra: Vec<pd::Property>,
 }
 
-impl pd::Readable for CBNPCategory {
-    fn read(pdr: &mut pd::PersistentDataRecord) -> CBNPCategory {
-        CBNPCategory {
-            _Name: pdr.read("_Name"),
-            _IsOptional: pdr.read("_IsOptional"),
-            _UnpackTo: pdr.read("_UnpackTo"),
-            _IsIncremental: pdr.read("_IsIncremental"),
-            _CatRequired: pdr.read("_CatRequired"),
-            _Hidden: pdr.read("_Hidden"),
-            _Files: pdr.read_prop_vec("_Files"),
-        }
-    }
-}
-
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, PdrRead, PdrWrite)]
 pub struct CBNPFile {
     pub _FileName: String,               // read_prop
     pub _Versions: Vec<CBNPFileVersion>, // read_struct_vec
+    /// Properties added by newer servers, in file order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[pdr(extra)]
+    pub extra: Vec<pd::Property>,
 }
 
-impl pd::Readable for CBNPFile {
-    fn read(pdr: &mut pd::PersistentDataRecord) -> CBNPFile {
-        CBNPFile {
-            _FileName: pdr.read("_FileName"),
-            _Versions: pdr.read("_Versions"),
-        }
-    }
-}
-
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, PdrRead, PdrWrite)]
 pub struct CBNPFileVersion {
     pub _VersionNumber: u32,
-    pub _FileSize: u32,
-    pub _7ZFileSize: u32,
-    pub _FileTime: u32,
-    pub _PatchSize: u32,
+    pub _FileSize: FileSize,
+    pub _7ZFileSize: FileSize,
+    pub _FileTime: FileTime,
+    pub _PatchSize: FileSize,
+    #[pdr(repeated)]
     pub _HashKey: Vec<u32>, // read_prop_vec
+    /// Properties added by newer servers, in file order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[pdr(extra)]
+    pub extra: Vec<pd::Property>,
 }
 
-impl pd::Readable for CBNPFileVersion {
-    fn read(pdr: &mut pd::PersistentDataRecord) -> CBNPFileVersion {
-        CBNPFileVersion {
-            _VersionNumber: pdr.read("_VersionNumber"),
-            _FileSize: pdr.read("_FileSize"),
-            _7ZFileSize: pdr.read("_7ZFileSize"),
-            _FileTime: pdr.read("_FileTime"),
-            _PatchSize: pdr.read("_PatchSize"),
-            _HashKey: pdr.read_prop_vec("_HashKey"), // read_prop_vec
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pd::strategy::{extra, text};
+    use crate::schema;
+    use pd::Readable;
+    use proptest::prelude::*;
+
+    fn version() -> impl Strategy<Value = CBNPFileVersion> {
+        (
+            any::<[u32; 5]>(),
+            prop::collection::vec(any::<u32>(), 0..6),
+            extra(CBNPFileVersion::FIELDS),
+        )
+            .prop_map(|([number, size, packed, time, patch], hash, extra)| {
+                CBNPFileVersion {
+                    _VersionNumber: number,
+                    _FileSize: FileSize(size),
+                    _7ZFileSize: FileSize(packed),
+                    _FileTime: FileTime(time),
+                    _PatchSize: FileSize(patch),
+                    _HashKey: hash,
+                    extra,
+                }
+            })
+    }
+
+    fn file() -> impl Strategy<Value = CBNPFile> {
+        (
+            text(),
+            prop::collection::vec(version(), 0..3),
+            extra(CBNPFile::FIELDS),
+        )
+            .prop_map(|(name, versions, extra)| CBNPFile {
+                _FileName: name,
+                _Versions: versions,
+                extra,
+            })
+    }
+
+    fn category() -> impl Strategy<Value = CBNPCategory> {
+        (
+            text(),
+            any::<[Option<bool>; 3]>(),
+            prop::option::of(text()),
+            prop::option::of(text()),
+            prop::collection::vec(text(), 0..4),
+            extra(CBNPCategory::FIELDS),
+        )
+            .prop_map(
+                |(name, [optional, incremental, hidden], unpack_to, required, files, extra)| {
+                    CBNPCategory {
+                        _Name: name,
+                        _IsOptional: optional,
+                        _UnpackTo: unpack_to,
+                        _IsIncremental: incremental,
+                        _CatRequired: required,
+                        _Hidden: hidden,
+                        _Files: files,
+                        extra,
+                    }
+                },
+            )
+    }
+
+    fn product() -> impl Strategy<Value = CProductDescriptionForClient> {
+        (
+            prop::collection::vec(file(), 0..4),
+            prop::collection::vec(category(), 0..4),
+        )
+            .prop_map(|(files, categories)| CProductDescriptionForClient {
+                _Files: CBNPFileSet { _Files: files },
+                _Categories: CBNPCategorySet {
+                    _Category: categories,
+                },
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn test_binary_round_trip(product in product()) {
+            let document = schema::Document::Product(product.clone());
+            let data = schema::write(&document, pd::Writer::default()).unwrap();
+            let limits = pd::Limits::default();
+            let mut pdr = pd::read_index_file(&data, false, limits, pd::Encoding::Utf8).unwrap();
+
+            prop_assert_eq!(product, CProductDescriptionForClient::from(&mut pdr).unwrap());
+        }
+
+        #[test]
+        fn test_markup_round_trip(product in product()) {
+            let document = schema::Document::Product(product.clone());
+            for file_encoding in [pd::FileEncoding::Xml, pd::FileEncoding::Text] {
+                let data = schema::write_as(&document, pd::Writer::default(), file_encoding)
+                    .unwrap();
+                prop_assert_eq!(file_encoding, pd::FileEncoding::detect(&data));
+                let limits = pd::Limits::default();
+                let mut pdr =
+                    pd::read_index_file(&data, false, limits, pd::Encoding::Utf8).unwrap();
+
+                prop_assert_eq!(&product, &CProductDescriptionForClient::from(&mut pdr).unwrap());
+            }
+        }
+
+        #[test]
+        fn test_json_round_trip(product in product()) {
+            // as `from-json` reads it, detecting the schema
+            let json = serde_json::to_string(&product).unwrap();
+            let document = schema::from_json(&json, schema::Schema::Auto).unwrap();
+            let schema::Document::Product(read) = document else {
+                return Err(TestCaseError::fail("not read as a product description"));
+            };
+
+            prop_assert_eq!(product, read);
         }
     }
 }
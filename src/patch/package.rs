@@ -0,0 +1,20 @@
+use super::CBNPCategorySet;
+use pdr_derive::{PdrRead, PdrWrite};
+use serde::{Deserialize, Serialize};
+
+/// Package description of NeL's patch_gen tool: the categories to build and where the index,
+/// patches, bnp files and reference copies are written
+#[derive(Debug, Serialize, Deserialize, PdrRead, PdrWrite)]
+pub struct CPackageDescription {
+    pub _Categories: CBNPCategorySet,
+    #[pdr(default)]
+    pub _IndexFileName: String,
+    #[pdr(default)]
+    pub _PatchDirectory: String,
+    #[pdr(default)]
+    pub _BnpDirectory: String,
+    #[pdr(default)]
+    pub _RefDirectory: String,
+    #[pdr(default)]
+    pub _NextVersionFile: String,
+}
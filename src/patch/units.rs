@@ -0,0 +1,122 @@
+use crate::pd;
+use chrono::{DateTime, NaiveDate, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Size of a file in bytes, displayed with binary units
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FileSize(pub u32);
+
+/// Modification time of a file as Unix timestamp, displayed as RFC 3339
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FileTime(pub u32);
+
+impl fmt::Display for FileSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+        let mut size = self.0 as f64 / 1024.0;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+
+        write!(f, "{:.1} {}", size, UNITS[unit])
+    }
+}
+
+impl FileTime {
+    /// Parses an RFC 3339 timestamp or a `YYYY-MM-DD` date, which is taken as midnight UTC
+    pub fn parse(value: &str) -> Option<FileTime> {
+        FileTime::parse_at(value, (0, 0, 0))
+    }
+
+    /// Like [`FileTime::parse`] but a date is taken as its last second, for inclusive upper
+    /// bounds
+    pub fn parse_end(value: &str) -> Option<FileTime> {
+        FileTime::parse_at(value, (23, 59, 59))
+    }
+
+    fn parse_at(value: &str, (hour, minute, second): (u32, u32, u32)) -> Option<FileTime> {
+        let seconds = match DateTime::parse_from_rfc3339(value) {
+            Ok(time) => time.timestamp(),
+            Err(_) => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()?
+                .and_hms_opt(hour, minute, second)?
+                .and_utc()
+                .timestamp(),
+        };
+
+        u32::try_from(seconds).ok().map(FileTime)
+    }
+}
+
+impl fmt::Display for FileTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match DateTime::from_timestamp(self.0 as i64, 0) {
+            Some(time) => f.write_str(&time.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+impl pd::ReadableProperty for FileSize {
+    fn read(pdr: &mut pd::PersistentDataRecord, name: &str) -> crate::Result<Self> {
+        pdr.read(name).map(FileSize)
+    }
+}
+
+impl pd::ReadableProperty for FileTime {
+    fn read(pdr: &mut pd::PersistentDataRecord, name: &str) -> crate::Result<Self> {
+        pdr.read(name).map(FileTime)
+    }
+}
+
+impl pd::WritableProperty for FileSize {
+    fn write(&self, pdr: &mut pd::Writer, name: &str) {
+        pdr.write(name, &self.0);
+    }
+}
+
+impl pd::WritableProperty for FileTime {
+    fn write(&self, pdr: &mut pd::Writer, name: &str) {
+        pdr.write(name, &self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        assert_eq!("512 B", FileSize(512).to_string());
+        assert_eq!("20.0 MiB", FileSize(20 * 1024 * 1024).to_string());
+        assert_eq!("1.5 GiB", FileSize(3 << 29).to_string());
+        assert_eq!("2023-11-14T22:13:20Z", FileTime(1700000000).to_string());
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(
+            Some(FileTime(1700000000)),
+            FileTime::parse("2023-11-14T22:13:20Z")
+        );
+        assert_eq!(Some(FileTime(1699920000)), FileTime::parse("2023-11-14"));
+        assert_eq!(
+            Some(FileTime(1700006399)),
+            FileTime::parse_end("2023-11-14")
+        );
+        assert_eq!(
+            Some(FileTime(1700000000)),
+            FileTime::parse_end("2023-11-14T22:13:20Z")
+        );
+        assert_eq!(None, FileTime::parse("14.11.2023"));
+    }
+}
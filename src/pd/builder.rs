@@ -0,0 +1,169 @@
+use crate::pd::{Encoding, OriginalStrings, PersistentDataRecord, Property, Value, Writer};
+use crate::Result;
+
+/// Builds a PDR file property by property, for test fixtures and other synthetic files.
+///
+/// ```
+/// use ryzom_patch_info::pd::PdrBuilder;
+///
+/// let pdr = PdrBuilder::new()
+///     .begin("_Files")
+///     .begin("_Files")
+///     .string("_FileName", "sounds.bnp")
+///     .uint("_FileSize", 10)
+///     .end()
+///     .end()
+///     .build()
+///     .unwrap();
+/// assert_eq!(6, pdr.tokens.len());
+/// ```
+#[derive(Debug, Default)]
+pub struct PdrBuilder {
+    writer: Writer,
+    /// Names of the structs begun and not ended yet, the innermost last
+    open: Vec<String>,
+}
+
+impl PdrBuilder {
+    pub fn new() -> Self {
+        PdrBuilder::default()
+    }
+
+    /// A builder whose strings are stored with `encoding`, e.g. to build files of older NeL tools
+    pub fn with_encoding(encoding: Encoding) -> Self {
+        PdrBuilder {
+            writer: Writer::with_encoding(encoding, OriginalStrings::default()),
+            open: Vec::new(),
+        }
+    }
+
+    pub fn begin(mut self, name: &str) -> Self {
+        self.writer.begin(name);
+        self.open.push(name.to_string());
+        self
+    }
+
+    /// Ends the struct begun last.
+    ///
+    /// # Panics
+    ///
+    /// When every struct is already ended.
+    pub fn end(mut self) -> Self {
+        let name = self.open.pop().expect("end without a struct to end");
+        self.writer.end(&name);
+        self
+    }
+
+    /// Adds a property with the stored type of `value`, a struct with its properties
+    pub fn value(mut self, name: &str, value: Value) -> Self {
+        self.writer.write_property(&Property {
+            name: name.to_string(),
+            value,
+        });
+        self
+    }
+
+    pub fn flag(self, name: &str) -> Self {
+        self.value(name, Value::Flag)
+    }
+
+    pub fn sint(self, name: &str, value: i32) -> Self {
+        self.value(name, Value::Sint32(value))
+    }
+
+    pub fn uint(self, name: &str, value: u32) -> Self {
+        self.value(name, Value::Uint32(value))
+    }
+
+    pub fn float(self, name: &str, value: f32) -> Self {
+        self.value(name, Value::Float32(value))
+    }
+
+    pub fn string(self, name: &str, value: &str) -> Self {
+        self.value(name, Value::String(value.to_string()))
+    }
+
+    pub fn sint64(self, name: &str, value: i64) -> Self {
+        self.value(name, Value::Sint64(value))
+    }
+
+    pub fn uint64(self, name: &str, value: u64) -> Self {
+        self.value(name, Value::Uint64(value))
+    }
+
+    pub fn float64(self, name: &str, value: f64) -> Self {
+        self.value(name, Value::Float64(value))
+    }
+
+    pub fn sheet_id(self, name: &str, value: u32) -> Self {
+        self.value(name, Value::SheetId(value))
+    }
+
+    pub fn entity_id(self, name: &str, value: u64) -> Self {
+        self.value(name, Value::EntityId(value))
+    }
+
+    /// Ends the structs still open
+    fn finish(mut self) -> Writer {
+        while !self.open.is_empty() {
+            self = self.end();
+        }
+
+        self.writer
+    }
+
+    /// The record as read from the encoded file, structs still open are ended first
+    pub fn build(self) -> Result<PersistentDataRecord<'static>> {
+        self.finish().into_record()
+    }
+
+    /// Encodes the file, structs still open are ended first
+    pub fn into_bytes(self) -> Result<Vec<u8>> {
+        self.finish().into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pd::{self, Tokens::*};
+
+    #[test]
+    fn test_build() {
+        let builder = || {
+            PdrBuilder::new()
+                .begin("_Files")
+                .uint("_FileSize", 10)
+                .uint64("_FileSize", 1 << 32)
+                .entity_id("_Owner", 7)
+                .string("_FileName", "_Files")
+        };
+
+        let pdr = builder().build().unwrap();
+        assert_eq!(
+            vec![
+                BEGIN_TOKEN(0),
+                UINT_TOKEN(1),
+                EXTEND_TOKEN(1),
+                UINT_TOKEN(1),
+                EXTEND_TOKEN(2),
+                STRING_TOKEN(2),
+                STRING_TOKEN(3),
+                END_TOKEN(0),
+            ],
+            pdr.tokens
+        );
+        assert_eq!(vec![10, 0, 1, pd::ET_ENTITY_ID, 7, 0, 0], pdr.args);
+        assert_eq!(
+            vec!["_Files", "_FileSize", "_Owner", "_FileName"],
+            pdr.strings
+        );
+
+        let data = builder().into_bytes().unwrap();
+        let read =
+            pd::read_index_file(&data, false, pd::Limits::default(), Encoding::Utf8).unwrap();
+        assert_eq!(pdr.tokens, read.tokens);
+        assert_eq!(pdr.args, read.args);
+        assert_eq!(pdr.strings, read.strings);
+    }
+}
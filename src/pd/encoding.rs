@@ -0,0 +1,106 @@
+use clap::ValueEnum;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::Utf8Error;
+
+/// How the bytes of the string table are turned into text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Encoding {
+    /// Fail on strings that are not UTF-8
+    #[default]
+    Utf8,
+    /// Replace invalid UTF-8 sequences with U+FFFD
+    Lossy,
+    /// Take every byte as the character of the same code point, as older NeL tools wrote names
+    Latin1,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Lossy => "lossy UTF-8",
+            Encoding::Latin1 => "Latin-1",
+        })
+    }
+}
+
+impl Encoding {
+    /// Decodes a string, borrowing it when the bytes are already its text
+    pub fn decode(self, bytes: &[u8]) -> Result<Cow<'_, str>, Utf8Error> {
+        match self {
+            Encoding::Utf8 => std::str::from_utf8(bytes).map(Cow::Borrowed),
+            Encoding::Lossy => Ok(String::from_utf8_lossy(bytes)),
+            Encoding::Latin1 if bytes.is_ascii() => {
+                Ok(Cow::Borrowed(std::str::from_utf8(bytes).unwrap()))
+            }
+            Encoding::Latin1 => Ok(Cow::Owned(bytes.iter().map(|&byte| byte as char).collect())),
+        }
+    }
+
+    /// Encodes a text, `None` when Latin-1 can not represent one of its characters
+    pub fn encode(self, text: &str) -> Option<Cow<'_, [u8]>> {
+        match self {
+            Encoding::Latin1 if !text.is_ascii() => text
+                .chars()
+                .map(|c| u8::try_from(c).ok())
+                .collect::<Option<Vec<u8>>>()
+                .map(Cow::Owned),
+            _ => Some(Cow::Borrowed(text.as_bytes())),
+        }
+    }
+}
+
+/// Bytes of the strings that did not decode to their own UTF-8 encoding, by their decoded text, so
+/// writers re-emit them unchanged. Lossy decoding may map several strings to one text, the first
+/// one is kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OriginalStrings(HashMap<String, Vec<u8>>);
+
+impl OriginalStrings {
+    /// Remembers `bytes` if they differ from the encoding of `text`
+    pub fn insert(&mut self, text: &str, bytes: &[u8]) {
+        if text.as_bytes() != bytes {
+            self.0
+                .entry(text.to_string())
+                .or_insert_with(|| bytes.to_vec());
+        }
+    }
+
+    pub fn get(&self, text: &str) -> Option<&[u8]> {
+        self.0.get(text).map(Vec::as_slice)
+    }
+
+    /// Adds the strings of another file, known texts keep their bytes
+    pub fn extend(&mut self, other: OriginalStrings) {
+        for (text, bytes) in other.0 {
+            self.0.entry(text).or_insert(bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_encode() {
+        let bytes = b"caf\xe9.bnp";
+        assert!(Encoding::Utf8.decode(bytes).is_err());
+        assert_eq!("caf\u{fffd}.bnp", Encoding::Lossy.decode(bytes).unwrap());
+        let text = Encoding::Latin1.decode(bytes).unwrap();
+        assert_eq!("café.bnp", text);
+        assert!(matches!(
+            Encoding::Latin1.decode(b"main").unwrap(),
+            Cow::Borrowed("main")
+        ));
+
+        assert_eq!(Some(&bytes[..]), Encoding::Latin1.encode(&text).as_deref());
+        assert_eq!(None, Encoding::Latin1.encode("ryzom €"));
+        assert_eq!(
+            Some("café.bnp".as_bytes()),
+            Encoding::Utf8.encode(&text).as_deref()
+        );
+    }
+}
@@ -0,0 +1,359 @@
+use crate::error::{Location, ReadingError, Section};
+use crate::pd::{self, PersistentDataRecord};
+use crate::Result;
+use clap::ValueEnum;
+use std::borrow::Cow;
+use std::fmt;
+use std::io::Cursor;
+
+/// How a PDR file stores its properties
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum FileEncoding {
+    /// NeL's header followed by the token, arg and string tables
+    #[default]
+    Binary,
+    /// An `<xml>` root with one element per property, see [`pd::write_xml`]
+    Xml,
+    /// One line per property after a `PDR text` line, see [`pd::write_text`]
+    Text,
+}
+
+impl fmt::Display for FileEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            FileEncoding::Binary => "binary",
+            FileEncoding::Xml => "XML",
+            FileEncoding::Text => "text",
+        })
+    }
+}
+
+impl FileEncoding {
+    /// The encoding of a file by its first bytes, binary files start with their version number
+    pub fn detect(data: &[u8]) -> FileEncoding {
+        let start = data.trim_ascii_start();
+        if start.starts_with(b"<xml>") || start.starts_with(b"<?xml") {
+            FileEncoding::Xml
+        } else if start.starts_with(pd::TEXT_SIGNATURE.as_bytes()) {
+            FileEncoding::Text
+        } else {
+            FileEncoding::Binary
+        }
+    }
+}
+
+/// Decodes the tables of an index file, in lenient mode broken parts are dropped and recorded as
+/// warnings of the returned record. Counts above the limits fail in both modes. XML and text files
+/// are detected by [`FileEncoding::detect`] and decoded with `encoding` as a whole, their record
+/// has no header.
+pub fn read_index_file(
+    data: &[u8],
+    lenient: bool,
+    limits: pd::Limits,
+    encoding: pd::Encoding,
+) -> Result<PersistentDataRecord<'_>> {
+    match FileEncoding::detect(data) {
+        FileEncoding::Binary => {}
+        file_encoding => return read_markup(data, file_encoding, limits, encoding),
+    }
+    let file_size = data.len() as u64;
+    if file_size < pd::HEADER_SIZE {
+        return Err(ReadingError::Truncated(Section::Header).at(Location::at(Section::Header, 0)));
+    }
+    let mut reader = Cursor::new(data);
+    let mut warnings = Vec::new();
+
+    let header = match pd::read_header(file_size, &mut reader) {
+        Ok(header) => header,
+        Err(error) if lenient => {
+            warnings.push(error);
+            reader.set_position(0);
+            pd::Header::read(&mut reader)?
+        }
+        Err(error) => return Err(error),
+    };
+    limits.check_header(&header)?;
+    // a broken header may claim far more entries than the file holds
+    let present = |count: u32, size: usize| (count as usize).min(data.len() / size);
+
+    // the tables are read where the layout of the header's version puts them
+    reader.set_position(header.tokens_offset());
+    let mut packed_tokens: Vec<pd::Token> =
+        Vec::with_capacity(present(header.token_count, size_of::<pd::Token>()));
+    for _ in 0..header.token_count {
+        match pd::read_u16(&mut reader) {
+            Ok(token) => packed_tokens.push(token),
+            Err(_) if lenient => {
+                warnings.push(truncated(Section::TokenTable, &reader));
+                break;
+            }
+            Err(error) => return Err(error.into()),
+        }
+    }
+
+    reader.set_position(header.args_offset());
+    let mut args: Vec<pd::Arg> =
+        Vec::with_capacity(present(header.arg_count, size_of::<pd::Arg>()));
+    for _ in 0..header.arg_count {
+        match pd::read_u32(&mut reader) {
+            Ok(arg) => args.push(arg),
+            Err(_) if lenient => {
+                warnings.push(truncated(Section::ArgTable, &reader));
+                break;
+            }
+            Err(error) => return Err(error.into()),
+        }
+    }
+
+    // a string table that is not the last section must not run into the next one
+    let strings_end = if header.layout().sections.last() == Some(&Section::StringTable) {
+        file_size
+    } else {
+        header.section_end(Section::StringTable).min(file_size)
+    };
+    let string_data = &data[..strings_end as usize];
+    let mut reader = Cursor::new(string_data);
+    reader.set_position(header.strings_offset());
+    let mut strings: Vec<Cow<str>> = Vec::with_capacity(present(header.string_count, 1));
+    let mut originals = pd::OriginalStrings::default();
+    for index in 0..header.string_count {
+        // every string left takes at least its null byte
+        if reader.position() >= strings_end {
+            let error = truncated(Section::StringTable, &reader);
+            if !lenient {
+                return Err(error);
+            }
+            warnings.push(error);
+            break;
+        }
+        let start = reader.position();
+        let result = read_string(&mut reader, limits);
+        // only the last string may end without its null byte
+        if lenient
+            && index + 1 < header.string_count
+            && !string_data.ends_with(&[0])
+            && reader.position() >= strings_end
+        {
+            reader.set_position(start);
+            warnings.push(truncated(Section::StringTable, &reader));
+            break;
+        }
+        let decoded = result.and_then(|bytes| {
+            let string = encoding.decode(bytes).map_err(|e| {
+                ReadingError::InvalidString(e).at(Location::at(Section::StringTable, start))
+            })?;
+            originals.insert(&string, bytes);
+            Ok(string)
+        });
+        match decoded {
+            Ok(string) => strings.push(string),
+            Err(error) if lenient => {
+                warnings.push(error);
+                strings.push(Cow::Borrowed(""));
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    let token_offset =
+        |index: usize| header.tokens_offset() + index as u64 * size_of::<pd::Token>() as u64;
+    let (tokens, args) = if lenient {
+        drop_unnamed_tokens(
+            &packed_tokens,
+            args,
+            strings.len(),
+            token_offset,
+            &mut warnings,
+        )
+    } else {
+        let tokens = packed_tokens
+            .iter()
+            .enumerate()
+            .map(|(index, &x)| {
+                pd::parse_token(x, strings.len()).map_err(|error| {
+                    error.at(Location::at(Section::TokenTable, token_offset(index)))
+                })
+            })
+            .collect::<Result<_>>()?;
+        (tokens, args)
+    };
+
+    let mut pdr = PersistentDataRecord::new(tokens, args, strings);
+    pdr.warnings = warnings;
+    pdr.limits = limits;
+    pdr.originals = originals;
+    pdr.header = Some(header);
+
+    Ok(pdr)
+}
+
+fn truncated(section: Section, reader: &Cursor<&[u8]>) -> ReadingError {
+    ReadingError::Truncated(section).at(Location::at(section, reader.position()))
+}
+
+/// Drops the tokens whose name is past the end of the string table together with their args
+fn drop_unnamed_tokens(
+    packed_tokens: &[pd::Token],
+    args: Vec<pd::Arg>,
+    string_count: usize,
+    token_offset: impl Fn(usize) -> u64,
+    warnings: &mut Vec<ReadingError>,
+) -> (Vec<pd::Tokens>, Vec<pd::Arg>) {
+    let mut tokens = Vec::with_capacity(packed_tokens.len());
+    let mut kept_args = Vec::with_capacity(args.len());
+    let mut next_arg = 0;
+    let mut index = 0;
+    let mut dropped = 0;
+    while index < packed_tokens.len() {
+        // an EXTEND token and the token it extends are kept or dropped together
+        let extended = matches!(
+            pd::unchecked_token(packed_tokens[index]),
+            pd::Tokens::EXTEND_TOKEN(_)
+        ) && index + 1 < packed_tokens.len();
+        let group = index..index + 1 + extended as usize;
+        let token = pd::unchecked_token(packed_tokens[group.end - 1]);
+        let used = pd::arg_count(&token, extended, args.get(next_arg).copied())
+            .unwrap_or(1)
+            .min(args.len() - next_arg);
+
+        let unnamed = group
+            .clone()
+            .map(|index| (index, pd::unchecked_token(packed_tokens[index]).value()))
+            .find(|&(_, value)| value as usize >= string_count);
+        match unnamed {
+            Some((index, value)) => {
+                if dropped == 0 {
+                    warnings.push(
+                        ReadingError::InvalidStringIndex(value as u32)
+                            .at(Location::at(Section::TokenTable, token_offset(index))),
+                    );
+                }
+                dropped += group.len();
+            }
+            None => {
+                tokens.extend(
+                    group
+                        .clone()
+                        .map(|index| pd::unchecked_token(packed_tokens[index])),
+                );
+                kept_args.extend_from_slice(&args[next_arg..next_arg + used]);
+            }
+        }
+
+        next_arg += used;
+        index = group.end;
+    }
+    kept_args.extend_from_slice(&args[next_arg..]);
+    if dropped > 1 {
+        warnings.push(ReadingError::SkippedTokens(dropped));
+    }
+
+    (tokens, kept_args)
+}
+
+/// Reads an XML or text file into the record the binary tables of its properties would give
+fn read_markup(
+    data: &[u8],
+    file_encoding: FileEncoding,
+    limits: pd::Limits,
+    encoding: pd::Encoding,
+) -> Result<PersistentDataRecord<'static>> {
+    let text = encoding
+        .decode(data)
+        .map_err(|error| ReadingError::InvalidString(error).at(Location::default()))?;
+    let properties = match file_encoding {
+        FileEncoding::Xml => pd::read_xml(&text, limits)?,
+        FileEncoding::Text => pd::read_text(&text, limits)?,
+        FileEncoding::Binary => unreachable!("binary files are read from their tables"),
+    };
+    let mut writer = pd::Writer::default();
+    writer.write_extra(&properties);
+
+    writer.into_record()
+}
+
+/// The bytes of the next string, they are decoded by the caller
+pub fn read_string<'a>(
+    input_stream: &mut Cursor<&'a [u8]>,
+    limits: pd::Limits,
+) -> Result<&'a [u8]> {
+    let data: &'a [u8] = input_stream.get_ref();
+    let start = (input_stream.position() as usize).min(data.len());
+    let remaining = &data[start..];
+
+    // the trailing null byte is optional for the last string
+    let (bytes, consumed) = match remaining.iter().position(|&b| b == 0) {
+        Some(end) => (&remaining[..end], end + 1),
+        None => (remaining, remaining.len()),
+    };
+    input_stream.set_position((start + consumed) as u64);
+    let location = Location::at(Section::StringTable, start as u64);
+    limits.check(pd::Limit::StringLength, bytes.len() as u64, location)?;
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_index_file_borrows_strings() {
+        let mut data: Vec<u8> = Vec::new();
+        for value in [0u32, 40, 1, 1, 1, 10] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&3u16.to_le_bytes());
+        data.extend_from_slice(&10u32.to_le_bytes());
+        data.extend_from_slice(b"_FileSize\0");
+
+        let pdr = read_index_file(&data, false, pd::Limits::default(), pd::Encoding::Utf8).unwrap();
+
+        assert_eq!(vec![pd::Tokens::UINT_TOKEN(0)], pdr.tokens);
+        assert_eq!(vec![10], pdr.args);
+        assert_eq!("_FileSize", pdr.name(pdr.tokens[0]));
+        assert!(data.as_ptr_range().contains(&pdr.strings[0].as_ptr()));
+    }
+
+    #[test]
+    fn test_read_version_1() {
+        let mut data: Vec<u8> = Vec::new();
+        for value in [1u32, 40, 1, 1, 1, 10] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(b"_FileSize\0");
+        data.extend_from_slice(&3u16.to_le_bytes());
+        data.extend_from_slice(&10u32.to_le_bytes());
+
+        let mut pdr =
+            read_index_file(&data, false, pd::Limits::default(), pd::Encoding::Utf8).unwrap();
+
+        assert_eq!(1, pdr.header.as_ref().unwrap().version);
+        assert_eq!(10, pdr.read::<u32>("_FileSize").unwrap());
+    }
+
+    #[test]
+    fn test_more_strings_than_bytes() {
+        // found by the deserializer fuzz target, millions of empty strings were read past the end
+        let mut data: Vec<u8> = Vec::new();
+        for value in [0u32, 28, 0, 0, 3_080_195, 4] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(b"abc\0");
+
+        let error =
+            read_index_file(&data, false, pd::Limits::default(), pd::Encoding::Utf8).unwrap_err();
+        assert!(matches!(
+            error.unlocated(),
+            ReadingError::Truncated(Section::StringTable)
+        ));
+        assert_eq!(
+            Some(28),
+            error.location().and_then(|location| location.offset)
+        );
+
+        let pdr = read_index_file(&data, true, pd::Limits::default(), pd::Encoding::Utf8).unwrap();
+        assert_eq!(vec!["abc"], pdr.strings);
+        assert_eq!(1, pdr.warnings.len());
+    }
+}
@@ -1,4 +1,17 @@
-#[derive(Debug)]
+use crate::error::{Location, ReadingError, Section};
+use crate::pd;
+use crate::Result;
+use serde::Serialize;
+use std::io::{self, Read, Seek};
+
+/// Size in bytes of the header fields every format version starts with, a file has to hold them
+/// before its version is known
+pub const HEADER_SIZE: u64 = 24;
+
+/// The format version files are written with
+pub const WRITTEN_VERSION: u32 = 0;
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Header {
     pub version: u32,
     pub total_size: u32,
@@ -7,3 +20,261 @@ pub struct Header {
     pub string_count: u32,
     pub strings_size: u32,
 }
+
+impl Header {
+    /// Reads the header fields as they are, without checking them
+    pub fn read(input_stream: &mut impl Read) -> io::Result<Header> {
+        Ok(Header {
+            version: read_u32(input_stream)?,
+            total_size: read_u32(input_stream)?,
+            token_count: read_u32(input_stream)?,
+            arg_count: read_u32(input_stream)?,
+            string_count: read_u32(input_stream)?,
+            strings_size: read_u32(input_stream)?,
+        })
+    }
+
+    /// The header of a file holding tables of these sizes, `total_size` is saturated at 4 GiB
+    pub fn for_tables(
+        version: u32,
+        token_count: u32,
+        arg_count: u32,
+        string_count: u32,
+        strings_size: u32,
+    ) -> Header {
+        let mut header = Header {
+            version,
+            total_size: 0,
+            token_count,
+            arg_count,
+            string_count,
+            strings_size,
+        };
+        header.total_size = header.expected_size().min(u32::MAX as u64) as u32;
+
+        header
+    }
+
+    /// Appends the header fields in the order [`Header::read`] reads them
+    pub fn write(&self, output: &mut Vec<u8>) {
+        for field in [
+            self.version,
+            self.total_size,
+            self.token_count,
+            self.arg_count,
+            self.string_count,
+            self.strings_size,
+        ] {
+            output.extend_from_slice(&field.to_le_bytes());
+        }
+    }
+
+    /// The registered format of this header's version
+    pub fn format(&self) -> Option<&'static FormatVersion> {
+        format_version(self.version)
+    }
+
+    /// Section layout of the header's format, unknown versions are assumed to use version 0's
+    pub fn layout(&self) -> &'static FormatVersion {
+        self.format().unwrap_or(&FORMAT_VERSIONS[0])
+    }
+
+    /// Size of the file as computed from the counts of the sections
+    pub fn expected_size(&self) -> u64 {
+        let layout = self.layout();
+
+        layout.header_size
+            + layout
+                .sections
+                .iter()
+                .map(|&section| self.section_size(section))
+                .sum::<u64>()
+    }
+
+    /// Size in bytes the header announces for a section
+    pub fn section_size(&self, section: Section) -> u64 {
+        match section {
+            Section::Header => self.layout().header_size,
+            Section::TokenTable => self.token_count as u64 * size_of::<pd::Token>() as u64,
+            Section::ArgTable => self.arg_count as u64 * size_of::<pd::Arg>() as u64,
+            Section::StringTable => self.strings_size as u64,
+        }
+    }
+
+    /// Byte offset of a section, the sections follow each other in the order of the layout
+    pub fn section_offset(&self, section: Section) -> u64 {
+        let layout = self.layout();
+        if section == Section::Header {
+            return 0;
+        }
+
+        layout.header_size
+            + layout
+                .sections
+                .iter()
+                .take_while(|&&other| other != section)
+                .map(|&other| self.section_size(other))
+                .sum::<u64>()
+    }
+
+    /// Byte offset right after a section
+    pub fn section_end(&self, section: Section) -> u64 {
+        self.section_offset(section) + self.section_size(section)
+    }
+
+    pub fn tokens_offset(&self) -> u64 {
+        self.section_offset(Section::TokenTable)
+    }
+
+    pub fn args_offset(&self) -> u64 {
+        self.section_offset(Section::ArgTable)
+    }
+
+    pub fn strings_offset(&self) -> u64 {
+        self.section_offset(Section::StringTable)
+    }
+}
+
+/// A version of the PDR binary format with its section layout and header checks
+#[derive(Debug)]
+pub struct FormatVersion {
+    pub version: u32,
+    pub name: &'static str,
+    pub header_size: u64,
+    /// The sections after the header in file order
+    pub sections: [Section; 3],
+    /// Checks the header against the size of the file
+    validate: fn(&Header, u64) -> Result<()>,
+}
+
+/// Every format version the reader understands, a new encoding is added here
+pub static FORMAT_VERSIONS: &[FormatVersion] = &[
+    FormatVersion {
+        version: 0,
+        name: "NeL binary PDR",
+        header_size: HEADER_SIZE,
+        sections: [Section::TokenTable, Section::ArgTable, Section::StringTable],
+        validate: validate_total_size,
+    },
+    // NeL itself only writes version 0, this variant has the names before the tables that use
+    // them, so the string table does not have to be found at the end of the file
+    FormatVersion {
+        version: 1,
+        name: "binary PDR with the string table first",
+        header_size: HEADER_SIZE,
+        sections: [Section::StringTable, Section::TokenTable, Section::ArgTable],
+        validate: validate_total_size,
+    },
+];
+
+pub fn format_version(version: u32) -> Option<&'static FormatVersion> {
+    FORMAT_VERSIONS
+        .iter()
+        .find(|format| format.version == version)
+}
+
+/// Versions 0 and 1 store the file size in `total_size` and it has to match the sections
+fn validate_total_size(header: &Header, size: u64) -> Result<()> {
+    let total_size = header.total_size;
+    if u64::from(total_size) > size {
+        return Err(
+            ReadingError::ContentTooSmall(total_size, size).at(Location::at(Section::Header, 4))
+        );
+    }
+
+    let expected_size = header.expected_size();
+    if u64::from(total_size) != expected_size {
+        return Err(ReadingError::ContentWrongSize(total_size, expected_size)
+            .at(Location::at(Section::Header, 4)));
+    }
+
+    Ok(())
+}
+
+/// Reads the header and checks it with the rules of its format version
+pub fn read_header<Stream>(size: u64, file: &mut Stream) -> Result<Header>
+where
+    Stream: Read + Seek,
+{
+    let header = Header::read(file)?;
+    let Some(format) = header.format() else {
+        return Err(
+            ReadingError::UnsupportedVersion(header.version).at(Location::at(Section::Header, 0))
+        );
+    };
+    (format.validate)(&header, size)?;
+
+    Ok(header)
+}
+
+pub fn read_u16(input_stream: &mut impl Read) -> io::Result<u16> {
+    let mut buffer = [0; 2];
+    input_stream.read_exact(&mut buffer[..])?;
+
+    Ok(u16::from_le_bytes(buffer))
+}
+
+pub fn read_u32(input_stream: &mut impl Read) -> io::Result<u32> {
+    let mut buffer = [0; 4];
+    input_stream.read_exact(&mut buffer[..])?;
+
+    Ok(u32::from_le_bytes(buffer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn header_bytes(version: u32) -> Vec<u8> {
+        [version, 40, 1, 1, 1, 10]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_read_header_versions() {
+        let header = read_header(40, &mut Cursor::new(header_bytes(0))).unwrap();
+        assert_eq!(Some(0), header.format().map(|format| format.version));
+        assert_eq!(26, header.args_offset());
+        assert_eq!(30, header.strings_offset());
+        assert_eq!(40, header.expected_size());
+
+        let header = read_header(40, &mut Cursor::new(header_bytes(1))).unwrap();
+        assert_eq!(Some(1), header.format().map(|format| format.version));
+        assert_eq!(HEADER_SIZE, header.strings_offset());
+        assert_eq!(34, header.tokens_offset());
+        assert_eq!(36, header.args_offset());
+        assert_eq!(40, header.expected_size());
+
+        // sizes of files past 4 GiB are compared without truncation
+        assert!(read_header((1 << 32) + 20, &mut Cursor::new(header_bytes(0))).is_ok());
+
+        let error = read_header(40, &mut Cursor::new(header_bytes(3))).unwrap_err();
+        assert!(matches!(
+            error.unlocated(),
+            ReadingError::UnsupportedVersion(3)
+        ));
+    }
+
+    #[test]
+    fn test_header_for_tables() {
+        let header = Header::for_tables(0, 1, 1, 1, 10);
+        assert_eq!(40, header.total_size);
+        assert_eq!(26, header.section_end(Section::TokenTable));
+
+        let mut data = Vec::new();
+        header.write(&mut data);
+        let read = read_header(40, &mut Cursor::new(data)).unwrap();
+        assert_eq!(
+            (1, 1, 1, 10),
+            (
+                read.token_count,
+                read.arg_count,
+                read.string_count,
+                read.strings_size
+            )
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use crate::error::{Location, ReadingError, Section};
+use crate::pd::Header;
+use crate::Result;
+use clap::Args;
+use std::fmt;
+
+/// A bound on the size of a PDR file or of one of its parts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Tokens,
+    Args,
+    Strings,
+    StringLength,
+    Depth,
+    /// The header stores sizes in 32 bits
+    FileSize,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Limit::Tokens => "tokens",
+            Limit::Args => "args",
+            Limit::Strings => "strings",
+            Limit::StringLength => "bytes in one string",
+            Limit::Depth => "levels of nested structs",
+            Limit::FileSize => "bytes",
+        })
+    }
+}
+
+/// Bounds on what a file may hold, checked before memory is set aside for it so a crafted header
+/// can not exhaust it
+#[derive(Args, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Most tokens an index file may hold
+    #[arg(long = "max-tokens", global = true, default_value_t = Limits::DEFAULT.tokens)]
+    pub tokens: u32,
+
+    /// Most args an index file may hold
+    #[arg(long = "max-args", global = true, default_value_t = Limits::DEFAULT.args)]
+    pub args: u32,
+
+    /// Most strings an index file may hold
+    #[arg(long = "max-strings", global = true, default_value_t = Limits::DEFAULT.strings)]
+    pub strings: u32,
+
+    /// Longest string in bytes
+    #[arg(long = "max-string-length", global = true, default_value_t = Limits::DEFAULT.string_length)]
+    pub string_length: u32,
+
+    /// Deepest nesting of structs, at most 256
+    #[arg(
+        long = "max-depth",
+        global = true,
+        default_value_t = Limits::DEFAULT.depth,
+        value_parser = clap::value_parser!(u32).range(1..=i64::from(Limits::MAX_DEPTH))
+    )]
+    pub depth: u32,
+}
+
+impl Limits {
+    /// Structs are read recursively, deeper nesting could overflow the stack of a thread with 2 MiB
+    /// whatever `depth` says
+    pub const MAX_DEPTH: u32 = 256;
+
+    /// Far above the largest Ryzom indexes, which hold a few hundred thousand tokens
+    pub const DEFAULT: Limits = Limits {
+        tokens: 1 << 24,
+        args: 1 << 24,
+        strings: 1 << 22,
+        string_length: 1 << 16,
+        depth: 64,
+    };
+
+    /// Fails when `value` is above the bound of `limit`
+    pub fn check(&self, limit: Limit, value: u64, location: Location) -> Result<()> {
+        let max = match limit {
+            Limit::Tokens => self.tokens,
+            Limit::Args => self.args,
+            Limit::Strings => self.strings,
+            Limit::StringLength => self.string_length,
+            Limit::Depth => self.depth.min(Limits::MAX_DEPTH),
+            Limit::FileSize => u32::MAX,
+        };
+        if value > u64::from(max) {
+            return Err(ReadingError::LimitExceeded {
+                limit,
+                value,
+                max: u64::from(max),
+            }
+            .at(location));
+        }
+
+        Ok(())
+    }
+
+    /// Checks the counts a header announces
+    pub fn check_header(&self, header: &Header) -> Result<()> {
+        for (limit, count, offset) in [
+            (Limit::Tokens, header.token_count, 8),
+            (Limit::Args, header.arg_count, 12),
+            (Limit::Strings, header.string_count, 16),
+        ] {
+            self.check(
+                limit,
+                u64::from(count),
+                Location::at(Section::Header, offset),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits::DEFAULT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_header() {
+        let header = Header {
+            version: 0,
+            total_size: 40,
+            token_count: 1,
+            arg_count: u32::MAX,
+            string_count: 1,
+            strings_size: 10,
+        };
+        assert!(Limits::default()
+            .check_header(&Header {
+                arg_count: 1,
+                ..header
+            })
+            .is_ok());
+
+        let error = Limits::default().check_header(&header).unwrap_err();
+        assert_eq!(
+            "The file holds 4294967295 args, more than the limit of 16777216",
+            error.unlocated().to_string()
+        );
+        assert_eq!(
+            Some(12),
+            error.location().and_then(|location| location.offset)
+        );
+    }
+}
@@ -1,25 +1,56 @@
+mod builder;
+mod encoding;
+mod file;
 mod header;
+mod limits;
 mod persistent_data;
+mod reader;
+#[cfg(test)]
+pub mod strategy;
+mod text;
+mod value;
+mod writer;
+mod xml;
 
+use crate::error::ReadingError;
+use crate::Result;
 use enum_ordinalize::Ordinalize;
 
+pub use builder::*;
+pub use encoding::*;
+pub use file::*;
 pub use header::*;
+pub use limits::*;
+pub use pdr_derive::{PdrRead, PdrWrite};
 pub use persistent_data::*;
+pub use reader::*;
+pub use text::*;
+pub use value::*;
+pub use writer::*;
+pub use xml::*;
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+/// Extend types of NeL's `CPersistentDataRecord::CArg`, a sheet id is stored in one arg after the type
+pub const ET_SHEET_ID: u32 = 0;
+/// An entity id is stored in two args after the type
+pub const ET_ENTITY_ID: u32 = 1;
+
+/// Index of a token name in the string table
+pub type StringId = u16;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Tokens {
-    BEGIN_TOKEN(String),
-    END_TOKEN(String),
-    SINT_TOKEN(String),
-    UINT_TOKEN(String),
-    FLOAT_TOKEN(String),
-    STRING_TOKEN(String),
-    FLAG_TOKEN(String),
-    EXTEND_TOKEN(String),
+    BEGIN_TOKEN(StringId),
+    END_TOKEN(StringId),
+    SINT_TOKEN(StringId),
+    UINT_TOKEN(StringId),
+    FLOAT_TOKEN(StringId),
+    STRING_TOKEN(StringId),
+    FLAG_TOKEN(StringId),
+    EXTEND_TOKEN(StringId),
 }
 
 impl Tokens {
-    pub fn value(&self) -> &String {
+    pub fn value(&self) -> StringId {
         match self {
             Tokens::BEGIN_TOKEN(val)
             | Tokens::END_TOKEN(val)
@@ -28,7 +59,7 @@ impl Tokens {
             | Tokens::FLOAT_TOKEN(val)
             | Tokens::STRING_TOKEN(val)
             | Tokens::FLAG_TOKEN(val)
-            | Tokens::EXTEND_TOKEN(val) => val,
+            | Tokens::EXTEND_TOKEN(val) => *val,
         }
     }
 }
@@ -49,6 +80,7 @@ pub enum TType {
 }
 
 impl TType {
+    #[allow(dead_code)]
     pub fn is_extended(&self) -> bool {
         match self {
             TType::STRUCT_BEGIN
@@ -79,3 +111,54 @@ pub fn token2Type(token: &Tokens, extended: bool) -> TType {
         _ => panic!("Unknown token type"),
     }
 }
+
+/// Splits a stored token into its type bits and string table index without checking either
+pub fn split_token(stored_token: Token) -> (u16, StringId) {
+    (stored_token & 0x7, stored_token >> 3)
+}
+
+/// Splits a stored token into its type bits and string table index
+pub fn parse_token(stored_token: Token, string_count: usize) -> Result<Tokens> {
+    let token = unchecked_token(stored_token);
+    if token.value() as usize >= string_count {
+        return Err(ReadingError::InvalidStringIndex(token.value() as u32));
+    }
+
+    Ok(token)
+}
+
+/// Decodes a stored token without checking its string index
+pub fn unchecked_token(stored_token: Token) -> Tokens {
+    let (token_type, token_value) = split_token(stored_token);
+
+    match token_type {
+        0 => Tokens::BEGIN_TOKEN(token_value),
+        1 => Tokens::END_TOKEN(token_value),
+        2 => Tokens::SINT_TOKEN(token_value),
+        3 => Tokens::UINT_TOKEN(token_value),
+        4 => Tokens::FLOAT_TOKEN(token_value),
+        5 => Tokens::STRING_TOKEN(token_value),
+        6 => Tokens::FLAG_TOKEN(token_value),
+        _ => Tokens::EXTEND_TOKEN(token_value),
+    }
+}
+
+/// Number of args a token reads, `None` for an unknown extend type.
+///
+/// `extend_type` is the next unread arg, extended string tokens store their type there.
+pub fn arg_count(token: &Tokens, extended: bool, extend_type: Option<Arg>) -> Option<usize> {
+    if let Tokens::EXTEND_TOKEN(_) = token {
+        return Some(0);
+    }
+
+    match token2Type(token, extended) {
+        TType::STRUCT_BEGIN | TType::STRUCT_END | TType::FLAG => Some(0),
+        TType::SINT32 | TType::UINT32 | TType::FLOAT32 | TType::STRING => Some(1),
+        TType::SINT64 | TType::UINT64 | TType::FLOAT64 => Some(2),
+        TType::EXTEND_TYPE => match extend_type {
+            Some(ET_SHEET_ID) => Some(2),
+            Some(ET_ENTITY_ID) => Some(3),
+            _ => None,
+        },
+    }
+}
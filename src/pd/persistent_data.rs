@@ -1,159 +1,754 @@
+use crate::error::{Location, ReadingError, Section};
 use crate::pd;
+use crate::Result;
+use std::borrow::Cow;
 
 pub type Token = u16;
 pub type Arg = u32;
 
-pub trait Readable {
-    fn read(pdr: &mut PersistentDataRecord) -> Self;
+pub trait Readable: Sized {
+    /// Names of the properties `read` knows, other properties inside the struct are collected for
+    /// [`PersistentDataRecord::take_extra`]. Empty keeps the struct strict.
+    const FIELDS: &'static [&'static str] = &[];
+
+    fn read(pdr: &mut PersistentDataRecord) -> Result<Self>;
 }
 
-pub trait ReadableProperty {
-    fn read(pdr: &mut PersistentDataRecord, name: &str) -> Self;
+pub trait ReadableProperty: Sized {
+    fn read(pdr: &mut PersistentDataRecord, name: &str) -> Result<Self>;
 }
 
 impl<T: Readable> ReadableProperty for T {
-    fn read(pdr: &mut PersistentDataRecord, name: &str) -> Self {
-        pdr.expect_token(name, pd::TType::STRUCT_BEGIN);
-        let result = T::read(pdr);
-        pdr.expect_token(name, pd::TType::STRUCT_END);
-
-        result
+    fn read(pdr: &mut PersistentDataRecord, name: &str) -> Result<Self> {
+        pdr.read_struct(name, name.to_string())
     }
 }
 
 impl<T: Readable> ReadableProperty for Vec<T> {
-    fn read(pdr: &mut PersistentDataRecord, property: &str) -> Self {
+    fn read(pdr: &mut PersistentDataRecord, property: &str) -> Result<Self> {
         let mut items: Vec<T> = Vec::new();
+        // counts skipped structs as well so paths match the positions in the file
+        let mut position = 0;
         while pdr.has_begin(property) {
-            items.push(pdr.read::<T>(property));
+            let segment = format!("{}[{}]", property, position);
+            position += 1;
+            match pdr.read_struct(property, segment) {
+                Ok(item) => items.push(item),
+                // the struct was skipped, keep the items around it
+                Err(error) if pdr.lenient => pdr.warn(error),
+                Err(error) => return Err(error),
+            }
         }
 
-        items
+        Ok(items)
     }
 }
 
 impl ReadableProperty for u32 {
-    fn read(pdr: &mut PersistentDataRecord, name: &str) -> Self {
-        pdr.expect_token(name, pd::TType::UINT32);
-        let arg = pdr.pop_arg();
+    fn read(pdr: &mut PersistentDataRecord, name: &str) -> Result<Self> {
+        pdr.expect_token(name, pd::TType::UINT32)?;
 
-        arg
+        pdr.pop_arg(name)
     }
 }
 
 impl ReadableProperty for i32 {
-    fn read(pdr: &mut PersistentDataRecord, name: &str) -> Self {
-        pdr.expect_token(name, pd::TType::SINT32);
-        let arg = pdr.pop_arg();
+    fn read(pdr: &mut PersistentDataRecord, name: &str) -> Result<Self> {
+        pdr.expect_token(name, pd::TType::SINT32)?;
+        let arg = pdr.pop_arg(name)?;
 
-        arg as i32
+        Ok(arg as i32)
     }
 }
 
 impl ReadableProperty for bool {
-    fn read(pdr: &mut PersistentDataRecord, name: &str) -> Self {
-        pdr.expect_token(name, pd::TType::SINT32);
-        let arg = pdr.pop_arg();
+    fn read(pdr: &mut PersistentDataRecord, name: &str) -> Result<Self> {
+        pdr.expect_token(name, pd::TType::SINT32)?;
+        let arg = pdr.pop_arg(name)?;
 
-        arg != 0
+        Ok(arg != 0)
     }
 }
 
 impl ReadableProperty for String {
-    fn read(pdr: &mut PersistentDataRecord, name: &str) -> Self {
-        pdr.expect_token(name, pd::TType::STRING);
-        let arg = pdr.pop_arg();
+    fn read(pdr: &mut PersistentDataRecord, name: &str) -> Result<Self> {
+        pdr.expect_token(name, pd::TType::STRING)?;
+        let arg = pdr.pop_arg(name)?;
 
-        pdr.strings[arg as usize].clone()
+        match pdr.strings.get(arg as usize) {
+            Some(value) => Ok(value.to_string()),
+            None => Err(ReadingError::InvalidStringIndex(arg)
+                .at(pdr.arg_location(pdr._ArgOffset - 1, Some(name)))),
+        }
     }
 }
 
 impl<T: ReadableProperty> ReadableProperty for Option<T> {
-    fn read(pdr: &mut PersistentDataRecord, name: &str) -> Self {
+    fn read(pdr: &mut PersistentDataRecord, name: &str) -> Result<Self> {
         if pdr.has_property(name) {
-            Some(T::read(pdr, name))
+            T::read(pdr, name).map(Some)
         } else {
-            None
+            Ok(None)
         }
     }
 }
 
+/// A decoded PDR that borrows its string table from the underlying buffer, strings that had to be
+/// decoded are owned and their bytes kept in `originals`.
+///
+/// Tokens only reference their names by index, use [`PersistentDataRecord::name`] to resolve them.
 #[derive(Debug)]
-pub struct PersistentDataRecord {
+pub struct PersistentDataRecord<'a> {
     pub _TokenOffset: usize,
     pub _ArgOffset: usize,
     pub tokens: Vec<pd::Tokens>,
     pub args: Vec<Arg>,
-    pub strings: Vec<String>,
+    pub strings: Vec<Cow<'a, str>>,
+    /// Bytes of the strings whose text differs from them, for writing them back unchanged
+    pub originals: pd::OriginalStrings,
+    /// Skips structs that can not be decoded and records why in `warnings` instead of failing
+    pub lenient: bool,
+    pub warnings: Vec<ReadingError>,
+    /// Bounds the nesting of structs, the counts and strings are checked by the loader
+    pub limits: pd::Limits,
+    /// The header of the file the tables were read from, it locates tokens and args in errors
+    pub header: Option<pd::Header>,
+    /// Structs entered while reading, for error locations
+    path: Vec<String>,
+    /// Structs entered while reading, including those read as generic values
+    depth: u32,
+    /// Known fields and the unknown properties collected so far of every struct being read
+    fields: Vec<(&'static [&'static str], Vec<pd::Property>)>,
 }
 
-impl PersistentDataRecord {
-    fn peek_token(&self) -> &pd::Tokens {
-        &self.tokens[self._TokenOffset]
-    }
-
-    fn expect_token(&mut self, name: &str, expected: pd::TType) {
-        let token = self.pop_token();
-        let token_type = pd::token2Type(&token, false);
-        let token_value = token.value();
-
-        if let pd::Tokens::EXTEND_TOKEN(_) = token {
-            let token = self.pop_token();
-            let token_type = pd::token2Type(&token, true);
-            if token_type != expected || token.value() != name {
-                panic!(
-                    "Expected {} {:?} token but found {} {:?}",
-                    name,
-                    expected,
-                    token.value(),
-                    token_type
-                );
+impl<'a> PersistentDataRecord<'a> {
+    pub fn new(
+        tokens: Vec<pd::Tokens>,
+        args: Vec<Arg>,
+        strings: Vec<impl Into<Cow<'a, str>>>,
+    ) -> Self {
+        PersistentDataRecord {
+            _TokenOffset: 0,
+            _ArgOffset: 0,
+            tokens,
+            args,
+            strings: strings.into_iter().map(Into::into).collect(),
+            originals: pd::OriginalStrings::default(),
+            lenient: false,
+            warnings: Vec::new(),
+            limits: pd::Limits::default(),
+            header: None,
+            path: Vec::new(),
+            depth: 0,
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn name(&self, token: pd::Tokens) -> &str {
+        &self.strings[token.value() as usize]
+    }
+
+    fn peek_token(&self) -> Option<pd::Tokens> {
+        self.tokens.get(self._TokenOffset).copied()
+    }
+
+    /// The token naming the next property, after an EXTEND token the one it extends as
+    /// [`PersistentDataRecord::read_property`] takes the name from it
+    fn peek_property_token(&self) -> Option<pd::Tokens> {
+        match self.peek_token()? {
+            pd::Tokens::EXTEND_TOKEN(_) => self.tokens.get(self._TokenOffset + 1).copied(),
+            token => Some(token),
+        }
+    }
+
+    /// Location of a token or arg, `property` is appended to the path of the enclosing structs
+    fn location(&self, section: Section, offset: u64, property: Option<&str>) -> Location {
+        let mut path = self.path.join("/");
+        if let Some(property) = property {
+            if !path.is_empty() {
+                path.push('/');
+            }
+            path.push_str(property);
+        }
+        let location = Location::at(section, offset);
+
+        // records read without a schema have no path
+        if path.is_empty() {
+            location
+        } else {
+            location.with_path(path)
+        }
+    }
+
+    /// Where a section starts in the file, records built in memory are laid out as written
+    fn section_offset(&self, section: Section) -> u64 {
+        match self.header {
+            Some(ref header) => header.section_offset(section),
+            None => pd::Header::for_tables(
+                pd::WRITTEN_VERSION,
+                self.tokens.len() as u32,
+                self.args.len() as u32,
+                self.strings.len() as u32,
+                0,
+            )
+            .section_offset(section),
+        }
+    }
+
+    fn token_location(&self, index: usize, property: Option<&str>) -> Location {
+        let offset = self.section_offset(Section::TokenTable) + (index * size_of::<Token>()) as u64;
+
+        self.location(Section::TokenTable, offset, property)
+    }
+
+    fn arg_location(&self, index: usize, property: Option<&str>) -> Location {
+        let offset = self.section_offset(Section::ArgTable) + (index * size_of::<Arg>()) as u64;
+
+        self.location(Section::ArgTable, offset, property)
+    }
+
+    fn describe(&self, token: pd::Tokens, token_type: pd::TType) -> String {
+        format!("{} {:?}", self.name(token), token_type)
+    }
+
+    fn expect_token(&mut self, name: &str, expected: pd::TType) -> Result<()> {
+        // struct tokens are already part of the path
+        let property = match expected {
+            pd::TType::STRUCT_BEGIN | pd::TType::STRUCT_END => None,
+            _ => Some(name),
+        };
+        let index = self._TokenOffset;
+        let (token, extended) = self.pop_extended_token(property)?;
+
+        let token_type = pd::token2Type(&token, extended);
+        if token_type != expected || self.name(token) != name {
+            return Err(ReadingError::UnexpectedToken {
+                expected: format!("{} {:?}", name, expected),
+                found: self.describe(token, token_type),
             }
-        } else if token_type != expected || token_value != name {
-            panic!(
-                "Expected {} {:?} token but found {} {:?}",
-                name, expected, token_value, token_type
-            );
+            .at(self.token_location(index, property)));
         }
+
+        Ok(())
     }
 
-    fn pop_token(&mut self) -> pd::Tokens {
-        let value = self.peek_token().clone();
+    fn pop_token(&mut self, property: Option<&str>) -> Result<pd::Tokens> {
+        let Some(value) = self.peek_token() else {
+            return Err(ReadingError::Truncated(Section::TokenTable)
+                .at(self.token_location(self._TokenOffset, property)));
+        };
 
         self._TokenOffset += 1;
 
-        value
+        Ok(value)
+    }
+
+    /// Pops a token and, after an EXTEND token, the token it extends
+    fn pop_extended_token(&mut self, property: Option<&str>) -> Result<(pd::Tokens, bool)> {
+        let index = self._TokenOffset;
+        let token = self.pop_token(property)?;
+        if !matches!(token, pd::Tokens::EXTEND_TOKEN(_)) {
+            return Ok((token, false));
+        }
+        match self.pop_token(property)? {
+            pd::Tokens::EXTEND_TOKEN(_) => {
+                Err(ReadingError::InvalidFileFormat.at(self.token_location(index, property)))
+            }
+            token => Ok((token, true)),
+        }
     }
 
     fn has_property(&self, name: &str) -> bool {
-        name == self.peek_token().value()
+        self.peek_token()
+            .is_some_and(|token| name == self.name(token))
+    }
+
+    fn has_end(&self, expected_name: &str) -> bool {
+        if let Some(token @ pd::Tokens::END_TOKEN(_)) = self.peek_token() {
+            expected_name == self.name(token)
+        } else {
+            false
+        }
     }
 
     fn has_begin(&self, expected_name: &str) -> bool {
-        if let pd::Tokens::BEGIN_TOKEN(actual_name) = self.peek_token() {
-            expected_name == actual_name
+        if let Some(token @ pd::Tokens::BEGIN_TOKEN(_)) = self.peek_token() {
+            expected_name == self.name(token)
         } else {
             false
         }
     }
 
-    fn pop_arg(&mut self) -> Arg {
-        let arg = self.args[self._ArgOffset];
+    fn pop_arg(&mut self, property: &str) -> Result<Arg> {
+        let Some(&arg) = self.args.get(self._ArgOffset) else {
+            return Err(ReadingError::Truncated(Section::ArgTable)
+                .at(self.arg_location(self._ArgOffset, Some(property))));
+        };
         self._ArgOffset += 1;
 
-        arg
+        Ok(arg)
+    }
+
+    /// Reads a struct enclosed by begin and end tokens, `segment` names it in error locations.
+    ///
+    /// In lenient mode a struct that fails to decode is skipped up to its end token.
+    fn read_struct<T: Readable>(&mut self, name: &str, segment: String) -> Result<T> {
+        self.path.push(segment);
+        let result = self.read_struct_body(name);
+        self.path.pop();
+
+        result
+    }
+
+    fn read_struct_body<T: Readable>(&mut self, name: &str) -> Result<T> {
+        let begin = self._TokenOffset;
+        self.expect_token(name, pd::TType::STRUCT_BEGIN)?;
+        let body = (self._TokenOffset, self._ArgOffset);
+        self.depth += 1;
+        self.fields.push((T::FIELDS, Vec::new()));
+        let result = self.check_depth(begin).and_then(|()| T::read(self));
+        self.fields.pop();
+        self.depth -= 1;
+        let result = result.and_then(|value| {
+            self.close_struct(name)?;
+            Ok(value)
+        });
+        if result.is_err() && self.lenient {
+            (self._TokenOffset, self._ArgOffset) = body;
+            self.skip_struct();
+            self.resync_args();
+        }
+
+        result
+    }
+
+    /// Fails once the struct starting at token `index` is nested deeper than the limit
+    fn check_depth(&self, index: usize) -> Result<()> {
+        let location = self.token_location(index, None);
+
+        self.limits
+            .check(pd::Limit::Depth, u64::from(self.depth), location)
+    }
+
+    /// Expects the end token of a struct, in lenient mode unknown properties before it are skipped
+    /// and a missing end at the end of a truncated file is accepted
+    fn close_struct(&mut self, name: &str) -> Result<()> {
+        if !self.lenient || self.has_end(name) {
+            return self.expect_token(name, pd::TType::STRUCT_END);
+        }
+
+        let location = self.token_location(self._TokenOffset, None);
+        if self.peek_token().is_none() {
+            self.warn(ReadingError::Truncated(Section::TokenTable).at(location));
+        } else {
+            let skipped = self.skip_struct();
+            self.warn(ReadingError::SkippedTokens(skipped).at(location));
+        }
+
+        Ok(())
+    }
+
+    /// Points the arg cursor at the args of the remaining tokens counted back from the end of the
+    /// arg table, a token whose type was damaged would otherwise shift every arg after it
+    fn resync_args(&mut self) {
+        let mut remaining = 0;
+        let mut extended = false;
+        for token in &self.tokens[self._TokenOffset..] {
+            if !matches!(token, pd::Tokens::BEGIN_TOKEN(_) | pd::Tokens::END_TOKEN(_)) {
+                // the extend type is not known yet, sheet ids are the common case
+                remaining += pd::arg_count(token, extended, Some(pd::ET_SHEET_ID)).unwrap_or(1);
+            }
+            extended = matches!(token, pd::Tokens::EXTEND_TOKEN(_));
+        }
+        if let Some(offset) = self.args.len().checked_sub(remaining) {
+            self._ArgOffset = offset;
+        }
+    }
+
+    /// Records a warning, the end of a truncated token table is only reported once
+    fn warn(&mut self, warning: ReadingError) {
+        let truncated = |warning: &ReadingError| {
+            matches!(
+                warning.unlocated(),
+                ReadingError::Truncated(Section::TokenTable)
+            )
+        };
+        if !(truncated(&warning) && self.warnings.iter().any(truncated)) {
+            self.warnings.push(warning);
+        }
+    }
+
+    /// Moves past the end token of the current struct, returns the number of tokens skipped
+    fn skip_struct(&mut self) -> usize {
+        let start = self._TokenOffset;
+        let mut depth = 0;
+        let mut extended = false;
+        while let Some(token) = self.peek_token() {
+            self._TokenOffset += 1;
+            match token {
+                pd::Tokens::BEGIN_TOKEN(_) => depth += 1,
+                pd::Tokens::END_TOKEN(_) if depth == 0 => break,
+                pd::Tokens::END_TOKEN(_) => depth -= 1,
+                _ => {
+                    let extend_type = self.args.get(self._ArgOffset).copied();
+                    self._ArgOffset += pd::arg_count(&token, extended, extend_type).unwrap_or(1);
+                }
+            }
+            extended = matches!(token, pd::Tokens::EXTEND_TOKEN(_));
+        }
+
+        self._TokenOffset - start
+    }
+
+    /// Reads the next property whatever its name and type
+    pub fn read_property(&mut self) -> Result<pd::Property> {
+        let index = self._TokenOffset;
+        let (token, extended) = self.pop_extended_token(None)?;
+        let name = self.name(token).to_string();
+
+        let value = match pd::token2Type(&token, extended) {
+            pd::TType::STRUCT_BEGIN => {
+                self.depth += 1;
+                let properties = self
+                    .check_depth(index)
+                    .and_then(|()| self.read_struct_properties(&name));
+                self.depth -= 1;
+                pd::Value::Struct(properties?)
+            }
+            pd::TType::STRUCT_END => {
+                return Err(ReadingError::UnexpectedToken {
+                    expected: "a property".to_string(),
+                    found: self.describe(token, pd::TType::STRUCT_END),
+                }
+                .at(self.token_location(index, None)))
+            }
+            pd::TType::FLAG => pd::Value::Flag,
+            pd::TType::SINT32 => pd::Value::Sint32(self.pop_arg(&name)? as i32),
+            pd::TType::UINT32 => pd::Value::Uint32(self.pop_arg(&name)?),
+            pd::TType::FLOAT32 => pd::Value::Float32(f32::from_bits(self.pop_arg(&name)?)),
+            pd::TType::STRING => {
+                let arg = self.pop_arg(&name)?;
+                match self.strings.get(arg as usize) {
+                    Some(value) => pd::Value::String(value.to_string()),
+                    None => {
+                        return Err(ReadingError::InvalidStringIndex(arg)
+                            .at(self.arg_location(self._ArgOffset - 1, Some(&name))))
+                    }
+                }
+            }
+            pd::TType::SINT64 => pd::Value::Sint64(self.pop_arg64(&name)? as i64),
+            pd::TType::UINT64 => pd::Value::Uint64(self.pop_arg64(&name)?),
+            pd::TType::FLOAT64 => pd::Value::Float64(f64::from_bits(self.pop_arg64(&name)?)),
+            pd::TType::EXTEND_TYPE => match self.pop_arg(&name)? {
+                pd::ET_SHEET_ID => pd::Value::SheetId(self.pop_arg(&name)?),
+                pd::ET_ENTITY_ID => pd::Value::EntityId(self.pop_arg64(&name)?),
+                extend_type => {
+                    return Err(ReadingError::UnexpectedToken {
+                        expected: "a sheet or entity id".to_string(),
+                        found: format!("{} with extend type {}", name, extend_type),
+                    }
+                    .at(self.token_location(index, Some(&name))))
+                }
+            },
+        };
+
+        Ok(pd::Property { name, value })
+    }
+
+    /// The properties of a struct read as a generic value, up to and including its end token
+    fn read_struct_properties(&mut self, name: &str) -> Result<Vec<pd::Property>> {
+        let mut properties = Vec::new();
+        while !self.has_end(name) {
+            properties.push(self.read_property()?);
+        }
+        self.expect_token(name, pd::TType::STRUCT_END)?;
+
+        Ok(properties)
+    }
+
+    /// 64-bit values are stored in two args, the low half first
+    fn pop_arg64(&mut self, property: &str) -> Result<u64> {
+        let low = self.pop_arg(property)? as u64;
+        let high = self.pop_arg(property)? as u64;
+
+        Ok(high << 32 | low)
+    }
+
+    /// Reads every remaining property whatever its name, for records without a schema
+    pub fn read_properties(&mut self) -> Result<Vec<pd::Property>> {
+        let mut properties = Vec::new();
+        while self.peek_token().is_some() {
+            properties.push(self.read_property()?);
+        }
+
+        Ok(properties)
+    }
+
+    /// Collects the properties ahead that are not fields of the struct being read
+    fn collect_unknown(&mut self) -> Result<()> {
+        let Some(&(fields, _)) = self.fields.last() else {
+            return Ok(());
+        };
+        if fields.is_empty() {
+            return Ok(());
+        }
+
+        while let Some(token) = self.peek_property_token() {
+            if matches!(token, pd::Tokens::END_TOKEN(_)) || fields.contains(&self.name(token)) {
+                break;
+            }
+            let property = self.read_property()?;
+            if let Some((_, extra)) = self.fields.last_mut() {
+                extra.push(property);
+            }
+        }
+
+        Ok(())
     }
 
-    pub fn read<T: ReadableProperty>(&mut self, name: &str) -> T {
+    /// The properties of the struct being read that are not in [`Readable::FIELDS`], call it
+    /// after reading the known fields
+    pub fn take_extra(&mut self) -> Result<Vec<pd::Property>> {
+        self.collect_unknown()?;
+
+        Ok(self
+            .fields
+            .last_mut()
+            .map(|(_, extra)| std::mem::take(extra))
+            .unwrap_or_default())
+    }
+
+    pub fn read<T: ReadableProperty>(&mut self, name: &str) -> Result<T> {
+        self.collect_unknown()?;
         T::read(self, name)
     }
 
-    pub fn read_prop_vec<T: ReadableProperty>(&mut self, name: &str) -> Vec<T> {
+    /// Like [`PersistentDataRecord::read`], in lenient mode a property that can not be read is
+    /// replaced by its default and recorded as a warning
+    pub fn read_or_default<T: ReadableProperty + Default>(&mut self, name: &str) -> Result<T> {
+        match self.read(name) {
+            Err(error) if self.lenient => {
+                self.warn(error);
+                Ok(T::default())
+            }
+            result => result,
+        }
+    }
+
+    pub fn read_prop_vec<T: ReadableProperty>(&mut self, name: &str) -> Result<Vec<T>> {
+        self.collect_unknown()?;
         let mut items: Vec<T> = Vec::new();
         while self.has_property(name) {
-            items.push(T::read(self, name));
+            items.push(T::read(self, name)?);
+        }
+
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pd::Tokens::*;
+
+    #[derive(Debug)]
+    struct Version {
+        _HashKey: Vec<u32>,
+    }
+
+    impl Readable for Version {
+        fn read(pdr: &mut PersistentDataRecord) -> Result<Self> {
+            Ok(Version {
+                _HashKey: pdr.read_prop_vec("_HashKey")?,
+            })
+        }
+    }
+
+    #[test]
+    fn test_error_path() {
+        let strings = vec!["_Versions", "_HashKey", "_FileSize"];
+        let mut pdr = PersistentDataRecord::new(
+            vec![
+                BEGIN_TOKEN(0),
+                UINT_TOKEN(1),
+                END_TOKEN(0),
+                BEGIN_TOKEN(0),
+                UINT_TOKEN(1),
+                FLAG_TOKEN(1),
+                END_TOKEN(0),
+            ],
+            vec![1, 2],
+            strings,
+        );
+
+        let error = pdr.read::<Vec<Version>>("_Versions").unwrap_err();
+
+        assert_eq!(
+            Some(&Location {
+                file: None,
+                offset: Some(pd::HEADER_SIZE + 10),
+                section: Some(Section::TokenTable),
+                path: Some("_Versions[1]/_HashKey".to_string()),
+            }),
+            error.location()
+        );
+        assert_eq!(
+            "Expected _HashKey UINT32 token but found _HashKey FLAG",
+            error.unlocated().to_string()
+        );
+    }
+
+    #[test]
+    fn test_lenient_skips_broken_struct() {
+        let strings = vec!["_Versions", "_HashKey"];
+        let mut pdr = PersistentDataRecord::new(
+            vec![
+                BEGIN_TOKEN(0),
+                FLAG_TOKEN(1),
+                END_TOKEN(0),
+                BEGIN_TOKEN(0),
+                UINT_TOKEN(1),
+                END_TOKEN(0),
+            ],
+            vec![7, 8],
+            strings,
+        );
+        pdr.lenient = true;
+
+        let versions = pdr.read::<Vec<Version>>("_Versions").unwrap();
+
+        // the damaged token used one arg, resyncing keeps the next struct aligned
+        assert_eq!(1, versions.len());
+        assert_eq!(vec![8], versions[0]._HashKey);
+        assert_eq!(1, pdr.warnings.len());
+        assert_eq!(
+            Some("_Versions[0]/_HashKey".to_string()),
+            pdr.warnings[0]
+                .location()
+                .and_then(|location| location.path.clone())
+        );
+    }
+
+    #[derive(Debug)]
+    struct File {
+        _FileName: String,
+        extra: Vec<pd::Property>,
+    }
+
+    impl Readable for File {
+        const FIELDS: &'static [&'static str] = &["_FileName"];
+
+        fn read(pdr: &mut PersistentDataRecord) -> Result<Self> {
+            Ok(File {
+                _FileName: pdr.read("_FileName")?,
+                extra: pdr.take_extra()?,
+            })
         }
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let strings = vec![
+            "_File",
+            "_FileName",
+            "_Mirror",
+            "_Priority",
+            "fauna_maps.bnp",
+        ];
+        let mut pdr = PersistentDataRecord::new(
+            vec![
+                BEGIN_TOKEN(0),
+                SINT_TOKEN(3),
+                STRING_TOKEN(1),
+                BEGIN_TOKEN(2),
+                EXTEND_TOKEN(3),
+                UINT_TOKEN(3),
+                FLAG_TOKEN(2),
+                END_TOKEN(2),
+                END_TOKEN(0),
+            ],
+            vec![u32::MAX, 4, 1, 2],
+            strings,
+        );
+
+        let file = pdr.read::<File>("_File").unwrap();
+
+        assert_eq!("fauna_maps.bnp", file._FileName);
+        assert_eq!(
+            serde_json::json!([
+                {"name": "_Priority", "value": {"sint32": -1}},
+                {"name": "_Mirror", "value": {"struct": [
+                    {"name": "_Priority", "value": {"uint64": (2u64 << 32) | 1}},
+                    {"name": "_Mirror", "value": "flag"},
+                ]}},
+            ]),
+            serde_json::to_value(&file.extra).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_extend_names_known_field() {
+        // found by the persistent_data fuzz target, the EXTEND token of a known field had another
+        // name so the field was collected as an extra property and written twice
+        let strings = vec!["_File", "_FileName", "_Mirror", "fauna_maps.bnp"];
+        let mut pdr = PersistentDataRecord::new(
+            vec![
+                BEGIN_TOKEN(0),
+                EXTEND_TOKEN(2),
+                STRING_TOKEN(1),
+                END_TOKEN(0),
+            ],
+            vec![pd::ET_SHEET_ID, 3],
+            strings,
+        );
+
+        let error = pdr.read::<File>("_File").unwrap_err();
+        assert_eq!(
+            "Expected _FileName STRING token but found _FileName EXTEND_TYPE",
+            error.unlocated().to_string()
+        );
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let nested = |depth, limit| {
+            let mut tokens = vec![BEGIN_TOKEN(0); depth];
+            tokens.extend(vec![END_TOKEN(0); depth]);
+            let mut pdr = PersistentDataRecord::new(tokens, Vec::new(), vec!["_Mirror"]);
+            pdr.limits.depth = limit;
+            pdr.read_properties()
+        };
+
+        assert!(nested(3, 3).is_ok());
+        // a limit above the maximum does not let reading recurse deeper
+        let depth = pd::Limits::MAX_DEPTH as usize;
+        assert!(nested(depth, u32::MAX).is_ok());
+        assert!(nested(depth + 1, u32::MAX).is_err());
+        let error = nested(4, 3).unwrap_err();
+        assert!(matches!(
+            error.unlocated(),
+            ReadingError::LimitExceeded { value: 4, .. }
+        ));
+        assert_eq!(
+            Some(pd::HEADER_SIZE + 6),
+            error.location().and_then(|location| location.offset)
+        );
+    }
+
+    #[test]
+    fn test_extend_of_extend() {
+        // found by the persistent_data fuzz target, it panicked on the second EXTEND token
+        let mut pdr = PersistentDataRecord::new(
+            vec![EXTEND_TOKEN(0), EXTEND_TOKEN(0), UINT_TOKEN(0)],
+            vec![1, 2],
+            vec!["_Xp"],
+        );
 
-        items
+        let error = pdr.read_properties().unwrap_err();
+        assert!(matches!(error.unlocated(), ReadingError::InvalidFileFormat));
+        assert_eq!(
+            Some(pd::HEADER_SIZE),
+            error.location().and_then(|location| location.offset)
+        );
     }
 }
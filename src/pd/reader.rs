@@ -0,0 +1,297 @@
+use crate::error::{Location, ReadingError, Section};
+use crate::pd::{self, Encoding, Limit, Limits, TType, Tokens};
+use crate::Result;
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Number of bytes fetched at once from the token and arg tables
+const CHUNK_SIZE: u64 = 4096;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Scalar<'a> {
+    Flag,
+    Sint32(i32),
+    Uint32(u32),
+    Float32(f32),
+    String(&'a str),
+    Sint64(i64),
+    Uint64(u64),
+    Float64(f64),
+    SheetId(u32),
+    EntityId(u64),
+}
+
+impl fmt::Display for Scalar<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Scalar::Flag => write!(f, "true"),
+            Scalar::Sint32(value) => write!(f, "{}", value),
+            Scalar::Uint32(value) => write!(f, "{}", value),
+            Scalar::Float32(value) => write!(f, "{}", value),
+            Scalar::String(value) => write!(f, "{}", value),
+            Scalar::Sint64(value) => write!(f, "{}", value),
+            Scalar::Uint64(value) => write!(f, "{}", value),
+            Scalar::Float64(value) => write!(f, "{}", value),
+            Scalar::SheetId(value) => write!(f, "{}", value),
+            Scalar::EntityId(value) => write!(f, "{:#x}", value),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Event<'a> {
+    Begin(&'a str),
+    End(&'a str),
+    Value(&'a str, Scalar<'a>),
+}
+
+/// Pull based reader that decodes one token at a time.
+///
+/// Only the string table is held in memory, the token and arg tables are read in chunks as the
+/// events are consumed. The source has to be seekable: the string table that names every token is
+/// stored after the token and arg tables, reading a pipe in order would mean holding both tables
+/// in memory. Pipes fail with [`ReadingError::NotSeekable`].
+pub struct Reader<R> {
+    source: R,
+    strings: Vec<String>,
+    tokens: Table,
+    args: Table,
+}
+
+impl<R: Read + Seek> Reader<R> {
+    pub fn new(mut source: R, limits: Limits, encoding: Encoding) -> Result<Self> {
+        let size = source
+            .seek(SeekFrom::End(0))
+            .map_err(|error| match error.kind() {
+                io::ErrorKind::NotSeekable => ReadingError::NotSeekable,
+                _ => ReadingError::from(error),
+            })?;
+        if size < pd::HEADER_SIZE {
+            return Err(
+                ReadingError::Truncated(Section::Header).at(Location::at(Section::Header, 0))
+            );
+        }
+        source.seek(SeekFrom::Start(0))?;
+        let header = pd::read_header(size, &mut source)?;
+        limits.check_header(&header)?;
+
+        source.seek(SeekFrom::Start(header.strings_offset()))?;
+        let mut table = vec![0; header.strings_size as usize];
+        source.read_exact(&mut table)?;
+        // every string takes at least its null byte
+        let mut strings = Vec::with_capacity(header.string_count.min(header.strings_size) as usize);
+        let mut offset = header.strings_offset();
+        for bytes in table.split(|&b| b == 0).take(header.string_count as usize) {
+            let location = Location::at(Section::StringTable, offset);
+            limits.check(Limit::StringLength, bytes.len() as u64, location.clone())?;
+            let value = encoding
+                .decode(bytes)
+                .map_err(|e| ReadingError::InvalidString(e).at(location))?;
+            strings.push(value.into_owned());
+            offset += bytes.len() as u64 + 1;
+        }
+        if strings.len() != header.string_count as usize {
+            return Err(ReadingError::Truncated(Section::StringTable)
+                .at(Location::at(Section::StringTable, offset)));
+        }
+
+        Ok(Reader {
+            source,
+            strings,
+            tokens: Table::new(
+                Section::TokenTable,
+                header.tokens_offset(),
+                header.section_end(Section::TokenTable),
+            ),
+            args: Table::new(
+                Section::ArgTable,
+                header.args_offset(),
+                header.section_end(Section::ArgTable),
+            ),
+        })
+    }
+
+    pub fn next_event(&mut self) -> Result<Option<Event<'_>>> {
+        let token_location = self.tokens.location();
+        let Some(mut token) = self.next_token()? else {
+            return Ok(None);
+        };
+        let extended = matches!(token, Tokens::EXTEND_TOKEN(_));
+        if extended {
+            token = match self.next_token()? {
+                Some(Tokens::EXTEND_TOKEN(_)) | None => {
+                    return Err(ReadingError::InvalidFileFormat.at(token_location))
+                }
+                Some(token) => token,
+            };
+        }
+
+        let strings = &self.strings;
+        let args = &mut self.args;
+        let source = &mut self.source;
+        let name = strings[token.value() as usize].as_str();
+        let event = match pd::token2Type(&token, extended) {
+            TType::STRUCT_BEGIN => Event::Begin(name),
+            TType::STRUCT_END => Event::End(name),
+            TType::FLAG => Event::Value(name, Scalar::Flag),
+            TType::SINT32 => Event::Value(name, Scalar::Sint32(args.take_u32(source)? as i32)),
+            TType::UINT32 => Event::Value(name, Scalar::Uint32(args.take_u32(source)?)),
+            TType::FLOAT32 => Event::Value(
+                name,
+                Scalar::Float32(f32::from_bits(args.take_u32(source)?)),
+            ),
+            TType::STRING => {
+                let location = args.location();
+                let index = args.take_u32(source)?;
+                let value = strings
+                    .get(index as usize)
+                    .ok_or_else(|| ReadingError::InvalidStringIndex(index).at(location))?;
+                Event::Value(name, Scalar::String(value))
+            }
+            TType::SINT64 => Event::Value(name, Scalar::Sint64(args.take_u64(source)? as i64)),
+            TType::UINT64 => Event::Value(name, Scalar::Uint64(args.take_u64(source)?)),
+            TType::FLOAT64 => Event::Value(
+                name,
+                Scalar::Float64(f64::from_bits(args.take_u64(source)?)),
+            ),
+            TType::EXTEND_TYPE => match args.take_u32(source)? {
+                pd::ET_SHEET_ID => Event::Value(name, Scalar::SheetId(args.take_u32(source)?)),
+                pd::ET_ENTITY_ID => Event::Value(name, Scalar::EntityId(args.take_u64(source)?)),
+                _ => return Err(ReadingError::InvalidFileFormat.at(token_location)),
+            },
+        };
+
+        Ok(Some(event))
+    }
+
+    fn next_token(&mut self) -> Result<Option<Tokens>> {
+        let location = self.tokens.location();
+        match self.tokens.take(&mut self.source)? {
+            Some(bytes) => pd::parse_token(u16::from_le_bytes(bytes), self.strings.len())
+                .map(Some)
+                .map_err(|error| error.at(location)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Table of fixed size entries that is read from the source in chunks
+struct Table {
+    section: Section,
+    next: u64,
+    end: u64,
+    buffer: Vec<u8>,
+    position: usize,
+}
+
+impl Table {
+    fn new(section: Section, start: u64, end: u64) -> Self {
+        Table {
+            section,
+            next: start,
+            end,
+            buffer: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Location of the next entry
+    fn location(&self) -> Location {
+        let offset = self.next - self.buffer.len() as u64 + self.position as u64;
+
+        Location::at(self.section, offset)
+    }
+
+    fn take<const N: usize>(&mut self, source: &mut (impl Read + Seek)) -> Result<Option<[u8; N]>> {
+        if self.position >= self.buffer.len() {
+            if self.next >= self.end {
+                return Ok(None);
+            }
+            let len = (self.end - self.next).min(CHUNK_SIZE);
+            self.buffer.resize(len as usize, 0);
+            source.seek(SeekFrom::Start(self.next))?;
+            source.read_exact(&mut self.buffer)?;
+            self.next += len;
+            self.position = 0;
+        }
+
+        let bytes = self.buffer[self.position..]
+            .first_chunk::<N>()
+            .copied()
+            .ok_or_else(|| ReadingError::Truncated(self.section).at(self.location()))?;
+        self.position += N;
+
+        Ok(Some(bytes))
+    }
+
+    fn take_u32(&mut self, source: &mut (impl Read + Seek)) -> Result<u32> {
+        match self.take(source)? {
+            Some(bytes) => Ok(u32::from_le_bytes(bytes)),
+            None => Err(ReadingError::Truncated(self.section).at(self.location())),
+        }
+    }
+
+    /// 64 bit values are stored as two args, low word first
+    fn take_u64(&mut self, source: &mut (impl Read + Seek)) -> Result<u64> {
+        let low = self.take_u32(source)? as u64;
+        let high = self.take_u32(source)? as u64;
+
+        Ok(low | high << 32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_events() {
+        let mut data: Vec<u8> = Vec::new();
+        for value in [0u32, 51, 4, 2, 3, 11] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        // BEGIN _Files, UINT _FileSize, STRING _FileName, END _Files
+        for token in [0u16, 1 << 3 | 3, 2 << 3 | 5, 1] {
+            data.extend_from_slice(&token.to_le_bytes());
+        }
+        for arg in [10u32, 0] {
+            data.extend_from_slice(&arg.to_le_bytes());
+        }
+        data.extend_from_slice(b"_F\0_FS\0_FN\0");
+
+        let mut reader = Reader::new(Cursor::new(data), Limits::default(), Encoding::Utf8).unwrap();
+
+        assert_eq!(Some(Event::Begin("_F")), reader.next_event().unwrap());
+        assert_eq!(
+            Some(Event::Value("_FS", Scalar::Uint32(10))),
+            reader.next_event().unwrap()
+        );
+        assert_eq!(
+            Some(Event::Value("_FN", Scalar::String("_F"))),
+            reader.next_event().unwrap()
+        );
+        assert_eq!(Some(Event::End("_F")), reader.next_event().unwrap());
+        assert_eq!(None, reader.next_event().unwrap());
+    }
+
+    #[test]
+    fn test_pipe() {
+        struct Pipe;
+        impl Read for Pipe {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Ok(0)
+            }
+        }
+        impl Seek for Pipe {
+            fn seek(&mut self, _: SeekFrom) -> io::Result<u64> {
+                Err(io::ErrorKind::NotSeekable.into())
+            }
+        }
+
+        let error = Reader::new(Pipe, Limits::default(), Encoding::Utf8)
+            .err()
+            .unwrap();
+        assert!(matches!(error, ReadingError::NotSeekable));
+    }
+}
@@ -0,0 +1,62 @@
+//! Strategies generating generic values for the round trip property tests
+
+use crate::pd::{Property, Value};
+use proptest::prelude::*;
+
+/// A string the string table can hold, it ends every string with a null byte
+pub fn text() -> impl Strategy<Value = String> {
+    "[^\\x00]{0,16}"
+}
+
+/// A property name, unlike NeL names they may start with a digit
+pub fn name() -> impl Strategy<Value = String> {
+    "_[A-Za-z0-9]{1,12}"
+}
+
+/// Floats JSON can represent, it writes NaN and the infinities as null
+fn float32() -> impl Strategy<Value = f32> {
+    use prop::num::f32::*;
+    POSITIVE | NEGATIVE | NORMAL | SUBNORMAL | ZERO
+}
+
+fn float64() -> impl Strategy<Value = f64> {
+    use prop::num::f64::*;
+    POSITIVE | NEGATIVE | NORMAL | SUBNORMAL | ZERO
+}
+
+/// A value of any stored type, structs nest up to 4 levels
+pub fn value() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Flag),
+        any::<i32>().prop_map(Value::Sint32),
+        any::<u32>().prop_map(Value::Uint32),
+        float32().prop_map(Value::Float32),
+        text().prop_map(Value::String),
+        any::<i64>().prop_map(Value::Sint64),
+        any::<u64>().prop_map(Value::Uint64),
+        float64().prop_map(Value::Float64),
+        any::<u32>().prop_map(Value::SheetId),
+        any::<u64>().prop_map(Value::EntityId),
+    ];
+    leaf.prop_recursive(4, 64, 8, |inner| {
+        prop::collection::vec(property_of(inner), 0..8).prop_map(Value::Struct)
+    })
+}
+
+pub fn property() -> impl Strategy<Value = Property> {
+    property_of(value())
+}
+
+fn property_of(value: impl Strategy<Value = Value>) -> impl Strategy<Value = Property> {
+    (name(), value).prop_map(|(name, value)| Property { name, value })
+}
+
+/// Properties a struct does not know, reading them back would otherwise take them as fields
+pub fn extra(fields: &'static [&'static str]) -> impl Strategy<Value = Vec<Property>> {
+    prop::collection::vec(
+        property().prop_filter("names a field", move |property| {
+            !fields.contains(&property.name.as_str())
+        }),
+        0..3,
+    )
+}
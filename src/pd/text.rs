@@ -0,0 +1,221 @@
+use crate::error::{Location, ReadingError};
+use crate::pd::{self, FileEncoding, Limit, Property, Value};
+use crate::Result;
+
+/// First line of a text PDR file
+pub const TEXT_SIGNATURE: &str = "PDR text";
+
+/// Writes properties as text: after the signature line one line per property, `name {` opens a
+/// struct that a `}` line closes and values are written like `_FileName STRING "sounds.bnp"`
+pub fn write_text(properties: &[Property]) -> Result<String> {
+    let mut output = format!("{}\n", TEXT_SIGNATURE);
+    write_lines(&mut output, properties, 0)?;
+
+    Ok(output)
+}
+
+fn write_lines(output: &mut String, properties: &[Property], depth: usize) -> Result<()> {
+    for property in properties {
+        if !pd::is_plain_name(&property.name) {
+            return Err(ReadingError::UnwritableName(
+                property.name.clone(),
+                FileEncoding::Text,
+            ));
+        }
+        let indent = "  ".repeat(depth);
+        match property.value {
+            Value::Struct(ref properties) => {
+                output.push_str(&format!("{}{} {{\n", indent, property.name));
+                write_lines(output, properties, depth + 1)?;
+                output.push_str(&format!("{}}}\n", indent));
+            }
+            Value::Flag => output.push_str(&format!("{}{} FLAG\n", indent, property.name)),
+            Value::String(ref value) => {
+                output.push_str(&format!("{}{} STRING \"", indent, property.name));
+                escape(output, value);
+                output.push_str("\"\n");
+            }
+            ref value => output.push_str(&format!(
+                "{}{} {} {}\n",
+                indent,
+                property.name,
+                value.type_name(),
+                value.to_text()
+            )),
+        }
+    }
+
+    Ok(())
+}
+
+/// Escapes quotes, backslashes and control characters so every string stays on its line
+fn escape(output: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '\\' => output.push_str("\\\\"),
+            '"' => output.push_str("\\\""),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => output.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => output.push(c),
+        }
+    }
+}
+
+/// The inverse of [`escape`] for the text between the quotes, `None` on an unknown escape
+fn unescape(text: &str) -> Option<String> {
+    let mut value = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        value.push(match chars.next()? {
+            '\\' => '\\',
+            '"' => '"',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => {
+                let rest = chars.as_str().strip_prefix('{')?;
+                let (hex, after) = rest.split_once('}')?;
+                chars = after.chars();
+                char::from_u32(u32::from_str_radix(hex, 16).ok()?)?
+            }
+            _ => return None,
+        });
+    }
+
+    Some(value)
+}
+
+/// Reads the properties of a file written by [`write_text`], indentation and empty lines are
+/// ignored
+pub fn read_text(text: &str, limits: pd::Limits) -> Result<Vec<Property>> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|&(_, line)| !line.is_empty());
+    let error = |line: usize, message: String| ReadingError::InvalidSyntax {
+        encoding: FileEncoding::Text,
+        line,
+        message,
+    };
+    match lines.next() {
+        Some((_, TEXT_SIGNATURE)) => {}
+        found => {
+            let line = found.map_or(1, |(line, _)| line);
+            return Err(error(line, format!("expected {:?}", TEXT_SIGNATURE)));
+        }
+    }
+
+    // the properties of every open struct, the root first
+    let mut open: Vec<(String, Vec<Property>)> = vec![(String::new(), Vec::new())];
+    let mut tokens = 0u64;
+    let mut last = 1;
+    for (line, content) in lines {
+        last = line;
+        let location = Location {
+            offset: Some((content.as_ptr() as usize - text.as_ptr() as usize) as u64),
+            ..Location::default()
+        };
+        let property = if content == "}" {
+            if open.len() == 1 {
+                return Err(error(line, "} closes no struct".to_string()));
+            }
+            let (name, properties) = open.pop().unwrap();
+
+            Property {
+                name,
+                value: Value::Struct(properties),
+            }
+        } else {
+            let (name, rest) = content.split_once(' ').unwrap_or((content, ""));
+            if !pd::is_plain_name(name) {
+                return Err(error(line, format!("expected a name, found {:?}", name)));
+            }
+            limits.check(Limit::StringLength, name.len() as u64, location.clone())?;
+            let rest = rest.trim_start();
+            if rest == "{" {
+                limits.check(Limit::Depth, open.len() as u64, location)?;
+                open.push((name.to_string(), Vec::new()));
+                continue;
+            }
+
+            let (type_name, text) = rest.split_once(' ').unwrap_or((rest, ""));
+            let text = text.trim_start();
+            let value = if type_name == "STRING" {
+                text.strip_prefix('"')
+                    .and_then(|text| text.strip_suffix('"'))
+                    .and_then(unescape)
+                    .and_then(|text| Value::from_text(type_name, &text))
+            } else {
+                Value::from_text(type_name, text)
+            };
+            let Some(value) = value else {
+                return Err(error(
+                    line,
+                    format!("{:?} is not a {} value", text, type_name),
+                ));
+            };
+            if let Value::String(ref value) = value {
+                limits.check(Limit::StringLength, value.len() as u64, location.clone())?;
+            }
+
+            Property {
+                name: name.to_string(),
+                value,
+            }
+        };
+
+        tokens += 1;
+        limits.check(Limit::Tokens, tokens, location)?;
+        open.last_mut().unwrap().1.push(property);
+    }
+
+    if open.len() > 1 {
+        let (name, _) = open.pop().unwrap();
+        return Err(error(last, format!("struct {} is not closed", name)));
+    }
+
+    Ok(open.pop().unwrap().1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_text() {
+        let properties = vec![Property {
+            name: "_Files".to_string(),
+            value: Value::Struct(vec![
+                Property {
+                    name: "_FileName".to_string(),
+                    value: Value::String("say \"hi\"\n".to_string()),
+                },
+                Property {
+                    name: "_FileSize".to_string(),
+                    value: Value::Uint32(2048),
+                },
+            ]),
+        }];
+
+        let text = write_text(&properties).unwrap();
+        assert_eq!(
+            "PDR text\n_Files {\n  _FileName STRING \"say \\\"hi\\\"\\n\"\n  \
+             _FileSize UINT32 2048\n}\n",
+            text
+        );
+        assert_eq!(properties, read_text(&text, pd::Limits::default()).unwrap());
+
+        let error = read_text("PDR text\n_Files {\n", pd::Limits::default()).unwrap_err();
+        assert_eq!(
+            "Invalid text PDR on line 2: struct _Files is not closed",
+            error.to_string()
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+
+/// A property decoded from its token type alone, used for fields the patch structs do not know.
+///
+/// The variants keep the stored type so the value can be written back as it was read.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Value {
+    Flag,
+    Sint32(i32),
+    Uint32(u32),
+    Float32(f32),
+    String(String),
+    Sint64(i64),
+    Uint64(u64),
+    Float64(f64),
+    SheetId(u32),
+    EntityId(u64),
+    Struct(Vec<Property>),
+}
+
+/// A named value, structs may repeat a name so properties are kept in a list in file order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Property {
+    pub name: String,
+    pub value: Value,
+}
+
+impl Value {
+    /// Name of the stored type in the XML and text encodings
+    pub fn type_name(&self) -> &'static str {
+        match *self {
+            Value::Flag => "FLAG",
+            Value::Sint32(..) => "SINT32",
+            Value::Uint32(..) => "UINT32",
+            Value::Float32(..) => "FLOAT32",
+            Value::String(..) => "STRING",
+            Value::Sint64(..) => "SINT64",
+            Value::Uint64(..) => "UINT64",
+            Value::Float64(..) => "FLOAT64",
+            Value::SheetId(..) => "SHEET_ID",
+            Value::EntityId(..) => "ENTITY_ID",
+            Value::Struct(..) => "STRUCT",
+        }
+    }
+
+    /// The value as the XML and text encodings store it, empty for flags and structs. Floats are
+    /// written with the fewest digits that read back to the same value.
+    pub fn to_text(&self) -> String {
+        match *self {
+            Value::Flag | Value::Struct(..) => String::new(),
+            Value::Sint32(value) => value.to_string(),
+            Value::Uint32(value) | Value::SheetId(value) => value.to_string(),
+            Value::Float32(value) => value.to_string(),
+            Value::String(ref value) => value.clone(),
+            Value::Sint64(value) => value.to_string(),
+            Value::Uint64(value) | Value::EntityId(value) => value.to_string(),
+            Value::Float64(value) => value.to_string(),
+        }
+    }
+
+    /// The inverse of [`Value::to_text`] for every type but structs, `None` for an unknown type or
+    /// a text that is not a value of it. Strings can not hold a null byte, the string table ends
+    /// them with it.
+    pub fn from_text(type_name: &str, text: &str) -> Option<Value> {
+        Some(match type_name {
+            "FLAG" if text.is_empty() => Value::Flag,
+            "SINT32" => Value::Sint32(text.parse().ok()?),
+            "UINT32" => Value::Uint32(text.parse().ok()?),
+            "FLOAT32" => Value::Float32(text.parse().ok()?),
+            "STRING" if !text.contains('\0') => Value::String(text.to_string()),
+            "SINT64" => Value::Sint64(text.parse().ok()?),
+            "UINT64" => Value::Uint64(text.parse().ok()?),
+            "FLOAT64" => Value::Float64(text.parse().ok()?),
+            "SHEET_ID" => Value::SheetId(text.parse().ok()?),
+            "ENTITY_ID" => Value::EntityId(text.parse().ok()?),
+            _ => return None,
+        })
+    }
+}
+
+/// Whether the XML and text encodings can store a property name: a letter or underscore followed
+/// by letters, digits, underscores, dots or dashes
+pub(crate) fn is_plain_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pd;
+    use proptest::prelude::*;
+
+    fn properties() -> impl Strategy<Value = Vec<Property>> {
+        prop::collection::vec(pd::strategy::property(), 0..8)
+    }
+
+    proptest! {
+        #[test]
+        fn test_binary_round_trip(properties in properties()) {
+            let mut writer = pd::Writer::default();
+            writer.write_extra(&properties);
+            let data = writer.into_bytes().unwrap();
+            let limits = pd::Limits::default();
+            let mut pdr = pd::read_index_file(&data, false, limits, pd::Encoding::Utf8).unwrap();
+
+            prop_assert_eq!(properties, pdr.read_properties().unwrap());
+        }
+
+        #[test]
+        fn test_xml_round_trip(properties in properties()) {
+            let xml = pd::write_xml(&properties).unwrap();
+
+            prop_assert_eq!(properties, pd::read_xml(&xml, pd::Limits::default()).unwrap());
+        }
+
+        #[test]
+        fn test_text_round_trip(properties in properties()) {
+            let text = pd::write_text(&properties).unwrap();
+
+            prop_assert_eq!(properties, pd::read_text(&text, pd::Limits::default()).unwrap());
+        }
+
+        #[test]
+        fn test_json_round_trip(properties in properties()) {
+            let json = serde_json::to_string(&properties).unwrap();
+            let read: Vec<Property> = serde_json::from_str(&json).unwrap();
+
+            prop_assert_eq!(properties, read);
+        }
+    }
+}
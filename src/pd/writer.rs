@@ -0,0 +1,409 @@
+use crate::error::{Location, ReadingError, Section};
+use crate::pd::{
+    self, Arg, Encoding, Limit, Limits, OriginalStrings, Property, StringId, Token, Value,
+};
+use crate::Result;
+use std::collections::HashMap;
+
+/// Token names are stored above the three type bits of a token
+const MAX_NAMES: usize = 1 << 13;
+
+pub trait Writable {
+    /// Writes the properties of the struct, the enclosing begin and end tokens are written by the caller
+    fn write(&self, pdr: &mut Writer);
+}
+
+pub trait WritableProperty {
+    fn write(&self, pdr: &mut Writer, name: &str);
+}
+
+impl<T: Writable> WritableProperty for T {
+    fn write(&self, pdr: &mut Writer, name: &str) {
+        pdr.begin(name);
+        Writable::write(self, pdr);
+        pdr.end(name);
+    }
+}
+
+impl<T: Writable> WritableProperty for Vec<T> {
+    fn write(&self, pdr: &mut Writer, name: &str) {
+        for item in self {
+            pdr.write(name, item);
+        }
+    }
+}
+
+impl WritableProperty for u32 {
+    fn write(&self, pdr: &mut Writer, name: &str) {
+        pdr.token(name, pd::Tokens::UINT_TOKEN, &[*self]);
+    }
+}
+
+impl WritableProperty for i32 {
+    fn write(&self, pdr: &mut Writer, name: &str) {
+        pdr.token(name, pd::Tokens::SINT_TOKEN, &[*self as Arg]);
+    }
+}
+
+/// Booleans are stored as signed integers like NeL does
+impl WritableProperty for bool {
+    fn write(&self, pdr: &mut Writer, name: &str) {
+        (*self as i32).write(pdr, name);
+    }
+}
+
+impl WritableProperty for String {
+    fn write(&self, pdr: &mut Writer, name: &str) {
+        pdr.string_token(name, self);
+    }
+}
+
+impl<T: WritableProperty> WritableProperty for Option<T> {
+    fn write(&self, pdr: &mut Writer, name: &str) {
+        if let Some(value) = self {
+            value.write(pdr, name);
+        }
+    }
+}
+
+/// Builds the tables of a version 0 PDR, the counterpart of [`pd::PersistentDataRecord`]
+#[derive(Debug, Default)]
+pub struct Writer {
+    tokens: Vec<Token>,
+    args: Vec<Arg>,
+    /// Encoded strings in table order
+    strings: Vec<Vec<u8>>,
+    string_ids: HashMap<String, usize>,
+    encoding: Encoding,
+    originals: OriginalStrings,
+    /// The first token name that does not fit into a token
+    overflow: Option<String>,
+    /// The first string the encoding can not represent
+    unencodable: Option<String>,
+}
+
+impl Writer {
+    /// A writer that encodes strings with `encoding`, except those read from a file whose
+    /// original bytes are re-emitted unchanged
+    pub fn with_encoding(encoding: Encoding, originals: OriginalStrings) -> Self {
+        Writer {
+            encoding,
+            originals,
+            ..Writer::default()
+        }
+    }
+
+    /// Index of a string in the string table, adding it if needed
+    fn string(&mut self, value: &str) -> usize {
+        if let Some(&index) = self.string_ids.get(value) {
+            return index;
+        }
+        let bytes = match self.originals.get(value) {
+            Some(bytes) => bytes.to_vec(),
+            None => match self.encoding.encode(value) {
+                Some(bytes) => bytes.into_owned(),
+                None => {
+                    self.unencodable.get_or_insert_with(|| value.to_string());
+                    value.as_bytes().to_vec()
+                }
+            },
+        };
+        let index = self.strings.len();
+        self.strings.push(bytes);
+        self.string_ids.insert(value.to_string(), index);
+
+        index
+    }
+
+    fn name(&mut self, name: &str) -> StringId {
+        let index = self.string(name);
+        if index >= MAX_NAMES && self.overflow.is_none() {
+            self.overflow = Some(name.to_string());
+        }
+
+        index as StringId
+    }
+
+    fn token(&mut self, name: &str, token: fn(StringId) -> pd::Tokens, args: &[Arg]) {
+        let id = self.name(name);
+        self.tokens.push(pack_token(token(id)));
+        self.args.extend_from_slice(args);
+    }
+
+    /// Names are added before values, keeping them in the part of the string table tokens can refer to
+    fn string_token(&mut self, name: &str, value: &str) {
+        let id = self.name(name);
+        let value = self.string(value) as Arg;
+        self.tokens.push(pack_token(pd::Tokens::STRING_TOKEN(id)));
+        self.args.push(value);
+    }
+
+    /// Writes an EXTEND token followed by `token`, which then takes 64-bit or extended args
+    fn extended(&mut self, name: &str, token: fn(StringId) -> pd::Tokens, args: &[Arg]) {
+        self.token(name, pd::Tokens::EXTEND_TOKEN, &[]);
+        self.token(name, token, args);
+    }
+
+    pub fn begin(&mut self, name: &str) {
+        self.token(name, pd::Tokens::BEGIN_TOKEN, &[]);
+    }
+
+    pub fn end(&mut self, name: &str) {
+        self.token(name, pd::Tokens::END_TOKEN, &[]);
+    }
+
+    pub fn write<T: WritableProperty>(&mut self, name: &str, value: &T) {
+        value.write(self, name);
+    }
+
+    /// Writes every item as a property of the same name, the counterpart of `read_prop_vec`
+    pub fn write_prop_vec<T: WritableProperty>(&mut self, name: &str, values: &[T]) {
+        for value in values {
+            self.write(name, value);
+        }
+    }
+
+    /// Writes properties with the type they were read with
+    pub fn write_extra(&mut self, properties: &[Property]) {
+        for property in properties {
+            self.write_property(property);
+        }
+    }
+
+    pub fn write_property(&mut self, property: &Property) {
+        let name = property.name.as_str();
+        match property.value {
+            Value::Flag => self.token(name, pd::Tokens::FLAG_TOKEN, &[]),
+            Value::Sint32(value) => self.token(name, pd::Tokens::SINT_TOKEN, &[value as Arg]),
+            Value::Uint32(value) => self.token(name, pd::Tokens::UINT_TOKEN, &[value]),
+            Value::Float32(value) => self.token(name, pd::Tokens::FLOAT_TOKEN, &[value.to_bits()]),
+            Value::String(ref value) => self.string_token(name, value),
+            Value::Sint64(value) => {
+                self.extended(name, pd::Tokens::SINT_TOKEN, &split64(value as u64))
+            }
+            Value::Uint64(value) => self.extended(name, pd::Tokens::UINT_TOKEN, &split64(value)),
+            Value::Float64(value) => {
+                self.extended(name, pd::Tokens::FLOAT_TOKEN, &split64(value.to_bits()))
+            }
+            Value::SheetId(value) => {
+                self.extended(name, pd::Tokens::STRING_TOKEN, &[pd::ET_SHEET_ID, value])
+            }
+            Value::EntityId(value) => {
+                let [low, high] = split64(value);
+                self.extended(
+                    name,
+                    pd::Tokens::STRING_TOKEN,
+                    &[pd::ET_ENTITY_ID, low, high],
+                );
+            }
+            Value::Struct(ref properties) => {
+                self.begin(name);
+                self.write_extra(properties);
+                self.end(name);
+            }
+        }
+    }
+
+    /// Fails on the first name or string that could not be stored
+    fn check(&mut self) -> Result<()> {
+        if let Some(name) = self.overflow.take() {
+            return Err(ReadingError::NameOutOfRange(name));
+        }
+        if let Some(value) = self.unencodable.take() {
+            return Err(ReadingError::UnencodableString(value, self.encoding));
+        }
+
+        Ok(())
+    }
+
+    /// The record a reader returns for the encoded tables, without encoding them
+    pub fn into_record(mut self) -> Result<pd::PersistentDataRecord<'static>> {
+        self.check()?;
+        let tokens = self.tokens.iter().map(|&token| pd::unchecked_token(token));
+        let mut strings = vec![String::new(); self.strings.len()];
+        for (text, index) in self.string_ids {
+            strings[index] = text;
+        }
+
+        Ok(pd::PersistentDataRecord::new(
+            tokens.collect(),
+            self.args,
+            strings,
+        ))
+    }
+
+    /// Encodes the header and the tables
+    pub fn into_bytes(mut self) -> Result<Vec<u8>> {
+        self.check()?;
+
+        let strings_size: u64 = self
+            .strings
+            .iter()
+            .map(|value| value.len() as u64 + 1)
+            .sum();
+        // every size and count of the header has to fit into 32 bits
+        let location = Location::at(Section::Header, 4);
+        let tables_size = (self.tokens.len() * size_of::<Token>()) as u64
+            + (self.args.len() * size_of::<Arg>()) as u64
+            + strings_size;
+        Limits::DEFAULT.check(Limit::FileSize, tables_size, location.clone())?;
+        let header = pd::Header::for_tables(
+            pd::WRITTEN_VERSION,
+            self.tokens.len() as u32,
+            self.args.len() as u32,
+            self.strings.len() as u32,
+            strings_size as u32,
+        );
+        let total_size = header.expected_size();
+        Limits::DEFAULT.check(Limit::FileSize, total_size, location)?;
+
+        let mut data = Vec::with_capacity(total_size as usize);
+        header.write(&mut data);
+        data.resize(header.layout().header_size as usize, 0);
+        for &section in &header.layout().sections {
+            match section {
+                Section::TokenTable => {
+                    for &token in &self.tokens {
+                        data.extend_from_slice(&token.to_le_bytes());
+                    }
+                }
+                Section::ArgTable => {
+                    for &arg in &self.args {
+                        data.extend_from_slice(&arg.to_le_bytes());
+                    }
+                }
+                Section::StringTable => {
+                    for value in &self.strings {
+                        data.extend_from_slice(value);
+                        data.push(0);
+                    }
+                }
+                Section::Header => unreachable!("the header is not one of the tables"),
+            }
+        }
+
+        Ok(data)
+    }
+}
+
+/// The inverse of [`pd::unchecked_token`]
+fn pack_token(token: pd::Tokens) -> Token {
+    let token_type = match token {
+        pd::Tokens::BEGIN_TOKEN(_) => 0,
+        pd::Tokens::END_TOKEN(_) => 1,
+        pd::Tokens::SINT_TOKEN(_) => 2,
+        pd::Tokens::UINT_TOKEN(_) => 3,
+        pd::Tokens::FLOAT_TOKEN(_) => 4,
+        pd::Tokens::STRING_TOKEN(_) => 5,
+        pd::Tokens::FLAG_TOKEN(_) => 6,
+        pd::Tokens::EXTEND_TOKEN(_) => 7,
+    };
+
+    token.value() << 3 | token_type
+}
+
+/// 64-bit values are stored in two args, the low half first
+fn split64(value: u64) -> [Arg; 2] {
+    [value as Arg, (value >> 32) as Arg]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch;
+    use pd::PersistentDataRecord;
+    use pdr_derive::{PdrRead, PdrWrite};
+    use std::io::Cursor;
+
+    /// Decodes what the writer produced without the checks of the cli reader
+    fn record(data: &[u8]) -> PersistentDataRecord<'_> {
+        let header = pd::Header::read(&mut Cursor::new(data)).unwrap();
+        let section = |section| {
+            let start = header.section_offset(section) as usize;
+            &data[start..start + header.section_size(section) as usize]
+        };
+        let tokens = section(Section::TokenTable)
+            .chunks_exact(2)
+            .map(|bytes| pd::unchecked_token(Token::from_le_bytes([bytes[0], bytes[1]])))
+            .collect();
+        let args = section(Section::ArgTable)
+            .chunks_exact(4)
+            .map(|bytes| Arg::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        let strings = section(Section::StringTable)
+            .split(|&byte| byte == 0)
+            .take(header.string_count as usize)
+            .map(|bytes| std::str::from_utf8(bytes).unwrap())
+            .collect();
+
+        PersistentDataRecord::new(tokens, args, strings)
+    }
+
+    #[derive(Debug, PartialEq, PdrRead, PdrWrite)]
+    struct Mirror {
+        #[pdr(rename = "_Url")]
+        url: String,
+        #[pdr(default = "default_weight")]
+        _Weight: u32,
+        #[pdr(default)]
+        _Backup: bool,
+    }
+
+    fn default_weight() -> u32 {
+        10
+    }
+
+    #[test]
+    fn test_derive_attributes() {
+        let mut writer = Writer::default();
+        writer.begin("_Mirror");
+        writer.write("_Url", &"https://cdn.ryzom.com".to_string());
+        writer.end("_Mirror");
+        let data = writer.into_bytes().unwrap();
+
+        let mirror: Mirror = record(&data).read("_Mirror").unwrap();
+
+        assert_eq!(
+            Mirror {
+                url: "https://cdn.ryzom.com".to_string(),
+                _Weight: 10,
+                _Backup: false,
+            },
+            mirror
+        );
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let file = patch::CBNPFile {
+            _FileName: "fauna_maps.bnp".to_string(),
+            _Versions: vec![patch::CBNPFileVersion {
+                _VersionNumber: 1021,
+                _FileSize: patch::FileSize(2048),
+                _7ZFileSize: patch::FileSize(1024),
+                _FileTime: patch::FileTime(1700000000),
+                _PatchSize: patch::FileSize(512),
+                _HashKey: vec![1, 2, 3, 4, 5],
+                extra: vec![Property {
+                    name: "_Mirror".to_string(),
+                    value: Value::Struct(vec![Property {
+                        name: "_Id".to_string(),
+                        value: Value::EntityId(u64::MAX - 1),
+                    }]),
+                }],
+            }],
+            extra: Vec::new(),
+        };
+        let mut writer = Writer::default();
+        writer.write("_Files", &file);
+        let data = writer.into_bytes().unwrap();
+
+        let read: patch::CBNPFile = record(&data).read("_Files").unwrap();
+
+        assert_eq!(file._Versions, read._Versions);
+        assert_eq!(
+            data.len(),
+            u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize
+        );
+    }
+}
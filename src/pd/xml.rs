@@ -0,0 +1,307 @@
+use crate::error::{Location, ReadingError};
+use crate::pd::{self, FileEncoding, Limit, Property, Value};
+use crate::Result;
+
+/// Writes properties as XML: a `<xml>` root with one element per property, structs hold their
+/// properties and values are empty elements like `<_FileName type="STRING" value="sounds.bnp"/>`
+pub fn write_xml(properties: &[Property]) -> Result<String> {
+    let mut output = String::from("<xml>\n");
+    write_elements(&mut output, properties, 1)?;
+    output.push_str("</xml>\n");
+
+    Ok(output)
+}
+
+fn write_elements(output: &mut String, properties: &[Property], depth: usize) -> Result<()> {
+    for property in properties {
+        if !pd::is_plain_name(&property.name) {
+            return Err(ReadingError::UnwritableName(
+                property.name.clone(),
+                FileEncoding::Xml,
+            ));
+        }
+        let indent = "  ".repeat(depth);
+        match property.value {
+            Value::Struct(ref properties) => {
+                output.push_str(&format!("{}<{}>\n", indent, property.name));
+                write_elements(output, properties, depth + 1)?;
+                output.push_str(&format!("{}</{}>\n", indent, property.name));
+            }
+            Value::Flag => {
+                output.push_str(&format!("{}<{} type=\"FLAG\"/>\n", indent, property.name));
+            }
+            ref value => {
+                output.push_str(&format!(
+                    "{}<{} type=\"{}\" value=\"",
+                    indent,
+                    property.name,
+                    value.type_name()
+                ));
+                escape(output, &value.to_text());
+                output.push_str("\"/>\n");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Escapes the markup characters and every control character, attribute values would lose their
+/// line breaks and tabs otherwise
+fn escape(output: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            c if c.is_control() => output.push_str(&format!("&#x{:X};", c as u32)),
+            c => output.push(c),
+        }
+    }
+}
+
+/// Reads the properties of a file written by [`write_xml`]. Only that subset of XML is read: an
+/// optional `<?xml ...?>` declaration, no comments, no text between the elements.
+pub fn read_xml(text: &str, limits: pd::Limits) -> Result<Vec<Property>> {
+    let mut parser = Parser { text, position: 0 };
+    parser.skip_space();
+    if parser.eat("<?xml") {
+        let Some(end) = parser.rest().find("?>") else {
+            return Err(parser.error("the XML declaration is not closed"));
+        };
+        parser.position += end + 2;
+        parser.skip_space();
+    }
+    if !parser.eat("<xml>") {
+        return Err(parser.error("expected <xml>"));
+    }
+
+    // the properties of every open struct, the root first
+    let mut open: Vec<(String, Vec<Property>)> = vec![(String::new(), Vec::new())];
+    let mut tokens = 0u64;
+    loop {
+        parser.skip_space();
+        let start = parser.position;
+        let property = if parser.eat("</") {
+            let name = parser.name()?;
+            parser.skip_space();
+            if !parser.eat(">") {
+                return Err(parser.error("expected >"));
+            }
+            if open.len() == 1 {
+                if name == "xml" {
+                    break;
+                }
+                return Err(parser.error(format!("</{}> closes no element", name)));
+            }
+            let (expected, properties) = open.pop().unwrap();
+            if name != expected {
+                return Err(parser.error(format!("expected </{}>", expected)));
+            }
+
+            Property {
+                name,
+                value: Value::Struct(properties),
+            }
+        } else if parser.eat("<") {
+            let name = parser.name()?;
+            parser.check_length(limits, &name, start)?;
+            let (attributes, closed) = parser.attributes()?;
+            if !closed {
+                if !attributes.is_empty() {
+                    return Err(parser.error(format!("struct {} has attributes", name)));
+                }
+                limits.check(Limit::Depth, open.len() as u64, parser.location(start))?;
+                open.push((name, Vec::new()));
+                continue;
+            }
+
+            let attribute = |key: &str| {
+                attributes
+                    .iter()
+                    .find(|(name, _)| name == key)
+                    .map(|(_, value)| value.as_str())
+            };
+            let Some(type_name) = attribute("type") else {
+                return Err(parser.error(format!("{} has no type", name)));
+            };
+            let text = attribute("value").unwrap_or_default();
+            parser.check_length(limits, text, start)?;
+            let Some(value) = Value::from_text(type_name, text) else {
+                return Err(parser.error(format!("{:?} is not a {} value", text, type_name)));
+            };
+
+            Property { name, value }
+        } else if parser.rest().is_empty() {
+            return Err(parser.error("the file ends before </xml>"));
+        } else {
+            return Err(parser.error("expected an element"));
+        };
+
+        tokens += 1;
+        limits.check(Limit::Tokens, tokens, parser.location(start))?;
+        open.last_mut().unwrap().1.push(property);
+    }
+
+    parser.skip_space();
+    if !parser.rest().is_empty() {
+        return Err(parser.error("expected the end of the file after </xml>"));
+    }
+
+    Ok(open.pop().unwrap().1)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn eat(&mut self, expected: &str) -> bool {
+        let found = self.rest().starts_with(expected);
+        if found {
+            self.position += expected.len();
+        }
+
+        found
+    }
+
+    fn skip_space(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '='))
+            .unwrap_or(rest.len());
+        let name = &rest[..end];
+        if !pd::is_plain_name(name) {
+            return Err(self.error(format!("expected a name, found {:?}", name)));
+        }
+        self.position += end;
+
+        Ok(name.to_string())
+    }
+
+    /// The attributes of an element and whether it closes itself with `/>`
+    fn attributes(&mut self) -> Result<(Vec<(String, String)>, bool)> {
+        let mut attributes = Vec::new();
+        loop {
+            self.skip_space();
+            if self.eat("/>") {
+                return Ok((attributes, true));
+            }
+            if self.eat(">") {
+                return Ok((attributes, false));
+            }
+            let key = self.name()?;
+            self.skip_space();
+            if !self.eat("=") {
+                return Err(self.error("expected ="));
+            }
+            self.skip_space();
+            let value = self.quoted()?;
+            attributes.push((key, value));
+        }
+    }
+
+    fn quoted(&mut self) -> Result<String> {
+        if !self.eat("\"") {
+            return Err(self.error("expected a quoted value"));
+        }
+        let Some(end) = self.rest().find('"') else {
+            return Err(self.error("the value is not closed"));
+        };
+        let raw = &self.rest()[..end];
+        let mut value = String::with_capacity(raw.len());
+        let mut parts = raw.split('&');
+        value.push_str(parts.next().unwrap_or_default());
+        for part in parts {
+            let Some((entity, text)) = part.split_once(';') else {
+                return Err(self.error("an entity is not closed"));
+            };
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#').and_then(|code| code.parse().ok()),
+                }
+                .and_then(char::from_u32),
+            };
+            let Some(c) = c else {
+                return Err(self.error(format!("unknown entity &{};", entity)));
+            };
+            value.push(c);
+            value.push_str(text);
+        }
+        self.position += end + 1;
+
+        Ok(value)
+    }
+
+    fn check_length(&self, limits: pd::Limits, text: &str, start: usize) -> Result<()> {
+        limits.check(Limit::StringLength, text.len() as u64, self.location(start))
+    }
+
+    fn location(&self, offset: usize) -> Location {
+        Location {
+            offset: Some(offset as u64),
+            ..Location::default()
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> ReadingError {
+        ReadingError::InvalidSyntax {
+            encoding: FileEncoding::Xml,
+            line: self.text[..self.position].matches('\n').count() + 1,
+            message: message.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_xml() {
+        let xml = "<?xml version=\"1.0\"?>\n<xml>\n  <_Files>\n    \
+                   <_FileName type=\"STRING\" value=\"a &amp; b&#xA;\"/>\n    \
+                   <_Hidden type=\"FLAG\"/>\n  </_Files>\n</xml>\n";
+        let properties = read_xml(xml, pd::Limits::default()).unwrap();
+
+        assert_eq!(
+            vec![Property {
+                name: "_Files".to_string(),
+                value: Value::Struct(vec![
+                    Property {
+                        name: "_FileName".to_string(),
+                        value: Value::String("a & b\n".to_string()),
+                    },
+                    Property {
+                        name: "_Hidden".to_string(),
+                        value: Value::Flag,
+                    },
+                ]),
+            }],
+            properties
+        );
+
+        let error = read_xml("<xml>\n<_Files>\n</_File>\n</xml>", pd::Limits::default());
+        assert_eq!(
+            "Invalid XML PDR on line 3: expected </_Files>",
+            error.unwrap_err().to_string()
+        );
+    }
+}
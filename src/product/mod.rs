@@ -0,0 +1,455 @@
+use crate::patch::{self, FileSize, FileTime};
+use crate::pd;
+use serde::{Serialize, Serializer};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// Position of a file in its product description
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId(usize);
+
+impl FileId {
+    /// Position of the file in [`ProductDescription::files`]
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Inconsistencies that keep a raw product description from being used as [`ProductDescription`]
+#[derive(Debug, PartialEq)]
+pub enum ValidationError {
+    DuplicateFile(String),
+    DuplicateCategory(String),
+    UnknownFile { category: String, file: String },
+    UnorderedVersions(String),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValidationError::DuplicateFile(ref name) => write!(f, "file {} is listed twice", name),
+            ValidationError::DuplicateCategory(ref name) => {
+                write!(f, "category {} is listed twice", name)
+            }
+            ValidationError::UnknownFile {
+                ref category,
+                ref file,
+            } => write!(f, "category {} lists the unknown file {}", category, file),
+            ValidationError::UnorderedVersions(ref name) => {
+                write!(
+                    f,
+                    "the versions of file {} are not in ascending order",
+                    name
+                )
+            }
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileVersion {
+    #[serde(rename = "_VersionNumber")]
+    pub number: u32,
+    #[serde(rename = "_FileSize")]
+    pub size: FileSize,
+    #[serde(rename = "_7ZFileSize")]
+    pub packed_size: FileSize,
+    #[serde(rename = "_FileTime")]
+    pub time: FileTime,
+    #[serde(rename = "_PatchSize")]
+    pub patch_size: FileSize,
+    #[serde(rename = "_HashKey")]
+    pub hash_key: Vec<u32>,
+    /// Properties this tool does not know, kept to be written back
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra: Vec<pd::Property>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileEntry {
+    #[serde(rename = "_FileName")]
+    pub name: String,
+    /// Ordered by ascending version number
+    #[serde(rename = "_Versions")]
+    pub versions: Vec<FileVersion>,
+    /// Properties this tool does not know, kept to be written back
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra: Vec<pd::Property>,
+}
+
+impl FileEntry {
+    pub fn latest(&self) -> Option<&FileVersion> {
+        self.versions.last()
+    }
+}
+
+/// A category with NeL's defaults applied, empty strings are `None`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Category {
+    pub name: String,
+    pub optional: bool,
+    pub unpack_to: Option<String>,
+    pub incremental: bool,
+    pub required: Option<String>,
+    pub hidden: bool,
+    files: Vec<FileId>,
+    /// Properties this tool does not know, kept to be written back
+    pub extra: Vec<pd::Property>,
+}
+
+/// Validated product description with resolved category members and lookups by name
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProductDescription {
+    files: Vec<FileEntry>,
+    categories: Vec<Category>,
+    files_by_name: HashMap<String, FileId>,
+    categories_by_name: HashMap<String, usize>,
+    /// Indexes into `categories` per file
+    memberships: Vec<Vec<usize>>,
+}
+
+impl ProductDescription {
+    pub fn files(&self) -> &[FileEntry] {
+        &self.files
+    }
+
+    pub fn categories(&self) -> &[Category] {
+        &self.categories
+    }
+
+    pub fn file_id(&self, name: &str) -> Option<FileId> {
+        self.files_by_name.get(name).copied()
+    }
+
+    pub fn file(&self, name: &str) -> Option<&FileEntry> {
+        self.file_id(name).map(|id| self.entry(id))
+    }
+
+    pub fn entry(&self, id: FileId) -> &FileEntry {
+        &self.files[id.0]
+    }
+
+    pub fn category(&self, name: &str) -> Option<&Category> {
+        self.categories_by_name
+            .get(name)
+            .map(|&index| &self.categories[index])
+    }
+
+    pub fn category_files<'a>(
+        &'a self,
+        category: &'a Category,
+    ) -> impl Iterator<Item = &'a FileEntry> + 'a {
+        category.files.iter().map(|&id| self.entry(id))
+    }
+
+    pub fn file_categories(&self, id: FileId) -> impl Iterator<Item = &Category> + '_ {
+        self.memberships[id.0]
+            .iter()
+            .map(|&index| &self.categories[index])
+    }
+
+    /// Pairs every file with its id, in the order of the index
+    pub fn entries(&self) -> impl Iterator<Item = (FileId, &FileEntry)> + '_ {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(index, file)| (FileId(index), file))
+    }
+}
+
+impl TryFrom<patch::CProductDescriptionForClient> for ProductDescription {
+    type Error = ValidationError;
+
+    fn try_from(raw: patch::CProductDescriptionForClient) -> Result<Self, Self::Error> {
+        ProductDescription::build(raw, None)
+    }
+}
+
+impl ProductDescription {
+    /// Fixes what `try_from` rejects and returns what was fixed: later duplicates of files and
+    /// categories are dropped, versions are sorted and unknown category members are removed
+    pub fn repaired(raw: patch::CProductDescriptionForClient) -> (Self, Vec<ValidationError>) {
+        let mut repairs = Vec::new();
+        let product =
+            ProductDescription::build(raw, Some(&mut repairs)).expect("repairing never fails");
+
+        (product, repairs)
+    }
+
+    /// Fails on the first inconsistency unless `repairs` collects them
+    fn build(
+        raw: patch::CProductDescriptionForClient,
+        mut repairs: Option<&mut Vec<ValidationError>>,
+    ) -> Result<Self, ValidationError> {
+        let mut report = |error: ValidationError| match repairs.as_deref_mut() {
+            Some(repairs) => {
+                repairs.push(error);
+                Ok(())
+            }
+            None => Err(error),
+        };
+
+        let mut files = Vec::with_capacity(raw._Files._Files.len());
+        let mut files_by_name = HashMap::with_capacity(raw._Files._Files.len());
+        for mut file in raw._Files._Files {
+            if files_by_name.contains_key(&file._FileName) {
+                report(ValidationError::DuplicateFile(file._FileName))?;
+                continue;
+            }
+            if file
+                ._Versions
+                .windows(2)
+                .any(|pair| pair[0]._VersionNumber >= pair[1]._VersionNumber)
+            {
+                report(ValidationError::UnorderedVersions(file._FileName.clone()))?;
+                file._Versions.sort_by_key(|version| version._VersionNumber);
+            }
+
+            files_by_name.insert(file._FileName.clone(), FileId(files.len()));
+            files.push(FileEntry {
+                name: file._FileName,
+                versions: file._Versions.into_iter().map(FileVersion::from).collect(),
+                extra: file.extra,
+            });
+        }
+
+        let mut categories = Vec::with_capacity(raw._Categories._Category.len());
+        let mut categories_by_name = HashMap::with_capacity(raw._Categories._Category.len());
+        let mut memberships = vec![Vec::new(); files.len()];
+        for category in raw._Categories._Category {
+            let index = categories.len();
+            if categories_by_name.contains_key(&category._Name) {
+                report(ValidationError::DuplicateCategory(category._Name))?;
+                continue;
+            }
+
+            let mut members = Vec::with_capacity(category._Files.len());
+            for file in &category._Files {
+                let Some(&id) = files_by_name.get(file) else {
+                    report(ValidationError::UnknownFile {
+                        category: category._Name.clone(),
+                        file: file.clone(),
+                    })?;
+                    continue;
+                };
+                members.push(id);
+                memberships[id.0].push(index);
+            }
+
+            categories_by_name.insert(category._Name.clone(), index);
+            categories.push(Category {
+                name: category._Name,
+                optional: category._IsOptional.unwrap_or(false),
+                unpack_to: category._UnpackTo.filter(|path| !path.is_empty()),
+                incremental: category._IsIncremental.unwrap_or(false),
+                required: category._CatRequired.filter(|name| !name.is_empty()),
+                hidden: category._Hidden.unwrap_or(false),
+                files: members,
+                extra: category.extra,
+            });
+        }
+
+        Ok(ProductDescription {
+            files,
+            categories,
+            files_by_name,
+            categories_by_name,
+            memberships,
+        })
+    }
+}
+
+impl From<patch::CBNPFileVersion> for FileVersion {
+    fn from(version: patch::CBNPFileVersion) -> Self {
+        FileVersion {
+            number: version._VersionNumber,
+            size: version._FileSize,
+            packed_size: version._7ZFileSize,
+            time: version._FileTime,
+            patch_size: version._PatchSize,
+            hash_key: version._HashKey,
+            extra: version.extra,
+        }
+    }
+}
+
+// borrowed views that serialize with the layout and names of the raw patch structs
+
+#[derive(Serialize)]
+struct Document<'a> {
+    _Files: FileSet<'a>,
+    _Categories: CategorySet<'a>,
+}
+
+#[derive(Serialize)]
+struct FileSet<'a> {
+    _Files: &'a [FileEntry],
+}
+
+#[derive(Serialize)]
+struct CategorySet<'a> {
+    _Category: Vec<CategoryView<'a>>,
+}
+
+#[derive(Serialize)]
+struct CategoryView<'a> {
+    _Name: &'a str,
+    _IsOptional: bool,
+    _UnpackTo: Option<&'a str>,
+    _IsIncremental: bool,
+    _CatRequired: Option<&'a str>,
+    _Hidden: bool,
+    _Files: Vec<&'a str>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    extra: &'a [pd::Property],
+}
+
+impl Serialize for ProductDescription {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let categories = self
+            .categories
+            .iter()
+            .map(|category| CategoryView {
+                _Name: &category.name,
+                _IsOptional: category.optional,
+                _UnpackTo: category.unpack_to.as_deref(),
+                _IsIncremental: category.incremental,
+                _CatRequired: category.required.as_deref(),
+                _Hidden: category.hidden,
+                _Files: self
+                    .category_files(category)
+                    .map(|file| file.name.as_str())
+                    .collect(),
+                extra: &category.extra,
+            })
+            .collect();
+
+        Document {
+            _Files: FileSet {
+                _Files: &self.files,
+            },
+            _Categories: CategorySet {
+                _Category: categories,
+            },
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(category_files: &[&str]) -> patch::CProductDescriptionForClient {
+        patch::CProductDescriptionForClient {
+            _Files: patch::CBNPFileSet {
+                _Files: ["fauna_maps.bnp", "sounds.bnp"]
+                    .iter()
+                    .map(|name| patch::CBNPFile {
+                        _FileName: name.to_string(),
+                        _Versions: vec![patch::CBNPFileVersion {
+                            _VersionNumber: 1021,
+                            _FileSize: FileSize(100),
+                            _7ZFileSize: FileSize(50),
+                            _FileTime: FileTime(1700000000),
+                            _PatchSize: FileSize(10),
+                            _HashKey: vec![1, 2, 3, 4, 5],
+                            extra: Vec::new(),
+                        }],
+                        extra: Vec::new(),
+                    })
+                    .collect(),
+            },
+            _Categories: patch::CBNPCategorySet {
+                _Category: vec![patch::CBNPCategory {
+                    _Name: "sound".to_string(),
+                    _IsOptional: Some(true),
+                    _UnpackTo: Some(String::new()),
+                    _IsIncremental: None,
+                    _CatRequired: None,
+                    _Hidden: None,
+                    _Files: category_files.iter().map(|name| name.to_string()).collect(),
+                    extra: Vec::new(),
+                }],
+            },
+        }
+    }
+
+    #[test]
+    fn test_try_from() {
+        let product = ProductDescription::try_from(raw(&["sounds.bnp"])).unwrap();
+
+        let sound = product.category("sound").unwrap();
+        assert!(sound.optional);
+        assert!(!sound.hidden);
+        assert_eq!(None, sound.unpack_to);
+        let members: Vec<&str> = product
+            .category_files(sound)
+            .map(|file| file.name.as_str())
+            .collect();
+        assert_eq!(vec!["sounds.bnp"], members);
+
+        let id = product.file_id("sounds.bnp").unwrap();
+        assert_eq!(1, product.file_categories(id).count());
+        assert_eq!(
+            Some(1021),
+            product
+                .file("fauna_maps.bnp")
+                .and_then(|file| file.latest())
+                .map(|version| version.number)
+        );
+
+        assert_eq!(
+            Err(ValidationError::UnknownFile {
+                category: "sound".to_string(),
+                file: "music.bnp".to_string()
+            }),
+            ProductDescription::try_from(raw(&["music.bnp"]))
+        );
+    }
+
+    #[test]
+    fn test_serialize_nel_names() {
+        let product = ProductDescription::try_from(raw(&["sounds.bnp"])).unwrap();
+
+        let json = serde_json::to_value(&product).unwrap();
+
+        assert_eq!(
+            serde_json::json!(["sounds.bnp"]),
+            json["_Categories"]["_Category"][0]["_Files"]
+        );
+        assert_eq!(
+            serde_json::json!(false),
+            json["_Categories"]["_Category"][0]["_Hidden"]
+        );
+        assert_eq!(
+            serde_json::json!(50),
+            json["_Files"]["_Files"][0]["_Versions"][0]["_7ZFileSize"]
+        );
+    }
+
+    #[test]
+    fn test_repaired() {
+        let mut raw = raw(&["music.bnp", "sounds.bnp"]);
+        let version = raw._Files._Files[1]._Versions[0].clone();
+        raw._Files._Files[0]._Versions.insert(0, version);
+
+        let (product, repairs) = ProductDescription::repaired(raw);
+
+        assert_eq!(
+            vec![
+                ValidationError::UnorderedVersions("fauna_maps.bnp".to_string()),
+                ValidationError::UnknownFile {
+                    category: "sound".to_string(),
+                    file: "music.bnp".to_string()
+                },
+            ],
+            repairs
+        );
+        let sound = product.category("sound").unwrap();
+        assert_eq!(1, product.category_files(sound).count());
+    }
+}
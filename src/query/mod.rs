@@ -0,0 +1,147 @@
+mod parser;
+
+use crate::output::FileRow;
+use glob::Pattern;
+
+pub use parser::{parse, ParseError};
+
+/// A parsed filter expression, see [`parse`] for the syntax
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    /// Glob match against a text field, matches if any of the field's values matches
+    Matches(TextField, Pattern),
+    Compare(NumberField, Op, u64),
+    /// Inclusive range of a numeric field
+    InRange(NumberField, u64, u64),
+    Flag(FlagField),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextField {
+    Name,
+    Category,
+    UnpackTo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberField {
+    Version,
+    Size,
+    PackedSize,
+    PatchSize,
+    Time,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlagField {
+    Optional,
+    Hidden,
+    Incremental,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Expr {
+    pub fn matches(&self, row: &FileRow) -> bool {
+        match self {
+            Expr::And(left, right) => left.matches(row) && right.matches(row),
+            Expr::Or(left, right) => left.matches(row) || right.matches(row),
+            Expr::Not(inner) => !inner.matches(row),
+            Expr::Matches(field, pattern) => match field {
+                TextField::Name => pattern.matches(&row.name),
+                TextField::Category => row.categories.iter().any(|name| pattern.matches(name)),
+                TextField::UnpackTo => row
+                    .unpack_to
+                    .as_ref()
+                    .is_some_and(|path| pattern.matches(path)),
+            },
+            Expr::Compare(field, op, expected) => field.value(row).is_some_and(|actual| {
+                let actual = actual as u64;
+                match op {
+                    Op::Eq => actual == *expected,
+                    Op::Ne => actual != *expected,
+                    Op::Lt => actual < *expected,
+                    Op::Le => actual <= *expected,
+                    Op::Gt => actual > *expected,
+                    Op::Ge => actual >= *expected,
+                }
+            }),
+            Expr::InRange(field, from, to) => field
+                .value(row)
+                .is_some_and(|actual| (*from..=*to).contains(&(actual as u64))),
+            Expr::Flag(field) => match field {
+                FlagField::Optional => row.optional,
+                FlagField::Hidden => row.hidden,
+                FlagField::Incremental => row.incremental,
+            },
+        }
+    }
+}
+
+impl NumberField {
+    fn value(&self, row: &FileRow) -> Option<u32> {
+        match self {
+            NumberField::Version => row.version,
+            NumberField::Size => row.size.map(|size| size.0),
+            NumberField::PackedSize => row.packed_size.map(|size| size.0),
+            NumberField::PatchSize => row.patch_size.map(|size| size.0),
+            NumberField::Time => row.time.map(|time| time.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::FileSize;
+
+    fn row(name: &str, version: u32, size: u32, categories: &[&str]) -> FileRow {
+        FileRow {
+            name: name.to_string(),
+            version: Some(version),
+            size: Some(FileSize(size)),
+            packed_size: None,
+            patch_size: None,
+            time: None,
+            categories: categories.iter().map(|name| name.to_string()).collect(),
+            unpack_to: None,
+            optional: categories.contains(&"optional"),
+            hidden: false,
+            incremental: false,
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let rows = [
+            row("fauna_maps.bnp", 1021, 20_000_000, &["main"]),
+            row("fauna_shapes.bnp", 1019, 20_000_000, &["main"]),
+            row("ryzom_client.exe", 1025, 30_000_000, &["exe"]),
+            row("sounds.bnp", 1022, 5_000_000, &["main", "optional"]),
+        ];
+        let filter =
+            parse(r#"category = main and size > 10MB and version in 1020..1030 or name = "*.exe""#)
+                .unwrap();
+
+        let names: Vec<&str> = rows
+            .iter()
+            .filter(|row| filter.matches(row))
+            .map(|row| row.name.as_str())
+            .collect();
+
+        assert_eq!(vec!["fauna_maps.bnp", "ryzom_client.exe"], names);
+        assert!(parse("not optional").unwrap().matches(&rows[0]));
+        assert!(!parse("not optional").unwrap().matches(&rows[3]));
+    }
+}
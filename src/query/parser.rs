@@ -0,0 +1,388 @@
+use crate::patch::FileTime;
+use crate::query::{Expr, FlagField, NumberField, Op, TextField};
+use glob::Pattern;
+use std::fmt;
+
+/// Parses a filter expression.
+///
+/// ```text
+/// expr      := and ("or" and)*
+/// and       := unary ("and" unary)*
+/// unary     := "not" unary | "(" expr ")" | predicate
+/// predicate := text_field ("=" | "!=") glob
+///            | number_field ("=" | "!=" | "<" | "<=" | ">" | ">=") number
+///            | number_field "in" number ".." number
+///            | flag_field
+/// ```
+///
+/// Text fields are `name`, `category` and `unpack_to`, number fields are `version`, `size`,
+/// `packed_size`, `patch_size` and `time`, flag fields are `optional`, `hidden` and `incremental`.
+/// Sizes accept the units `KB`, `MB`, `GB` as well as `KiB`, `MiB` and `GiB`, times accept RFC 3339
+/// timestamps and `YYYY-MM-DD` dates, which are taken as midnight UTC.
+pub fn parse(input: &str) -> Result<Expr, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        position: 0,
+        end: input.len(),
+    };
+    let expr = parser.parse_or()?;
+    if let Some((token, position)) = parser.tokens.get(parser.position) {
+        return Err(ParseError::new(format!("unexpected {}", token), *position));
+    }
+
+    Ok(expr)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl ParseError {
+    fn new(message: String, position: usize) -> Self {
+        ParseError { message, position }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Op(Op),
+    Word(String),
+    Quoted(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+            Token::Op(op) => write!(f, "operator {:?}", op),
+            Token::Word(word) => write!(f, "'{}'", word),
+            Token::Quoted(text) => write!(f, "\"{}\"", text),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(position, c)) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '(' => {
+                chars.next();
+                Token::LParen
+            }
+            ')' => {
+                chars.next();
+                Token::RParen
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let equals = chars.next_if(|&(_, c)| c == '=').is_some();
+                Token::Op(match (c, equals) {
+                    ('=', _) => Op::Eq,
+                    ('!', true) => Op::Ne,
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    ('>', false) => Op::Gt,
+                    ('>', true) => Op::Ge,
+                    _ => return Err(ParseError::new("expected '!='".to_string(), position)),
+                })
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, quote)) if quote == c => break,
+                        Some((_, other)) => text.push(other),
+                        None => {
+                            return Err(ParseError::new(
+                                "unterminated string".to_string(),
+                                position,
+                            ))
+                        }
+                    }
+                }
+                Token::Quoted(text)
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some((_, c)) =
+                    chars.next_if(|&(_, c)| !c.is_whitespace() && !"()=!<>\"'".contains(c))
+                {
+                    word.push(c);
+                }
+                Token::Word(word)
+            }
+        };
+        tokens.push((token, position));
+    }
+
+    Ok(tokens)
+}
+
+enum Field {
+    Text(TextField),
+    Number(NumberField),
+    Flag(FlagField),
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Field> {
+        Some(match name {
+            "name" => Field::Text(TextField::Name),
+            "category" => Field::Text(TextField::Category),
+            "unpack_to" => Field::Text(TextField::UnpackTo),
+            "version" => Field::Number(NumberField::Version),
+            "size" => Field::Number(NumberField::Size),
+            "packed_size" => Field::Number(NumberField::PackedSize),
+            "patch_size" => Field::Number(NumberField::PatchSize),
+            "time" => Field::Number(NumberField::Time),
+            "optional" => Field::Flag(FlagField::Optional),
+            "hidden" => Field::Flag(FlagField::Hidden),
+            "incremental" => Field::Flag(FlagField::Incremental),
+            _ => return None,
+        })
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.end, |&(_, position)| position)
+    }
+
+    fn next(&mut self) -> Result<(Token, usize), ParseError> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or_else(|| ParseError::new("unexpected end of filter".to_string(), self.end))?;
+        self.position += 1;
+
+        Ok(token)
+    }
+
+    fn next_if_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.parse_and()?;
+        while self.next_if_keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.parse_unary()?;
+        while self.next_if_keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+        if self.next_if_keyword("not") {
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        if let Some(Token::LParen) = self.peek() {
+            self.position += 1;
+            let expr = self.parse_or()?;
+            return match self.next()? {
+                (Token::RParen, _) => Ok(expr),
+                (token, position) => Err(ParseError::new(
+                    format!("expected ')' but found {}", token),
+                    position,
+                )),
+            };
+        }
+
+        self.parse_predicate()
+    }
+
+    fn parse_predicate(&mut self) -> Result<Expr, ParseError> {
+        let (token, position) = self.next()?;
+        let field = match &token {
+            Token::Word(name) => Field::from_name(name),
+            _ => None,
+        }
+        .ok_or_else(|| ParseError::new(format!("expected field but found {}", token), position))?;
+
+        match field {
+            Field::Flag(field) => Ok(Expr::Flag(field)),
+            Field::Text(field) => {
+                let op = self.parse_op()?;
+                let (value, position) = self.parse_value()?;
+                let pattern = Pattern::new(&value)
+                    .map_err(|e| ParseError::new(format!("invalid glob: {}", e.msg), position))?;
+                match op {
+                    Op::Eq => Ok(Expr::Matches(field, pattern)),
+                    Op::Ne => Ok(Expr::Not(Box::new(Expr::Matches(field, pattern)))),
+                    _ => Err(ParseError::new(
+                        "text fields only support '=' and '!='".to_string(),
+                        position,
+                    )),
+                }
+            }
+            Field::Number(field) => {
+                if self.next_if_keyword("in") {
+                    let (value, position) = self.parse_value()?;
+                    let (from, to) = value.split_once("..").ok_or_else(|| {
+                        ParseError::new("expected range like 1020..1030".to_string(), position)
+                    })?;
+                    return Ok(Expr::InRange(
+                        field,
+                        parse_field_value(field, from, position)?,
+                        parse_field_value(field, to, position)?,
+                    ));
+                }
+                let op = self.parse_op()?;
+                let (value, position) = self.parse_value()?;
+                let mut number = parse_field_value(field, &value, position)?;
+                // the unit may be separated by a space, e.g. `size > 10 MB`
+                if let Some(Token::Word(unit)) = self.peek() {
+                    if let Some(factor) = unit_factor(unit) {
+                        number = scale(number, factor, position)?;
+                        self.position += 1;
+                    }
+                }
+                Ok(Expr::Compare(field, op, number))
+            }
+        }
+    }
+
+    fn parse_op(&mut self) -> Result<Op, ParseError> {
+        match self.next()? {
+            (Token::Op(op), _) => Ok(op),
+            (token, position) => Err(ParseError::new(
+                format!("expected operator but found {}", token),
+                position,
+            )),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<(String, usize), ParseError> {
+        let position = self.offset();
+        match self.next()? {
+            (Token::Word(value), _) | (Token::Quoted(value), _) => Ok((value, position)),
+            (token, position) => Err(ParseError::new(
+                format!("expected value but found {}", token),
+                position,
+            )),
+        }
+    }
+}
+
+fn unit_factor(unit: &str) -> Option<u64> {
+    Some(match unit.to_ascii_lowercase().as_str() {
+        "b" => 1,
+        "k" | "kb" => 1_000,
+        "m" | "mb" => 1_000_000,
+        "g" | "gb" => 1_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        _ => return None,
+    })
+}
+
+/// Times may also be given as RFC 3339 timestamp or `YYYY-MM-DD` date
+fn parse_field_value(field: NumberField, value: &str, position: usize) -> Result<u64, ParseError> {
+    if field == NumberField::Time {
+        if let Some(time) = FileTime::parse(value) {
+            return Ok(time.0 as u64);
+        }
+    }
+
+    parse_number(value, position)
+}
+
+fn parse_number(value: &str, position: usize) -> Result<u64, ParseError> {
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(digits);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| ParseError::new(format!("expected number but found '{}'", value), position))?;
+    if unit.is_empty() {
+        return Ok(number);
+    }
+
+    let factor = unit_factor(unit)
+        .ok_or_else(|| ParseError::new(format!("unknown unit '{}'", unit), position))?;
+
+    scale(number, factor, position)
+}
+
+fn scale(number: u64, factor: u64, position: usize) -> Result<u64, ParseError> {
+    number
+        .checked_mul(factor)
+        .ok_or_else(|| ParseError::new("number too large".to_string(), position))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Ok(Expr::Or(
+                Box::new(Expr::And(
+                    Box::new(Expr::Compare(NumberField::Size, Op::Gt, 10_000_000)),
+                    Box::new(Expr::Not(Box::new(Expr::Flag(FlagField::Optional)))),
+                )),
+                Box::new(Expr::InRange(NumberField::Version, 1020, 1030)),
+            )),
+            parse("size > 10 MB and not optional or version in 1020..1030")
+        );
+        assert_eq!(
+            Ok(Expr::InRange(NumberField::Time, 1704067200, 1719705600)),
+            parse("time in 2024-01-01..2024-06-30")
+        );
+        assert_eq!(
+            Err(ParseError::new(
+                "expected operator but found 'main'".to_string(),
+                9
+            )),
+            parse("category main")
+        );
+    }
+}
@@ -0,0 +1,377 @@
+use crate::error::ReadingError;
+use crate::install;
+use crate::patch;
+use crate::pd;
+use crate::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The kinds of PDR files the cli can read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Schema {
+    /// Detect the schema from the names of the root properties
+    Auto,
+    /// Patch index of the client, `CProductDescriptionForClient`
+    Product,
+    /// Package description of the patch_gen tool, `CPackageDescription`
+    Package,
+    /// The patcher's local version file, `CLocalVersion`
+    LocalVersion,
+    /// The patcher's state file of an interrupted patch, `CPatchState`
+    PatchState,
+    /// A client config file stored as PDR, `CClientConfig`
+    ClientConfig,
+    /// Any PDR as a tree of named properties with their stored types
+    Generic,
+}
+
+/// Root properties only the package description has
+const PACKAGE_ROOTS: [&str; 5] = [
+    "_IndexFileName",
+    "_PatchDirectory",
+    "_BnpDirectory",
+    "_RefDirectory",
+    "_NextVersionFile",
+];
+
+/// A PDR file read with one of the schemas
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum Document {
+    Product(patch::CProductDescriptionForClient),
+    Package(patch::CPackageDescription),
+    LocalVersion(install::CLocalVersion),
+    PatchState(install::CPatchState),
+    ClientConfig(install::CClientConfig),
+    Generic { properties: Vec<pd::Property> },
+}
+
+/// A document as printed, with the format version of the file it was read from
+#[derive(Debug, Serialize)]
+pub struct Dump<'a> {
+    pub format_version: u32,
+    #[serde(flatten)]
+    pub document: &'a Document,
+}
+
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Schema::Auto => "auto detected",
+            Schema::Product => "product description",
+            Schema::Package => "package description",
+            Schema::LocalVersion => "local version",
+            Schema::PatchState => "patch state",
+            Schema::ClientConfig => "client config",
+            Schema::Generic => "generic",
+        })
+    }
+}
+
+impl Schema {
+    /// The schema whose root properties match, `Generic` if none does
+    pub fn detect(pdr: &pd::PersistentDataRecord) -> Schema {
+        let roots = root_names(pdr);
+        // the local version names its index like a package description does
+        if roots.contains(&"_InstalledVersion") {
+            Schema::LocalVersion
+        } else if roots.iter().any(|name| PACKAGE_ROOTS.contains(name)) {
+            Schema::Package
+        } else if roots.contains(&"_Files") {
+            Schema::Product
+        } else if roots.contains(&"_TargetVersion") {
+            Schema::PatchState
+        } else if roots.contains(&"_Variables") {
+            Schema::ClientConfig
+        } else {
+            Schema::Generic
+        }
+    }
+
+    /// The schema whose root fields a JSON document has, a product description unless another
+    /// schema matches so its errors name the missing or mistyped field
+    pub fn detect_json(value: &serde_json::Value) -> Schema {
+        let has = |name: &str| value.get(name).is_some();
+        if has("properties") {
+            Schema::Generic
+        } else if has("_InstalledVersion") {
+            Schema::LocalVersion
+        } else if PACKAGE_ROOTS.iter().any(|&name| has(name)) {
+            Schema::Package
+        } else if has("_TargetVersion") {
+            Schema::PatchState
+        } else if has("_Variables") {
+            Schema::ClientConfig
+        } else {
+            Schema::Product
+        }
+    }
+
+    /// Resolves `Auto` by looking at the fields of a JSON document
+    pub fn resolve_json(self, value: &serde_json::Value) -> Schema {
+        match self {
+            Schema::Auto => Schema::detect_json(value),
+            schema => schema,
+        }
+    }
+
+    /// Resolves `Auto` by looking at the record
+    pub fn resolve(self, pdr: &pd::PersistentDataRecord) -> Schema {
+        match self {
+            Schema::Auto => Schema::detect(pdr),
+            schema => schema,
+        }
+    }
+}
+
+/// The fields of a generic document in JSON
+#[derive(Deserialize)]
+struct Generic {
+    properties: Vec<pd::Property>,
+}
+
+impl Document {
+    pub fn schema(&self) -> Schema {
+        match *self {
+            Document::Product(..) => Schema::Product,
+            Document::Package(..) => Schema::Package,
+            Document::LocalVersion(..) => Schema::LocalVersion,
+            Document::PatchState(..) => Schema::PatchState,
+            Document::ClientConfig(..) => Schema::ClientConfig,
+            Document::Generic { .. } => Schema::Generic,
+        }
+    }
+}
+
+/// Reads the record with the given schema, or the detected one for `Auto`
+pub fn read(pdr: &mut pd::PersistentDataRecord, schema: Schema) -> Result<Document> {
+    Ok(match schema.resolve(pdr) {
+        Schema::Product => Document::Product(patch::CProductDescriptionForClient::from(pdr)?),
+        Schema::Package => Document::Package(pd::Readable::read(pdr)?),
+        Schema::LocalVersion => Document::LocalVersion(pd::Readable::read(pdr)?),
+        Schema::PatchState => Document::PatchState(pd::Readable::read(pdr)?),
+        Schema::ClientConfig => Document::ClientConfig(pd::Readable::read(pdr)?),
+        Schema::Auto | Schema::Generic => Document::Generic {
+            properties: pdr.read_properties()?,
+        },
+    })
+}
+
+/// Reads a document as printed by the cli, `Auto` detects the schema from the root fields. The
+/// `format_version` of the dump has to be a known one, the document is written with the current.
+pub fn from_json(json: &str, schema: Schema) -> Result<Document> {
+    let value: serde_json::Value = serde_json::from_str(json).map_err(ReadingError::InvalidJson)?;
+    if let Some(version) = value.get("format_version") {
+        let version = u32::deserialize(version).map_err(ReadingError::InvalidJson)?;
+        if pd::format_version(version).is_none() {
+            return Err(ReadingError::UnsupportedVersion(version));
+        }
+    }
+
+    // parsed again from the text so errors carry the line and column
+    let document = match schema.resolve_json(&value) {
+        Schema::Package => serde_json::from_str(json).map(Document::Package),
+        Schema::LocalVersion => serde_json::from_str(json).map(Document::LocalVersion),
+        Schema::PatchState => serde_json::from_str(json).map(Document::PatchState),
+        Schema::ClientConfig => serde_json::from_str(json).map(Document::ClientConfig),
+        Schema::Generic => serde_json::from_str(json).map(|generic: Generic| Document::Generic {
+            properties: generic.properties,
+        }),
+        Schema::Auto | Schema::Product => serde_json::from_str(json).map(Document::Product),
+    };
+
+    document.map_err(ReadingError::InvalidJson)
+}
+
+/// Encodes a document as a version 0 PDR file, `writer` decides how its strings are encoded
+pub fn write(document: &Document, mut writer: pd::Writer) -> Result<Vec<u8>> {
+    write_properties(document, &mut writer);
+
+    writer.into_bytes()
+}
+
+/// Encodes a document as a PDR file in `file_encoding`, `writer` is only used for binary files,
+/// XML and text are written as UTF-8
+pub fn write_as(
+    document: &Document,
+    writer: pd::Writer,
+    file_encoding: pd::FileEncoding,
+) -> Result<Vec<u8>> {
+    if file_encoding == pd::FileEncoding::Binary {
+        return write(document, writer);
+    }
+
+    // typed documents are turned into the properties they are stored as
+    let mut writer = pd::Writer::default();
+    write_properties(document, &mut writer);
+    let properties = writer.into_record()?.read_properties()?;
+    let text = match file_encoding {
+        pd::FileEncoding::Xml => pd::write_xml(&properties)?,
+        pd::FileEncoding::Text => pd::write_text(&properties)?,
+        pd::FileEncoding::Binary => unreachable!("binary files are written from their tables"),
+    };
+
+    Ok(text.into_bytes())
+}
+
+fn write_properties(document: &Document, writer: &mut pd::Writer) {
+    match *document {
+        Document::Product(ref product) => pd::Writable::write(product, writer),
+        Document::Package(ref package) => pd::Writable::write(package, writer),
+        Document::LocalVersion(ref version) => pd::Writable::write(version, writer),
+        Document::PatchState(ref state) => pd::Writable::write(state, writer),
+        Document::ClientConfig(ref config) => pd::Writable::write(config, writer),
+        Document::Generic { ref properties } => writer.write_extra(properties),
+    }
+}
+
+/// Names of the properties outside of any struct, in file order
+fn root_names<'p>(pdr: &'p pd::PersistentDataRecord) -> Vec<&'p str> {
+    let mut names = Vec::new();
+    let mut depth = 0usize;
+    for &token in &pdr.tokens {
+        match token {
+            pd::Tokens::BEGIN_TOKEN(_) => {
+                if depth == 0 {
+                    names.push(pdr.name(token));
+                }
+                depth += 1;
+            }
+            pd::Tokens::END_TOKEN(_) => depth = depth.saturating_sub(1),
+            // the extended token that follows carries the same name
+            pd::Tokens::EXTEND_TOKEN(_) => {}
+            _ if depth == 0 => names.push(pdr.name(token)),
+            _ => {}
+        }
+    }
+
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pd::Tokens::*;
+
+    #[test]
+    fn test_detect() {
+        let strings = vec!["_Categories", "_IndexFileName", "ryzom.idx", "_Files"];
+        let package = pd::PersistentDataRecord::new(
+            vec![BEGIN_TOKEN(0), END_TOKEN(0), STRING_TOKEN(1)],
+            vec![2],
+            strings.clone(),
+        );
+        let product = pd::PersistentDataRecord::new(
+            vec![BEGIN_TOKEN(3), END_TOKEN(3), BEGIN_TOKEN(0), END_TOKEN(0)],
+            vec![],
+            strings.clone(),
+        );
+        let mut generic = pd::PersistentDataRecord::new(
+            vec![BEGIN_TOKEN(0), STRING_TOKEN(3), END_TOKEN(0)],
+            vec![2],
+            strings,
+        );
+
+        let config = pd::PersistentDataRecord::new(
+            vec![BEGIN_TOKEN(0), END_TOKEN(0)],
+            vec![],
+            vec!["_Variables"],
+        );
+
+        assert_eq!(Schema::Package, Schema::detect(&package));
+        assert_eq!(Schema::Product, Schema::detect(&product));
+        assert_eq!(Schema::ClientConfig, Schema::detect(&config));
+        let json = serde_json::json!({"_InstalledVersion": 1028, "_IndexFileName": "ryzom.idx"});
+        assert_eq!(Schema::LocalVersion, Schema::detect_json(&json));
+        let Document::Generic { properties } = read(&mut generic, Schema::Auto).unwrap() else {
+            panic!("expected a generic document");
+        };
+        assert_eq!(
+            serde_json::json!([{"name": "_Categories", "value": {"struct": [
+                {"name": "_Files", "value": {"string": "ryzom.idx"}}
+            ]}}]),
+            serde_json::to_value(properties).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_from_json_round_trip() {
+        let json = serde_json::json!({
+            "_Files": {"_Files": [{
+                "_FileName": "fauna_maps.bnp",
+                "_Versions": [{
+                    "_VersionNumber": 1021,
+                    "_FileSize": 2048,
+                    "_7ZFileSize": 1024,
+                    "_FileTime": 1700000000,
+                    "_PatchSize": 512,
+                    "_HashKey": [1, 2, 3, 4, 5],
+                }],
+                "extra": [{"name": "_Mirror", "value": {"uint64": 1u64 << 40}}],
+            }]},
+            "_Categories": {"_Category": [{
+                "_Name": "main",
+                "_IsOptional": false,
+                "_UnpackTo": null,
+                "_IsIncremental": null,
+                "_CatRequired": null,
+                "_Hidden": null,
+                "_Files": ["fauna_maps.bnp"],
+            }]},
+        });
+        let document = from_json(&json.to_string(), Schema::Auto).unwrap();
+        assert_eq!(Schema::Product, document.schema());
+
+        let data = write(&document, pd::Writer::default()).unwrap();
+        let mut pdr =
+            pd::read_index_file(&data, false, pd::Limits::default(), pd::Encoding::Utf8).unwrap();
+        let read = read(&mut pdr, Schema::Auto).unwrap();
+
+        assert_eq!(json, serde_json::to_value(read).unwrap());
+    }
+
+    #[test]
+    fn test_from_json_errors() {
+        let json = r#"{"format_version": 0, "_Files": {"_Files": [
+            {"_Filename": "fauna_maps.bnp", "_Versions": []}
+        ]}, "_Categories": {"_Category": []}}"#;
+        let error = from_json(json, Schema::Auto).unwrap_err();
+        assert_eq!(
+            "Invalid JSON document: missing field `_FileName` at line 2 column 60",
+            error.to_string()
+        );
+        assert!(from_json(json, Schema::Generic).is_err());
+
+        let error = from_json(r#"{"format_version": 3, "properties": []}"#, Schema::Auto);
+        assert!(matches!(error, Err(ReadingError::UnsupportedVersion(3))));
+        let document = from_json(r#"{"format_version": 0, "properties": []}"#, Schema::Auto);
+        assert_eq!(Schema::Generic, document.unwrap().schema());
+    }
+
+    #[test]
+    fn test_latin1_names_round_trip() {
+        let properties = vec![pd::Property {
+            name: "_FileName".to_string(),
+            value: pd::Value::String("café.bnp".to_string()),
+        }];
+        let document = Document::Generic { properties };
+        let writer =
+            pd::Writer::with_encoding(pd::Encoding::Latin1, pd::OriginalStrings::default());
+        let data = write(&document, writer).unwrap();
+        assert!(data.windows(9).any(|bytes| bytes == b"caf\xe9.bnp\0"));
+
+        let limits = pd::Limits::default();
+        let error = pd::read_index_file(&data, false, limits, pd::Encoding::Utf8).unwrap_err();
+        assert!(matches!(
+            error.unlocated(),
+            crate::error::ReadingError::InvalidString(..)
+        ));
+
+        let mut pdr = pd::read_index_file(&data, false, limits, pd::Encoding::Lossy).unwrap();
+        let read = read(&mut pdr, Schema::Generic).unwrap();
+        let writer = pd::Writer::with_encoding(pd::Encoding::Utf8, pdr.originals);
+
+        assert_eq!(data, write(&read, writer).unwrap());
+    }
+}
@@ -0,0 +1,304 @@
+use crate::changelog::download_size;
+use crate::patch::FileSize;
+use crate::{edit, product};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+
+/// Directory of the install where the Ryzom client keeps the files of categories without an
+/// `UnpackTo` path
+pub const DATA_DIRECTORY: &str = "data";
+
+/// A file of the install that is missing or not at its latest version
+#[derive(Debug, Clone, PartialEq)]
+pub struct Update {
+    pub file: product::FileId,
+    pub path: PathBuf,
+    /// The version found on disk, `None` when the file is missing or matches no version
+    pub from: Option<u32>,
+    pub to: u32,
+    /// Bytes to download, the patches from `from` or the packed file
+    pub download: FileSize,
+}
+
+/// An installed file whose SHA-1 hash is not the one of its latest version
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub file: product::FileId,
+    pub path: PathBuf,
+    /// The older version the file matches, `None` when it matches none and is damaged
+    pub version: Option<u32>,
+}
+
+#[derive(Debug)]
+pub enum PlanError {
+    /// The progress callback asked to stop
+    Cancelled,
+    Io(PathBuf, io::Error),
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PlanError::Cancelled => write!(f, "the check of the install was cancelled"),
+            PlanError::Io(ref path, ref cause) => write!(f, "{}: {}", path.display(), cause),
+        }
+    }
+}
+
+impl Error for PlanError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            PlanError::Io(_, ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Where a file is installed: the `UnpackTo` path of its first category that has one, relative
+/// to the install directory, or [`DATA_DIRECTORY`]
+pub fn install_path(
+    product: &product::ProductDescription,
+    id: product::FileId,
+    install_dir: &Path,
+) -> PathBuf {
+    let directory = product
+        .file_categories(id)
+        .find_map(|category| category.unpack_to.as_deref())
+        .unwrap_or(DATA_DIRECTORY);
+
+    install_dir.join(directory).join(&product.entry(id).name)
+}
+
+/// Compares the install with the latest versions of the index. A local file is recognized by its
+/// SHA-1 hash, which is only computed when its size matches a version. Files that are only in
+/// optional categories are skipped unless `optional` is set.
+///
+/// `progress` is called before every file with the number of files checked, their total and the
+/// name of the file, it stops the plan by returning [`ControlFlow::Break`].
+pub fn plan(
+    product: &product::ProductDescription,
+    install_dir: &Path,
+    optional: bool,
+    mut progress: impl FnMut(usize, usize, &str) -> ControlFlow<()>,
+) -> Result<Vec<Update>, PlanError> {
+    let files = installed_files(product, optional);
+
+    let mut updates = Vec::new();
+    for (done, &(id, file)) in files.iter().enumerate() {
+        if progress(done, files.len(), &file.name).is_break() {
+            return Err(PlanError::Cancelled);
+        }
+        let latest = file.latest().expect("files without versions are skipped");
+        let path = install_path(product, id, install_dir);
+        let installed =
+            installed_version(file, &path).map_err(|e| PlanError::Io(path.clone(), e))?;
+        if installed.is_some_and(|version| version.number == latest.number) {
+            continue;
+        }
+
+        updates.push(Update {
+            file: id,
+            path,
+            from: installed.map(|version| version.number),
+            to: latest.number,
+            download: download_size(file, installed),
+        });
+    }
+    if progress(files.len(), files.len(), "").is_break() {
+        return Err(PlanError::Cancelled);
+    }
+
+    Ok(updates)
+}
+
+/// Hashes every installed file, whatever its size, and lists those that are not their latest
+/// version. Missing files are left to [`plan`], `optional` and `progress` work as there.
+pub fn verify(
+    product: &product::ProductDescription,
+    install_dir: &Path,
+    optional: bool,
+    mut progress: impl FnMut(usize, usize, &str) -> ControlFlow<()>,
+) -> Result<Vec<Mismatch>, PlanError> {
+    let files = installed_files(product, optional);
+
+    let mut mismatches = Vec::new();
+    for (done, &(id, file)) in files.iter().enumerate() {
+        if progress(done, files.len(), &file.name).is_break() {
+            return Err(PlanError::Cancelled);
+        }
+        let latest = file.latest().expect("files without versions are skipped");
+        let path = install_path(product, id, install_dir);
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+            Err(error) => return Err(PlanError::Io(path, error)),
+        };
+        let hash_key = edit::hash_key(&data);
+        if latest.hash_key == hash_key {
+            continue;
+        }
+
+        mismatches.push(Mismatch {
+            file: id,
+            path,
+            version: file
+                .versions
+                .iter()
+                .rev()
+                .find(|version| version.hash_key == hash_key)
+                .map(|version| version.number),
+        });
+    }
+    if progress(files.len(), files.len(), "").is_break() {
+        return Err(PlanError::Cancelled);
+    }
+
+    Ok(mismatches)
+}
+
+/// The files with a version that are in a required category, or in none, unless `optional`
+fn installed_files(
+    product: &product::ProductDescription,
+    optional: bool,
+) -> Vec<(product::FileId, &product::FileEntry)> {
+    product
+        .entries()
+        .filter(|&(id, file)| {
+            let mut categories = product.file_categories(id).peekable();
+            file.latest().is_some()
+                && (optional
+                    || categories.peek().is_none()
+                    || categories.any(|category| !category.optional))
+        })
+        .collect()
+}
+
+/// The latest version whose size and hash match the file on disk
+fn installed_version<'a>(
+    file: &'a product::FileEntry,
+    path: &Path,
+) -> io::Result<Option<&'a product::FileVersion>> {
+    let size = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    if !file
+        .versions
+        .iter()
+        .any(|version| version.size.0 as u64 == size)
+    {
+        return Ok(None);
+    }
+
+    let hash_key = edit::hash_key(&fs::read(path)?);
+    Ok(file
+        .versions
+        .iter()
+        .rev()
+        .find(|version| version.size.0 as u64 == size && version.hash_key == hash_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::{self, fixture};
+
+    fn version(number: u32, data: &[u8], patch_size: u32) -> patch::CBNPFileVersion {
+        patch::CBNPFileVersion {
+            _7ZFileSize: FileSize(100),
+            _HashKey: edit::hash_key(data),
+            ..fixture::version(number, data.len() as u32, patch_size)
+        }
+    }
+
+    #[test]
+    fn test_plan() {
+        let file =
+            |name: &str| fixture::file(name, vec![version(1, b"old", 0), version(2, b"newer", 10)]);
+        let category = |name: &str, optional: bool, unpack_to: Option<&str>, files: &[&str]| {
+            patch::CBNPCategory {
+                _IsOptional: Some(optional),
+                _UnpackTo: unpack_to.map(str::to_string),
+                ..fixture::category(name, files)
+            }
+        };
+        let product = product::ProductDescription::try_from(fixture::product(
+            vec![
+                file("current.bnp"),
+                file("old.bnp"),
+                file("client"),
+                file("music.bnp"),
+            ],
+            vec![
+                category("main", false, None, &["current.bnp", "old.bnp"]),
+                category("exe", false, Some("bin"), &["client"]),
+                category("music", true, None, &["music.bnp"]),
+            ],
+        ))
+        .unwrap();
+
+        let install_dir = std::env::temp_dir().join(format!("update-{}", std::process::id()));
+        fs::create_dir_all(install_dir.join(DATA_DIRECTORY)).unwrap();
+        fs::write(install_dir.join("data/current.bnp"), b"newer").unwrap();
+        fs::write(install_dir.join("data/old.bnp"), b"old").unwrap();
+
+        let mut calls = Vec::new();
+        let updates = plan(&product, &install_dir, false, |done, total, name| {
+            calls.push((done, total, name.to_string()));
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        let summary: Vec<_> = updates
+            .iter()
+            .map(|update| (update.path.clone(), update.from, update.to, update.download))
+            .collect();
+        assert_eq!(
+            vec![
+                (install_dir.join("data/old.bnp"), Some(1), 2, FileSize(10)),
+                (install_dir.join("bin/client"), None, 2, FileSize(100)),
+            ],
+            summary
+        );
+        assert_eq!(4, calls.len());
+        assert_eq!((3, 3, String::new()), calls[3]);
+
+        assert_eq!(
+            3,
+            plan(&product, &install_dir, true, |_, _, _| {
+                ControlFlow::Continue(())
+            })
+            .unwrap()
+            .len()
+        );
+        assert!(matches!(
+            plan(&product, &install_dir, false, |_, _, _| ControlFlow::Break(
+                ()
+            )),
+            Err(PlanError::Cancelled)
+        ));
+
+        // damaged with the size of its latest version, and one at an older version
+        fs::write(install_dir.join("data/current.bnp"), b"nexer").unwrap();
+        let mismatches = verify(&product, &install_dir, false, |_, _, _| {
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        let summary: Vec<_> = mismatches
+            .iter()
+            .map(|mismatch| (mismatch.path.clone(), mismatch.version))
+            .collect();
+        assert_eq!(
+            vec![
+                (install_dir.join("data/current.bnp"), None),
+                (install_dir.join("data/old.bnp"), Some(1)),
+            ],
+            summary
+        );
+        fs::remove_dir_all(install_dir).unwrap();
+    }
+}
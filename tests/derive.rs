@@ -0,0 +1,46 @@
+//! The derive macros used from outside of the crate, through its public paths.
+
+use ryzom_patch_info as rpi;
+use ryzom_patch_info::pd::{self, PdrRead, PdrWrite};
+
+#[derive(Debug, PartialEq, PdrRead, PdrWrite)]
+struct Mirror {
+    #[pdr(rename = "_Url")]
+    url: String,
+    #[pdr(rename = "_Weight", default)]
+    weight: u32,
+    #[pdr(rename = "_Shard", repeated)]
+    shards: Vec<String>,
+    #[pdr(extra)]
+    extra: Vec<pd::Property>,
+}
+
+#[derive(Debug, PartialEq, PdrRead, PdrWrite)]
+#[pdr(crate = "rpi")]
+struct Renamed {
+    #[pdr(rename = "_Name")]
+    name: String,
+}
+
+#[test]
+fn test_derive_round_trip() {
+    let mirror = Mirror {
+        url: "https://cdn.ryzom.com".to_string(),
+        weight: 10,
+        shards: vec!["atys".to_string(), "yubo".to_string()],
+        extra: vec![pd::Property {
+            name: "_Backup".to_string(),
+            value: pd::Value::Uint32(1),
+        }],
+    };
+    let renamed = Renamed {
+        name: "ryzom".to_string(),
+    };
+    let mut writer = pd::Writer::default();
+    writer.write("_Mirror", &mirror);
+    writer.write("_Renamed", &renamed);
+    let mut record = writer.into_record().unwrap();
+
+    assert_eq!(mirror, record.read::<Mirror>("_Mirror").unwrap());
+    assert_eq!(renamed, record.read::<Renamed>("_Renamed").unwrap());
+}